@@ -0,0 +1,164 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How much of a file is looked at to decide whether it is text.
+pub const SNIFF_LEN: usize = 8 * 1024;
+pub const BYTES_PER_ROW: usize = 16;
+const SEARCH_CHUNK: usize = 1024 * 1024;
+
+/// Guesses from the first bytes of a file whether it is binary: any NUL byte
+/// outside of UTF-16 text, or a large share of control characters.
+pub fn is_binary(sample: &[u8]) -> bool {
+    if sample.starts_with(&[0xFF, 0xFE]) || sample.starts_with(&[0xFE, 0xFF]) {
+        return false;
+    }
+    if sample.contains(&0) {
+        return true;
+    }
+    let control = sample
+        .iter()
+        .filter(|byte| byte.is_ascii_control() && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B))
+        .count();
+    control * 10 > sample.len()
+}
+
+/// Parses an offset typed by the user: `0x1f00`, `1f00h` or plain decimal.
+pub fn parse_offset(input: &str) -> Option<u64> {
+    let input = input.trim().replace('_', "");
+    if let Some(hex) = input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(hex) = input.strip_suffix(['h', 'H']) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        input.parse().ok()
+    }
+}
+
+/// Parses a search pattern: hex bytes like `89 50 4E 47` (spaces optional),
+/// or text in double quotes like `"IHDR"`.
+pub fn parse_pattern(input: &str) -> Option<Vec<u8>> {
+    let input = input.trim();
+    if let Some(text) = input.strip_prefix('"') {
+        let text = text.strip_suffix('"').unwrap_or(text);
+        return (!text.is_empty()).then(|| text.as_bytes().to_vec());
+    }
+    let digits = input.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|ix| u8::from_str_radix(digits.get(ix..ix + 2)?, 16).ok())
+        .collect()
+}
+
+/// A binary file read a row at a time for the hex view.
+pub struct HexFile {
+    file: Mutex<File>,
+    len: u64,
+}
+
+impl HexFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            file: Mutex::new(file),
+            len,
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.len.div_ceil(BYTES_PER_ROW as u64) as usize
+    }
+
+    pub fn read(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = Vec::with_capacity(len);
+        file.by_ref().take(len as u64).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Finds the first occurrence of `pattern` at or after `from`, wrapping
+    /// around to the start once. Stops early when `cancelled` is set.
+    pub fn find(&self, pattern: &[u8], from: u64, cancelled: &AtomicBool) -> io::Result<Option<u64>> {
+        if let Some(found) = self.find_in(pattern, from, self.len, cancelled)? {
+            return Ok(Some(found));
+        }
+        let wrap_end = (from + pattern.len() as u64).min(self.len);
+        self.find_in(pattern, 0, wrap_end, cancelled)
+    }
+
+    fn find_in(&self, pattern: &[u8], start: u64, end: u64, cancelled: &AtomicBool) -> io::Result<Option<u64>> {
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+        // Chunks overlap by one byte less than the pattern so matches that
+        // straddle a boundary are still seen.
+        let mut offset = start;
+        while offset < end {
+            if cancelled.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+            let len = SEARCH_CHUNK.min((end - offset) as usize);
+            let chunk = self.read(offset, len)?;
+            if let Some(ix) = chunk.windows(pattern.len()).position(|window| window == pattern) {
+                return Ok(Some(offset + ix as u64));
+            }
+            if chunk.len() < pattern.len() || offset + chunk.len() as u64 >= end {
+                break;
+            }
+            offset += (chunk.len() - (pattern.len() - 1)) as u64;
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_binary_data() {
+        assert!(!is_binary(b"fn main() {}\n\tprintln!();\r\n"));
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!is_binary(&[0xFF, 0xFE, b'h', 0, b'i', 0]));
+        assert!(is_binary(&[1, 2, 3, 4, 5, b'a']));
+    }
+
+    #[test]
+    fn parses_offsets_and_patterns() {
+        assert_eq!(parse_offset("0x1F"), Some(31));
+        assert_eq!(parse_offset("20h"), Some(32));
+        assert_eq!(parse_offset("1_000"), Some(1000));
+        assert_eq!(parse_offset("zz"), None);
+        assert_eq!(parse_pattern("de AD be ef"), Some(vec![0xDE, 0xAD, 0xBE, 0xEF]));
+        assert_eq!(parse_pattern("\"PNG\""), Some(b"PNG".to_vec()));
+        assert_eq!(parse_pattern("abc"), None);
+    }
+
+    #[test]
+    fn finds_patterns_and_wraps() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        let mut data = vec![0u8; SEARCH_CHUNK + 10];
+        data[SEARCH_CHUNK - 1..SEARCH_CHUNK + 2].copy_from_slice(&[0xCA, 0xFE, 0x42]);
+        data[3..6].copy_from_slice(&[0xCA, 0xFE, 0x42]);
+        std::fs::write(&path, &data).unwrap();
+
+        let file = HexFile::open(&path).unwrap();
+        let cancelled = AtomicBool::new(false);
+        let pattern = [0xCA, 0xFE, 0x42];
+        assert_eq!(file.find(&pattern, 4, &cancelled).unwrap(), Some(SEARCH_CHUNK as u64 - 1));
+        assert_eq!(file.find(&pattern, SEARCH_CHUNK as u64, &cancelled).unwrap(), Some(3));
+        assert_eq!(file.row_count(), (SEARCH_CHUNK + 10).div_ceil(BYTES_PER_ROW));
+    }
+}
@@ -0,0 +1,299 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::index::{Change, FileInfo, Index};
+use crate::tags::PathTags;
+
+/// A category as written in the config, e.g. books:
+/// `{"name": "Books", "rules": [{"glob": "*.epub|*.pdf|*.mobi"}]}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CategoryDef {
+    pub name: String,
+    /// Whether one matching rule is enough, rather than all of them.
+    #[serde(default)]
+    pub any: bool,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// Extensions without the dot, in any case.
+    Extensions(Vec<String>),
+    /// Patterns separated by `|`. Those without a `/` match the file name,
+    /// the others the path inside its library root.
+    Glob(String),
+    /// Size in bytes, both ends included.
+    Size { min: Option<u64>, max: Option<u64> },
+    /// Last modified, as `YYYY-MM-DD` in local time, both days included.
+    Modified { after: Option<String>, before: Option<String> },
+    Tag(String),
+    /// Guessed from the extension: `application/pdf`, or a family like
+    /// `image/*`.
+    Mime(String),
+}
+
+enum Matcher {
+    Extensions(HashSet<String>),
+    Glob { names: GlobSet, paths: GlobSet },
+    Size(u64, u64),
+    Modified(Option<SystemTime>, Option<SystemTime>),
+    Tag(String),
+    Mime(String),
+}
+
+pub struct Category {
+    pub name: String,
+    any: bool,
+    matchers: Vec<Matcher>,
+}
+
+impl Category {
+    pub fn compile(def: &CategoryDef) -> Result<Self, String> {
+        let matchers = def
+            .rules
+            .iter()
+            .map(compile_rule)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("{}: {err}", def.name))?;
+        Ok(Self {
+            name: def.name.clone(),
+            any: def.any,
+            matchers,
+        })
+    }
+
+    fn uses_tags(&self) -> bool {
+        self.matchers.iter().any(|matcher| matches!(matcher, Matcher::Tag(_)))
+    }
+
+    /// Whether the file at `path`, which is `relative` inside its library
+    /// root, belongs here. A category without rules holds nothing.
+    pub fn matches(&self, path: &Path, relative: &Path, info: &FileInfo, tags: &PathTags) -> bool {
+        let mut results = self.matchers.iter().map(|matcher| match matcher {
+            Matcher::Extensions(extensions) => path
+                .extension()
+                .is_some_and(|ext| extensions.contains(&ext.to_string_lossy().to_lowercase())),
+            Matcher::Glob { names, paths } => {
+                path.file_name().is_some_and(|name| names.is_match(name)) || paths.is_match(relative)
+            }
+            Matcher::Size(min, max) => (*min..=*max).contains(&info.len),
+            Matcher::Modified(after, before) => info.modified.is_some_and(|modified| {
+                after.is_none_or(|after| modified >= after) && before.is_none_or(|before| modified < before)
+            }),
+            Matcher::Tag(name) => tags.get(path).is_some_and(|names| names.contains(name)),
+            Matcher::Mime(pattern) => mime_guess::from_path(path).iter().any(|mime| match pattern.strip_suffix("/*") {
+                Some(family) => mime.type_().as_str().eq_ignore_ascii_case(family),
+                None => mime.essence_str().eq_ignore_ascii_case(pattern),
+            }),
+        });
+        match (self.matchers.is_empty(), self.any) {
+            (true, _) => false,
+            (false, true) => results.any(|matched| matched),
+            (false, false) => results.all(|matched| matched),
+        }
+    }
+}
+
+fn compile_rule(rule: &Rule) -> Result<Matcher, String> {
+    Ok(match rule {
+        Rule::Extensions(extensions) => Matcher::Extensions(
+            extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect(),
+        ),
+        Rule::Glob(patterns) => {
+            let mut names = GlobSetBuilder::new();
+            let mut paths = GlobSetBuilder::new();
+            for pattern in patterns.split('|').map(str::trim).filter(|pattern| !pattern.is_empty()) {
+                let glob = GlobBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|err| err.to_string())?;
+                if pattern.contains('/') {
+                    paths.add(glob);
+                } else {
+                    names.add(glob);
+                }
+            }
+            Matcher::Glob {
+                names: names.build().map_err(|err| err.to_string())?,
+                paths: paths.build().map_err(|err| err.to_string())?,
+            }
+        }
+        Rule::Size { min, max } => Matcher::Size(min.unwrap_or(0), max.unwrap_or(u64::MAX)),
+        Rule::Modified { after, before } => Matcher::Modified(
+            after.as_deref().map(|day| start_of_day(day, 0)).transpose()?,
+            before.as_deref().map(|day| start_of_day(day, 1)).transpose()?,
+        ),
+        Rule::Tag(name) => Matcher::Tag(name.clone()),
+        Rule::Mime(pattern) => Matcher::Mime(pattern.clone()),
+    })
+}
+
+/// Local midnight starting the day `days_later` days after `day`.
+fn start_of_day(day: &str, days_later: u64) -> Result<SystemTime, String> {
+    let date = chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(|err| format!("{day}: {err}"))?;
+    date.checked_add_days(chrono::Days::new(days_later))
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|time| time.and_local_timezone(chrono::Local).earliest())
+        .map(SystemTime::from)
+        .ok_or_else(|| format!("{day}: out of range"))
+}
+
+/// The categories and the files in each across the library, kept up to
+/// date one change at a time rather than by sorting everything again.
+#[derive(Default)]
+pub struct Collections {
+    categories: Vec<Category>,
+    members: Vec<BTreeSet<PathBuf>>,
+}
+
+impl Collections {
+    pub fn new(categories: Vec<Category>) -> Self {
+        let members = categories.iter().map(|_| BTreeSet::new()).collect();
+        Self { categories, members }
+    }
+
+    pub fn categories(&self) -> &[Category] {
+        &self.categories
+    }
+
+    /// Files in the category at `ix`, sorted by path.
+    pub fn members(&self, ix: usize) -> &BTreeSet<PathBuf> {
+        &self.members[ix]
+    }
+
+    /// Sorts every file of the library from scratch, after roots came or
+    /// went.
+    pub fn rebuild<'a>(&mut self, indexes: impl IntoIterator<Item = &'a Index>, tags: &PathTags) {
+        for members in &mut self.members {
+            members.clear();
+        }
+        for index in indexes {
+            for (path, info) in index.files() {
+                self.sort_file(index.root(), path, info, tags);
+            }
+        }
+    }
+
+    /// Follows changes made to the index of the library root `root`.
+    pub fn apply(&mut self, root: &Path, changes: &[Change], tags: &PathTags) {
+        for change in changes {
+            match change {
+                Change::Removed(path) => {
+                    for members in &mut self.members {
+                        members.retain(|known| !known.starts_with(path));
+                    }
+                }
+                Change::Updated(path, info) => self.sort_file(root, path, info, tags),
+            }
+        }
+    }
+
+    /// Sorts files again after tags changed, for categories with tag rules.
+    pub fn retag<'a>(&mut self, indexes: impl IntoIterator<Item = &'a Index> + Clone, tags: &PathTags) {
+        for (category, members) in self.categories.iter().zip(&mut self.members) {
+            if !category.uses_tags() {
+                continue;
+            }
+            *members = indexes
+                .clone()
+                .into_iter()
+                .flat_map(|index| {
+                    index
+                        .files()
+                        .iter()
+                        .filter(|(path, info)| category.matches(path, relative(index.root(), path), info, tags))
+                        .map(|(path, _)| path.clone())
+                })
+                .collect();
+        }
+    }
+
+    fn sort_file(&mut self, root: &Path, path: &Path, info: &FileInfo, tags: &PathTags) {
+        let relative = relative(root, path);
+        for (category, members) in self.categories.iter().zip(&mut self.members) {
+            if category.matches(path, relative, info, tags) {
+                members.insert(path.to_path_buf());
+            } else {
+                members.remove(path);
+            }
+        }
+    }
+}
+
+fn relative<'a>(root: &Path, path: &'a Path) -> &'a Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index;
+    use crate::tags::TagDb;
+    use std::fs;
+
+    #[test]
+    fn sorts_files_and_follows_changes() {
+        let root = tempfile::tempdir().unwrap();
+        let path = |name: &str| root.path().join(name);
+        fs::create_dir(path("papers")).unwrap();
+        fs::write(path("novel.EPUB"), "x".repeat(2000)).unwrap();
+        fs::write(path("papers/paper.pdf"), "x").unwrap();
+        fs::write(path("photo.png"), "").unwrap();
+        fs::write(path("notes.md"), "").unwrap();
+
+        let defs: Vec<CategoryDef> = serde_json::from_str(
+            r#"[
+                {"name": "Books", "rules": [{"glob": "*.epub|*.pdf|*.mobi"}]},
+                {"name": "Big books", "rules": [{"extensions": ["epub", "pdf"]}, {"size": {"min": 1000}}]},
+                {"name": "Papers", "rules": [{"glob": "papers/**"}]},
+                {"name": "Images or starred", "any": true, "rules": [{"mime": "image/*"}, {"tag": "starred"}]},
+                {"name": "Recent", "rules": [{"modified": {"after": "2000-01-01"}}]}
+            ]"#,
+        )
+        .unwrap();
+        let categories = defs.iter().map(Category::compile).collect::<Result<Vec<_>, _>>().unwrap();
+        let mut tags = TagDb::default();
+        let mut index = Index::build(root.path(), None);
+        let mut collections = Collections::new(categories);
+        collections.rebuild([&index], &tags.path_tags());
+
+        let names = |collections: &Collections, ix: usize| {
+            collections
+                .members(ix)
+                .iter()
+                .map(|path| path.strip_prefix(root.path()).unwrap().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&collections, 0), ["novel.EPUB", "papers/paper.pdf"]);
+        assert_eq!(names(&collections, 1), ["novel.EPUB"]);
+        assert_eq!(names(&collections, 2), ["papers/paper.pdf"]);
+        assert_eq!(names(&collections, 3), ["photo.png"]);
+        assert_eq!(collections.members(4).len(), 4);
+
+        tags.add(&path("notes.md"), "starred", None);
+        collections.retag([&index], &tags.path_tags());
+        assert_eq!(names(&collections, 3), ["notes.md", "photo.png"]);
+
+        fs::rename(path("papers"), path("archive")).unwrap();
+        fs::write(path("manual.mobi"), "").unwrap();
+        let changes = index.apply(index::refresh(vec![path("papers"), path("archive"), path("manual.mobi")]));
+        collections.apply(index.root(), &changes, &tags.path_tags());
+        assert_eq!(names(&collections, 0), ["archive/paper.pdf", "manual.mobi", "novel.EPUB"]);
+        assert!(names(&collections, 2).is_empty());
+
+        let bad = CategoryDef {
+            name: "Bad".to_string(),
+            any: false,
+            rules: vec![Rule::Modified { after: Some("yesterday".to_string()), before: None }],
+        };
+        assert!(Category::compile(&bad).is_err());
+    }
+}
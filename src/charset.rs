@@ -0,0 +1,127 @@
+use encoding_rs::{
+    BIG5, EUC_JP, EUC_KR, Encoding, GB18030, GBK, SHIFT_JIS, UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252,
+};
+
+/// Encodings offered for a manual override, in menu order.
+pub const CHOICES: &[&Encoding] = &[
+    UTF_8, UTF_16LE, UTF_16BE, SHIFT_JIS, EUC_JP, GBK, GB18030, BIG5, EUC_KR, WINDOWS_1252,
+];
+
+/// Legacy CJK encodings tried when the text isn't UTF-8. Korean and Big5
+/// are left to the manual override: GBK text also decodes cleanly as those,
+/// so guessing between them is mostly noise.
+const CANDIDATES: &[&Encoding] = &[GBK, SHIFT_JIS];
+
+/// Guesses the encoding of a file from its first bytes: a BOM wins, then
+/// UTF-16 by the position of its NUL bytes, then UTF-8 if it validates, then
+/// whichever CJK encoding decodes to the most plausible text.
+pub fn detect(sample: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return encoding;
+    }
+    if let Some(encoding) = detect_utf16(sample) {
+        return encoding;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return UTF_8,
+        // A multi-byte character cut off by the end of the sample.
+        Err(err) if err.error_len().is_none() => return UTF_8,
+        Err(_) => {}
+    }
+    CANDIDATES
+        .iter()
+        .filter_map(|encoding| score(encoding, sample).map(|score| (score, *encoding)))
+        .fold(None, |best: Option<(i64, &'static Encoding)>, (score, encoding)| match best {
+            Some((best_score, _)) if best_score >= score => best,
+            _ => Some((score, encoding)),
+        })
+        .map(|(_, encoding)| encoding)
+        .unwrap_or(WINDOWS_1252)
+}
+
+/// Decodes a whole file, dropping a BOM that matches `encoding`.
+pub fn decode(bytes: &[u8], encoding: &'static Encoding) -> String {
+    let (text, _) = encoding.decode_with_bom_removal(bytes);
+    text.into_owned()
+}
+
+/// The newline as it is stored in `encoding`. Its length is the width of
+/// one code unit, so lines can be split without decoding.
+pub fn newline(encoding: &'static Encoding) -> &'static [u8] {
+    if encoding == UTF_16LE {
+        b"\n\0"
+    } else if encoding == UTF_16BE {
+        b"\0\n"
+    } else {
+        b"\n"
+    }
+}
+
+fn detect_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let units = sample.len() / 2;
+    if units < 4 {
+        return None;
+    }
+    let even = sample.iter().step_by(2).filter(|byte| **byte == 0).count();
+    let odd = sample.iter().skip(1).step_by(2).filter(|byte| **byte == 0).count();
+    // Mostly-ASCII UTF-16 has a NUL in nearly every other byte.
+    if odd * 10 > units * 3 && even * 10 < units {
+        Some(UTF_16LE)
+    } else if even * 10 > units * 3 && odd * 10 < units {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Rates how much `sample` looks like real text in `encoding`; `None` when
+/// it doesn't decode at all.
+fn score(encoding: &'static Encoding, sample: &[u8]) -> Option<i64> {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut text = String::with_capacity(sample.len() * 2);
+    // `last = false` keeps a character cut off by the sample's end from
+    // counting as an error.
+    let (_, _, had_errors) = decoder.decode_to_string(sample, &mut text, false);
+    if had_errors {
+        return None;
+    }
+    Some(
+        text.chars()
+            .map(|c| match c as u32 {
+                // Kana only shows up in Japanese, and shows up a lot.
+                0x3040..=0x30FF => 2,
+                0x4E00..=0x9FFF => 1,
+                0x3000..=0x303F | 0xFF01..=0xFF5E => 1,
+                // Half-width kana and private use are what GBK looks like
+                // through Shift-JIS.
+                0xFF61..=0xFF9F => -1,
+                0xE000..=0xF8FF => -4,
+                _ => 0,
+            })
+            .sum(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_common_encodings() {
+        assert_eq!(detect(b"\xEF\xBB\xBFhello"), UTF_8);
+        assert_eq!(detect("图书馆 library".as_bytes()), UTF_8);
+
+        let (japanese, _, _) = SHIFT_JIS.encode("こんにちは、図書館へようこそ。");
+        assert_eq!(detect(&japanese), SHIFT_JIS);
+
+        let (chinese, _, _) = GBK.encode("图书馆里有很多书，欢迎光临。");
+        assert_eq!(detect(&chinese), GBK);
+
+        let utf16 = "plain text\r\n"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(detect(&utf16), UTF_16LE);
+        assert_eq!(decode(&utf16, UTF_16LE), "plain text\r\n");
+    }
+}
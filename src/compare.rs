@@ -0,0 +1,118 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Direct entries of `left` and `right` that differ from their namesake on
+/// the other side: missing there, a file facing a folder, or files with
+/// different contents. Folders with the same name count as equal; their
+/// contents aren't compared.
+pub fn differing(left: &Path, right: &Path) -> io::Result<(HashSet<PathBuf>, HashSet<PathBuf>)> {
+    let left_entries = read(left)?;
+    let right_entries = read(right)?;
+    let mut left_differing = HashSet::new();
+    let mut right_differing = HashSet::new();
+    for (name, left_meta) in &left_entries {
+        let same = right_entries
+            .get(name)
+            .is_some_and(|right_meta| same_entry(&left.join(name), left_meta, &right.join(name), right_meta));
+        if !same {
+            left_differing.insert(left.join(name));
+            if right_entries.contains_key(name) {
+                right_differing.insert(right.join(name));
+            }
+        }
+    }
+    for name in right_entries.keys() {
+        if !left_entries.contains_key(name) {
+            right_differing.insert(right.join(name));
+        }
+    }
+    Ok((left_differing, right_differing))
+}
+
+fn read(dir: &Path) -> io::Result<HashMap<OsString, Metadata>> {
+    let mut entries = HashMap::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        // Follow links so a link to a file compares like the file.
+        if let Ok(meta) = fs::metadata(entry.path()) {
+            entries.insert(entry.file_name(), meta);
+        }
+    }
+    Ok(entries)
+}
+
+fn same_entry(left: &Path, left_meta: &Metadata, right: &Path, right_meta: &Metadata) -> bool {
+    if left_meta.is_dir() || right_meta.is_dir() {
+        return left_meta.is_dir() == right_meta.is_dir();
+    }
+    if left_meta.len() != right_meta.len() {
+        return false;
+    }
+    // Copies rarely keep their timestamps, so only equal times are trusted
+    // without reading the files.
+    if let (Ok(left_time), Ok(right_time)) = (left_meta.modified(), right_meta.modified())
+        && left_time == right_time
+    {
+        return true;
+    }
+    same_contents(left, right).unwrap_or(false)
+}
+
+fn same_contents(left: &Path, right: &Path) -> io::Result<bool> {
+    let mut left = BufReader::new(File::open(left)?);
+    let mut right = BufReader::new(File::open(right)?);
+    let mut left_buf = [0; 8192];
+    let mut right_buf = [0; 8192];
+    loop {
+        let len = left.read(&mut left_buf)?;
+        if len == 0 {
+            return Ok(right.read(&mut right_buf)? == 0);
+        }
+        right.read_exact(&mut right_buf[..len])?;
+        if left_buf[..len] != right_buf[..len] {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_entries_that_differ() {
+        let left = tempfile::tempdir().unwrap();
+        let right = tempfile::tempdir().unwrap();
+        let write = |dir: &Path, name: &str, text: &str| fs::write(dir.join(name), text).unwrap();
+        write(left.path(), "same.txt", "hello");
+        write(right.path(), "same.txt", "hello");
+        write(left.path(), "edited.txt", "hello");
+        write(right.path(), "edited.txt", "hullo");
+        write(left.path(), "only-left.txt", "");
+        write(right.path(), "kind", "");
+        fs::create_dir(left.path().join("kind")).unwrap();
+        fs::create_dir(left.path().join("folder")).unwrap();
+        fs::create_dir(right.path().join("folder")).unwrap();
+        fs::create_dir(right.path().join("only-right")).unwrap();
+        // Same sizes with different times, so the contents decide.
+        for name in ["same.txt", "edited.txt"] {
+            let file = File::options().write(true).open(right.path().join(name)).unwrap();
+            file.set_modified(std::time::UNIX_EPOCH).unwrap();
+        }
+
+        let (left_differing, right_differing) = differing(left.path(), right.path()).unwrap();
+        let names = |paths: HashSet<PathBuf>| {
+            let mut names = paths
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(names(left_differing), ["edited.txt", "kind", "only-left.txt"]);
+        assert_eq!(names(right_differing), ["edited.txt", "kind", "only-right"]);
+    }
+}
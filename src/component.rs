@@ -1,8 +1,18 @@
+pub(crate) mod batch_fix_view;
+pub(crate) mod categories_view;
 pub(crate) mod content_viewer;
 pub(crate) mod header;
 pub(crate) mod file_tree;
-mod left_dock;
+pub(crate) mod image_view;
+pub(crate) mod left_dock;
+pub(crate) mod library_bar;
+pub(crate) mod tags_view;
+pub(crate) mod trash_view;
+pub(crate) mod typography_panel;
+pub(crate) mod workflow_view;
 
+use gpui::{App, Deferred, IntoElement, ParentElement, Pixels, Point, Styled, anchored, deferred, px};
+use gpui_component::ActiveTheme;
 use std::path::PathBuf;
 
 #[derive(Clone, Debug)]
@@ -25,3 +35,25 @@ impl From<std::fs::DirEntry> for DirEntry {
         }
     }
 }
+
+/// Floats `menu` above everything else, styled as a popup menu: at
+/// `position` in the window, or else just below where it is placed. Closing
+/// it is up to the caller.
+pub(crate) fn popup_menu(position: Option<Point<Pixels>>, menu: impl IntoElement + Styled, cx: &App) -> Deferred {
+    let anchored = match position {
+        Some(position) => anchored().position(position),
+        None => anchored(),
+    };
+    deferred(
+        anchored.snap_to_window().child(
+            menu.min_w(px(180.))
+                .p_1()
+                .bg(cx.theme().background)
+                .border_1()
+                .border_color(cx.theme().border)
+                .rounded(cx.theme().radius)
+                .shadow_md(),
+        ),
+    )
+    .with_priority(1)
+}
@@ -0,0 +1,293 @@
+use autocorrect::ignorer::Ignorer;
+use gpui::{
+    AppContext, Context, EventEmitter, InteractiveElement, IntoElement, ParentElement, Render,
+    SharedString, StatefulInteractiveElement, Styled, Task, Window, div, prelude::FluentBuilder, px,
+};
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::{h_flex, v_flex, ActiveTheme, Disableable, Selectable, Sizable};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::component::file_tree;
+use crate::component::typography_panel::render_diff_lines;
+use crate::typography::{self, BatchReport, DiffLine};
+
+#[derive(Clone)]
+pub enum BatchFixEvent {
+    /// Files that were rewritten.
+    Written(Vec<PathBuf>),
+    Closed,
+}
+
+/// Runs autocorrect over a folder and lets the user review each file's
+/// diff, and pick which files to write, before anything changes on disk.
+pub struct BatchFixView {
+    dir: PathBuf,
+    report: Option<BatchReport>,
+    /// Whether each of `report.changes` is going to be written.
+    included: Vec<bool>,
+    selected: Option<(usize, Vec<DiffLine>)>,
+    busy: bool,
+    status: Option<SharedString>,
+    /// Why the last write failed, for the files it failed on.
+    write_errors: HashMap<PathBuf, SharedString>,
+    cancel: Arc<AtomicBool>,
+    _task: Option<Task<()>>,
+}
+
+impl EventEmitter<BatchFixEvent> for BatchFixView {}
+
+impl BatchFixView {
+    /// `root` is the tree's root, whose ignore files decide what is skipped,
+    /// the same as for the tree itself.
+    pub fn new(dir: PathBuf, root: PathBuf, cx: &mut Context<Self>) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let task = cx.background_spawn({
+            let dir = dir.clone();
+            let cancelled = cancel.clone();
+            async move {
+                let ignorer = Ignorer::new(&root.to_string_lossy());
+                typography::check_dir(&dir, &|path| file_tree::is_ignored(&ignorer, &root, path), &cancelled)
+            }
+        });
+        let _task = Some(cx.spawn(async move |this, cx| {
+            let report = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.busy = false;
+                this.included = vec![true; report.changes.len()];
+                this.report = Some(report);
+                this.select(0, cx);
+            }) {
+                eprintln!("Update autocorrect report failed: {err}");
+            }
+        }));
+        Self {
+            dir,
+            report: None,
+            included: Vec::new(),
+            selected: None,
+            busy: true,
+            status: None,
+            write_errors: HashMap::new(),
+            cancel,
+            _task,
+        }
+    }
+
+    fn close(&mut self, cx: &mut Context<Self>) {
+        self.cancel.store(true, Ordering::Relaxed);
+        cx.emit(BatchFixEvent::Closed);
+    }
+
+    fn select(&mut self, ix: usize, cx: &mut Context<Self>) {
+        self.selected = self
+            .report
+            .as_ref()
+            .and_then(|report| report.changes.get(ix))
+            .map(|change| (ix, typography::diff(&change.original, &change.fixed)));
+        cx.notify();
+    }
+
+    /// Writes the included files. Written files leave the report; ones that
+    /// failed stay so they can be retried.
+    fn write(&mut self, cx: &mut Context<Self>) {
+        let Some(report) = self.report.as_mut() else {
+            return;
+        };
+        let mut changes = Vec::new();
+        let mut kept = Vec::new();
+        for (change, included) in report.changes.drain(..).zip(self.included.drain(..)) {
+            if included {
+                changes.push(change);
+            } else {
+                kept.push(change);
+            }
+        }
+        report.changes = kept;
+        let task = cx.background_spawn(async move {
+            changes
+                .into_iter()
+                .map(|change| {
+                    let result = typography::write_fixed(&change.path, &change.original, &change.fixed);
+                    (change, result)
+                })
+                .collect::<Vec<_>>()
+        });
+        self.busy = true;
+        self.selected = None;
+        self._task = Some(cx.spawn(async move |this, cx| {
+            let results = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.busy = false;
+                let Some(report) = this.report.as_mut() else {
+                    return;
+                };
+                let mut written = Vec::new();
+                this.write_errors.clear();
+                for (change, result) in results {
+                    match result {
+                        Ok(()) => written.push(change.path),
+                        Err(err) => {
+                            this.write_errors.insert(change.path.clone(), err.into());
+                            report.changes.push(change);
+                        }
+                    }
+                }
+                report.changes.sort_by(|a, b| a.path.cmp(&b.path));
+                this.included = vec![false; report.changes.len()];
+                this.status = Some(match this.write_errors.len() {
+                    0 => format!("Wrote {} files", written.len()).into(),
+                    failed => format!("Wrote {} files, {failed} failed", written.len()).into(),
+                });
+                cx.emit(BatchFixEvent::Written(written));
+                this.select(0, cx);
+            }) {
+                eprintln!("Write autocorrect fixes failed: {err}");
+            }
+        }));
+        cx.notify();
+    }
+
+    fn render_files(&self, report: &BatchReport, cx: &mut Context<Self>) -> impl IntoElement {
+        let rows = report.changes.iter().enumerate().map(|(ix, change)| {
+            let included = self.included.get(ix).copied().unwrap_or(false);
+            let selected = self.selected.as_ref().is_some_and(|(selected, _)| *selected == ix);
+            h_flex()
+                .id(("batch-file", ix))
+                .gap_2()
+                .px_2()
+                .py_1()
+                .border_b_1()
+                .border_color(cx.theme().border)
+                .when(selected, |this| this.bg(cx.theme().muted))
+                .cursor_pointer()
+                .on_click(cx.listener(move |this, _, _, cx| this.select(ix, cx)))
+                .child(
+                    Button::new(("batch-include", ix))
+                        .label(if included { "✓" } else { " " })
+                        .small()
+                        .ghost()
+                        .selected(included)
+                        .disabled(self.busy)
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            if let Some(included) = this.included.get_mut(ix) {
+                                *included = !*included;
+                            }
+                            cx.notify();
+                        })),
+                )
+                .child(
+                    v_flex()
+                        .flex_1()
+                        .overflow_hidden()
+                        .child(div().truncate().child(relative(&change.path, &self.dir)))
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(format!("{} issues", change.issues)),
+                        )
+                        .children(self.write_errors.get(&change.path).map(|err| {
+                            div().text_xs().text_color(cx.theme().danger).child(err.clone())
+                        })),
+                )
+        });
+        div()
+            .id("batch-files")
+            .w(px(320.0))
+            .flex_shrink_0()
+            .h_full()
+            .overflow_y_scroll()
+            .border_r_1()
+            .border_color(cx.theme().border)
+            .children(rows)
+    }
+}
+
+impl Render for BatchFixView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let included = self.included.iter().filter(|included| **included).count();
+        let summary: SharedString = match &self.report {
+            None => format!("Checking {}…", self.dir.display()).into(),
+            Some(report) => {
+                let mut summary = format!(
+                    "{} files checked · {} to change · {} issues",
+                    report.checked,
+                    report.changes.len(),
+                    report.issues
+                );
+                if !report.errors.is_empty() {
+                    summary.push_str(&format!(" · {} unreadable", report.errors.len()));
+                }
+                summary.into()
+            }
+        };
+
+        v_flex()
+            .size_full()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        v_flex()
+                            .flex_1()
+                            .overflow_hidden()
+                            .child(div().truncate().child(format!("Autocorrect {}", self.dir.display())))
+                            .child(
+                                div()
+                                    .text_sm()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child(summary),
+                            ),
+                    )
+                    .child(
+                        Button::new("batch-write")
+                            .label(format!("Write {included} Files"))
+                            .small()
+                            .disabled(self.busy || included == 0)
+                            .on_click(cx.listener(|this, _, _, cx| this.write(cx))),
+                    )
+                    .child(
+                        Button::new("batch-close")
+                            .label("Close")
+                            .small()
+                            .ghost()
+                            .on_click(cx.listener(|this, _, _, cx| this.close(cx))),
+                    ),
+            )
+            .children(self.status.clone().map(|status| {
+                div()
+                    .px_3()
+                    .py_1()
+                    .text_sm()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(status)
+            }))
+            .children(self.report.as_ref().map(|report| {
+                h_flex()
+                    .flex_1()
+                    .overflow_hidden()
+                    .child(self.render_files(report, cx))
+                    .map(|this| match &self.selected {
+                        Some((_, diff)) => this.child(render_diff_lines("batch-diff", diff, cx)),
+                        None => this.child(
+                            div()
+                                .flex_1()
+                                .p_4()
+                                .text_color(cx.theme().muted_foreground)
+                                .child("Nothing to change"),
+                        ),
+                    })
+            }))
+    }
+}
+
+fn relative(path: &Path, dir: &Path) -> String {
+    path.strip_prefix(dir).unwrap_or(path).display().to_string()
+}
@@ -0,0 +1,297 @@
+use gpui::{
+    AppContext, Context, Entity, EventEmitter, InteractiveElement, IntoElement, ParentElement, Render,
+    SharedString, StatefulInteractiveElement, Styled, Subscription, Task, Window, div, prelude::FluentBuilder, px,
+    uniform_list,
+};
+use gpui_component::{h_flex, v_flex, ActiveTheme};
+use std::mem;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::categories::{Category, Collections};
+use crate::config::Config;
+use crate::index::Change;
+use crate::library::{Library, LibraryEvent};
+use crate::tags::TagDb;
+
+#[derive(Clone)]
+pub enum CategoriesViewEvent {
+    /// Show this file in the explorer.
+    Open(PathBuf),
+}
+
+/// Work on the collections, done off the UI thread in the order it came in.
+enum Job {
+    Rebuild,
+    Apply { root: PathBuf, changes: Vec<Change> },
+    Retag,
+}
+
+/// One line of the selected category's virtual folder.
+enum Row {
+    Folder(SharedString),
+    File(PathBuf, SharedString),
+}
+
+/// The categories from the config, each a virtual folder of the matching
+/// files across the library roots.
+pub struct CategoriesView {
+    library: Entity<Library>,
+    tags: Entity<TagDb>,
+    /// Away in the background while jobs are being worked through.
+    collections: Option<Collections>,
+    jobs: Vec<Job>,
+    names: Vec<SharedString>,
+    /// Files in each category, as of the last finished jobs.
+    counts: Vec<usize>,
+    /// Categories left out because their rules don't compile, and why
+    /// config.json was ignored, if it was.
+    errors: Vec<SharedString>,
+    selected: usize,
+    rows: Vec<Row>,
+    _task: Option<Task<()>>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl EventEmitter<CategoriesViewEvent> for CategoriesView {}
+
+impl CategoriesView {
+    pub fn new(
+        config: &Config,
+        config_error: Option<SharedString>,
+        library: Entity<Library>,
+        tags: Entity<TagDb>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let mut errors = config_error.into_iter().collect::<Vec<_>>();
+        let categories = config
+            .categories
+            .iter()
+            .filter_map(|def| Category::compile(def).map_err(|err| errors.push(err.into())).ok())
+            .collect::<Vec<_>>();
+        let _subscriptions = vec![
+            cx.subscribe(&library, |this, _, event: &LibraryEvent, cx| {
+                let job = match event {
+                    LibraryEvent::Rebuilt => Job::Rebuild,
+                    LibraryEvent::Changed { root, changes } => Job::Apply {
+                        root: root.clone(),
+                        changes: changes.clone(),
+                    },
+                };
+                this.queue(job, cx);
+            }),
+            cx.observe(&tags, |this, _, cx| this.queue(Job::Retag, cx)),
+        ];
+        let mut this = Self {
+            library,
+            tags,
+            names: categories.iter().map(|category| category.name.clone().into()).collect(),
+            counts: vec![0; categories.len()],
+            collections: Some(Collections::new(categories)),
+            jobs: Vec::new(),
+            errors,
+            selected: 0,
+            rows: Vec::new(),
+            _task: None,
+            _subscriptions,
+        };
+        this.queue(Job::Rebuild, cx);
+        this
+    }
+
+    fn queue(&mut self, job: Job, cx: &mut Context<Self>) {
+        // Sorting everything again covers whatever was waiting.
+        if let Job::Rebuild = job {
+            self.jobs.clear();
+        }
+        self.jobs.push(job);
+        self.run_jobs(cx);
+    }
+
+    /// Works through the queued jobs in the background, unless that is
+    /// happening already; jobs queued meanwhile are run once it is done.
+    fn run_jobs(&mut self, cx: &mut Context<Self>) {
+        if self.jobs.is_empty() {
+            return;
+        }
+        let Some(mut collections) = self.collections.take() else {
+            return;
+        };
+        let jobs = mem::take(&mut self.jobs);
+        let indexes = self.library.read(cx).snapshot();
+        let tags = self.tags.read(cx).path_tags();
+        let task = cx.background_spawn(async move {
+            let indexes = indexes.iter().map(|index| &**index);
+            for job in jobs {
+                match job {
+                    Job::Rebuild => collections.rebuild(indexes.clone(), &tags),
+                    Job::Apply { root, changes } => collections.apply(&root, &changes, &tags),
+                    Job::Retag => collections.retag(indexes.clone(), &tags),
+                }
+            }
+            collections
+        });
+        self._task = Some(cx.spawn(async move |this, cx| {
+            let collections = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.collections = Some(collections);
+                this.build_rows(cx);
+                this.run_jobs(cx);
+            }) {
+                eprintln!("Update categories failed: {err}");
+            }
+        }));
+    }
+
+    fn select(&mut self, ix: usize, cx: &mut Context<Self>) {
+        self.selected = ix;
+        self.build_rows(cx);
+    }
+
+    /// Lays the selected category out as folders, each followed by its
+    /// files. Left as it is while the collections are being updated.
+    fn build_rows(&mut self, cx: &mut Context<Self>) {
+        let Some(collections) = &self.collections else {
+            return;
+        };
+        self.counts = (0..collections.categories().len()).map(|ix| collections.members(ix).len()).collect();
+        self.rows.clear();
+        if self.selected < collections.categories().len() {
+            let mut folder: Option<&Path> = None;
+            for path in collections.members(self.selected) {
+                let parent = path.parent();
+                if parent != folder {
+                    folder = parent;
+                    let label = parent.map(|dir| dir.display().to_string()).unwrap_or_default();
+                    self.rows.push(Row::Folder(label.into()));
+                }
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                self.rows.push(Row::File(path.clone(), name.into()));
+            }
+        }
+        cx.notify();
+    }
+
+    fn render_files(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        if self.rows.is_empty() {
+            let library = self.library.read(cx);
+            let message = if library.roots().is_empty() {
+                "Add folders to the library in the explorer to fill the categories"
+            } else if library.is_indexing() || self.collections.is_none() {
+                "Reading the library…"
+            } else {
+                "No files match"
+            };
+            return div()
+                .flex_1()
+                .p_4()
+                .text_color(cx.theme().muted_foreground)
+                .child(message)
+                .into_any_element();
+        }
+        uniform_list(
+            "category-files",
+            self.rows.len(),
+            cx.processor(|this, range: Range<usize>, _window, cx| {
+                range
+                    .filter_map(|ix| Some((ix, this.rows.get(ix)?)))
+                    .map(|(ix, row)| match row {
+                        Row::Folder(label) => div()
+                            .id(("category-row", ix))
+                            .h(px(26.0))
+                            .px_3()
+                            .pt_2()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .truncate()
+                            .child(label.clone()),
+                        Row::File(path, name) => div()
+                            .id(("category-row", ix))
+                            .h(px(26.0))
+                            .px_3()
+                            .py_1()
+                            .cursor_pointer()
+                            .hover(|style| style.bg(cx.theme().muted))
+                            .truncate()
+                            .on_click(cx.listener({
+                                let path = path.clone();
+                                move |_, _, _, cx| cx.emit(CategoriesViewEvent::Open(path.clone()))
+                            }))
+                            .child(name.clone()),
+                    })
+                    .collect()
+            }),
+        )
+        .flex_1()
+        .h_full()
+        .into_any_element()
+    }
+}
+
+impl Render for CategoriesView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let categories = self.names.iter().zip(&self.counts).enumerate().map(|(ix, (name, count))| {
+            h_flex()
+                .id(("category", ix))
+                .gap_2()
+                .px_3()
+                .py_1()
+                .cursor_pointer()
+                .when(ix == self.selected, |this| this.bg(cx.theme().accent))
+                .on_click(cx.listener(move |this, _, _, cx| this.select(ix, cx)))
+                .child(div().flex_1().truncate().child(name.clone()))
+                .child(
+                    div()
+                        .text_xs()
+                        .text_color(cx.theme().muted_foreground)
+                        .child(count.to_string()),
+                )
+        });
+        let empty = self.names.is_empty();
+
+        h_flex()
+            .size_full()
+            .child(
+                v_flex()
+                    .w(px(240.0))
+                    .h_full()
+                    .flex_shrink_0()
+                    .border_r_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        div()
+                            .px_3()
+                            .py_2()
+                            .border_b_1()
+                            .border_color(cx.theme().border)
+                            .child("Categories"),
+                    )
+                    .child(
+                        div()
+                            .id("categories")
+                            .flex_1()
+                            .overflow_y_scroll()
+                            .children(categories)
+                            .when(empty, |this| {
+                                this.p_4()
+                                    .text_sm()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child("No categories. Define them in config.json.")
+                            }),
+                    )
+                    .children(self.errors.iter().map(|err| {
+                        div()
+                            .px_3()
+                            .py_1()
+                            .text_xs()
+                            .text_color(cx.theme().danger)
+                            .child(err.clone())
+                    })),
+            )
+            .child(self.render_files(cx))
+    }
+}
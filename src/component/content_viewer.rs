@@ -1,116 +1,1033 @@
 use gpui::{
-    Context, IntoElement, ParentElement, Render, SharedString, Styled, Window, div, px,
+    AnyElement, App, Context, ElementId, Entity, EventEmitter, FontStyle, FontWeight, HighlightStyle,
+    InteractiveElement, InteractiveText, IntoElement, ParentElement, Render, SharedString, StatefulInteractiveElement,
+    StrikethroughStyle, Styled, StyledText, Subscription, ScrollStrategy, Task, UnderlineStyle,
+    UniformListScrollHandle, Window, combine_highlights, div, prelude::FluentBuilder, px, uniform_list,
 };
-use gpui_component::ActiveTheme;
-use std::path::PathBuf;
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::highlighter::{Language, SyntaxHighlighter};
+use gpui_component::input::{Input, InputEvent, InputState};
+use gpui_component::text::TextView;
+use gpui_component::{h_flex, v_flex, ActiveTheme, Rope, Selectable, Sizable};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use gpui::AppContext;
 
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use futures::StreamExt;
+
+use crate::binary::{self, HexFile, BYTES_PER_ROW, SNIFF_LEN};
+use crate::charset;
+use crate::component::image_view::{decode_image, DecodedImage, ImageView};
+use crate::component::tags_view::{tag_chip, update_tags};
+use crate::component::typography_panel::{TypographyPanel, TypographyPanelEvent};
+use crate::markdown::{self, Block, Inline, Mark, Preview, Target};
+use crate::syntax;
+use crate::tags::{self, TagDb};
+use crate::text_file::TextFile;
+
+pub(crate) const MONO_FONT: &str = "Consolas, Monaco, 'Courier New', monospace";
+const TAB_WIDTH: usize = 4;
+/// Files up to this size are also read whole, for highlighting and for the
+/// Markdown preview; bigger ones are only ever paged.
+const FULL_READ_LIMIT: u64 = 2 * 1024 * 1024;
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+/// Lines are read from disk this many at a time, off the UI thread.
+const PAGE_LINES: usize = 256;
+/// Pages kept in memory; the ones farthest from the view are dropped first.
+const MAX_PAGES: usize = 32;
+/// Rows of a hex dump are read the same way, this many at a time.
+const HEX_PAGE_ROWS: usize = 256;
+
+struct TextDocument {
+    file: Arc<TextFile>,
+    line_count: usize,
+    language: Language,
+    /// Highlighting for files read whole. Lines past the end (a paged file,
+    /// or a followed log that grew) are shown plain.
+    highlighter: Option<Box<SyntaxHighlighter>>,
+    /// Where each line starts in the text given to the highlighter, plus
+    /// where one past the last would.
+    line_starts: Vec<usize>,
+    markdown: Option<Preview>,
+    /// Whether the encoding was picked by the user rather than detected.
+    encoding_overridden: bool,
+    /// Lines read so far, by page. The list only ever renders from here.
+    pages: HashMap<usize, Vec<String>>,
+    pages_loading: HashSet<usize>,
+}
+
+impl TextDocument {
+    fn line(&self, ix: usize) -> Option<&str> {
+        self.pages.get(&(ix / PAGE_LINES))?.get(ix % PAGE_LINES).map(String::as_str)
+    }
+
+    /// The highlights for line `ix`, which is `len` bytes long as shown.
+    fn line_highlights(&self, ix: usize, len: usize, cx: &App) -> Vec<(Range<usize>, HighlightStyle)> {
+        let (Some(highlighter), Some(&[start, end])) = (&self.highlighter, self.line_starts.get(ix..ix + 2)) else {
+            return Vec::new();
+        };
+        highlighter
+            .styles(&(start..end - 1), &cx.theme().highlight_theme)
+            .into_iter()
+            .map(|(range, style)| (range.start - start..range.end - start, style))
+            // Over-long lines are cut when read, so their highlights may
+            // run past the end.
+            .filter(|(range, _)| range.end <= len)
+            .collect()
+    }
+
+    /// Marks the pages `range` needs that aren't read or being read yet as
+    /// loading, and returns them.
+    fn missing_pages(&mut self, range: &Range<usize>) -> Vec<usize> {
+        let first = range.start / PAGE_LINES;
+        if self.pages.len() > MAX_PAGES {
+            self.pages.retain(|page, _| page.abs_diff(first) < MAX_PAGES / 2);
+        }
+        (first..range.end.div_ceil(PAGE_LINES))
+            .filter(|page| !self.pages.contains_key(page) && self.pages_loading.insert(*page))
+            .collect()
+    }
+
+    /// Drops the pages a change to the line count made stale: all of them
+    /// for a file that shrank, otherwise those from the old last line on.
+    fn set_line_count(&mut self, line_count: usize) {
+        let first_stale = if line_count < self.line_count {
+            0
+        } else {
+            self.line_count.saturating_sub(1) / PAGE_LINES
+        };
+        self.pages.retain(|page, _| *page < first_stale);
+        self.pages_loading.retain(|page| *page < first_stale);
+        self.line_count = line_count;
+    }
+}
+
+struct HexDocument {
+    file: Arc<HexFile>,
+    /// The bytes of the last search match or go-to target, highlighted in
+    /// the dump.
+    found: Option<Range<u64>>,
+    /// The pattern that produced `found`; a different one is searched for
+    /// from the start of the file.
+    pattern: Option<Vec<u8>>,
+    pages: HashMap<usize, Vec<u8>>,
+    pages_loading: HashSet<usize>,
+}
+
+impl HexDocument {
+    fn new(file: HexFile) -> Self {
+        Self {
+            file: Arc::new(file),
+            found: None,
+            pattern: None,
+            pages: HashMap::new(),
+            pages_loading: HashSet::new(),
+        }
+    }
+
+    fn row(&self, ix: usize) -> Option<&[u8]> {
+        let page = self.pages.get(&(ix / HEX_PAGE_ROWS))?;
+        let start = ix % HEX_PAGE_ROWS * BYTES_PER_ROW;
+        page.get(start..(start + BYTES_PER_ROW).min(page.len()))
+    }
+
+    /// Marks the pages `range` of rows needs that aren't read or being read
+    /// yet as loading, and returns them.
+    fn missing_pages(&mut self, range: &Range<usize>) -> Vec<usize> {
+        let first = range.start / HEX_PAGE_ROWS;
+        if self.pages.len() > MAX_PAGES {
+            self.pages.retain(|page, _| page.abs_diff(first) < MAX_PAGES / 2);
+        }
+        (first..range.end.div_ceil(HEX_PAGE_ROWS))
+            .filter(|page| !self.pages.contains_key(page) && self.pages_loading.insert(*page))
+            .collect()
+    }
+}
+
+enum Loaded {
+    Text(TextDocument),
+    Binary(HexFile),
+}
+
+#[derive(Clone)]
+pub enum ContentViewerEvent {
+    /// A Markdown link to a local file or folder was clicked.
+    Open(PathBuf),
+}
+
 pub struct ContentViewer {
     file_name: Option<SharedString>,
     file_path: Option<PathBuf>,
-    content: Option<SharedString>,
+    text: Option<TextDocument>,
+    hex: Option<HexDocument>,
+    offset_input: Entity<InputState>,
+    search_input: Entity<InputState>,
+    hex_status: Option<SharedString>,
+    _search_task: Option<Task<()>>,
+    _subscriptions: Vec<Subscription>,
+    list_scroll: UniformListScrollHandle,
+    show_source: bool,
+    encoding_menu: bool,
+    following: bool,
+    _follow_task: Option<Task<()>>,
+    _load_task: Option<Task<()>>,
+    /// Set when the selection changes so image decoding and byte searches
+    /// still running for the old file stop early.
+    cancel: Arc<AtomicBool>,
     loading: bool,
     error: Option<SharedString>,
-    image_cols: usize,
-    image_rows: usize,
-    image_grid: Option<Vec<Vec<(u8, u8, u8)>>>,
+    image: Option<Entity<ImageView>>,
+    typography: Option<(Entity<TypographyPanel>, Subscription)>,
+    /// A folder is selected; only its header is shown, to tag it.
+    folder: bool,
+    tags: Entity<TagDb>,
+    tag_input: Entity<InputState>,
 }
 
+impl EventEmitter<ContentViewerEvent> for ContentViewer {}
+
 impl ContentViewer {
-    pub fn new(_: &mut Window, _: &mut Context<Self>) -> Self {
-        Self { file_name: None, file_path: None, content: None, loading: false, error: None, image_cols: 0, image_rows: 0, image_grid: None }
+    pub fn new(tags: Entity<TagDb>, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let offset_input = cx.new(|cx| InputState::new(window, cx).placeholder("Offset, e.g. 0x1F00"));
+        let search_input = cx.new(|cx| InputState::new(window, cx).placeholder("Bytes, e.g. 89 50 4E 47 or \"IHDR\""));
+        let tag_input = cx.new(|cx| InputState::new(window, cx).placeholder("Add tag…"));
+        let _subscriptions = vec![
+            cx.subscribe_in(&offset_input, window, |this, _, event: &InputEvent, _, cx| {
+                if let InputEvent::PressEnter { .. } = event {
+                    this.go_to_offset(cx);
+                }
+            }),
+            cx.subscribe_in(&search_input, window, |this, _, event: &InputEvent, _, cx| {
+                if let InputEvent::PressEnter { .. } = event {
+                    this.find_next(cx);
+                }
+            }),
+            cx.subscribe_in(&tag_input, window, |this, input, event: &InputEvent, window, cx| {
+                if let InputEvent::PressEnter { .. } = event
+                    && let Some(path) = this.file_path.clone()
+                {
+                    let name = input.read(cx).value().to_string();
+                    this.add_tag(path, name, cx);
+                    input.update(cx, |input, cx| input.set_value("", window, cx));
+                }
+            }),
+            cx.observe(&tags, |_, _, cx| cx.notify()),
+        ];
+        Self {
+            file_name: None,
+            file_path: None,
+            text: None,
+            hex: None,
+            offset_input,
+            search_input,
+            hex_status: None,
+            _search_task: None,
+            _subscriptions,
+            list_scroll: UniformListScrollHandle::new(),
+            show_source: false,
+            encoding_menu: false,
+            following: false,
+            _follow_task: None,
+            _load_task: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            loading: false,
+            error: None,
+            image: None,
+            typography: None,
+            folder: false,
+            tags,
+            tag_input,
+        }
     }
 
-    pub fn set_text(&mut self, file_name: Option<SharedString>, content: Option<String>, cx: &mut Context<Self>) {
-        self.file_name = file_name;
+    pub fn clear(&mut self, cx: &mut Context<Self>) {
+        self.reset();
+        self.file_name = None;
         self.file_path = None;
-        self.content = content.map(SharedString::from);
-        self.loading = false;
-        self.error = None;
         cx.notify();
     }
 
+    pub fn open_file(&mut self, file_name: SharedString, path: PathBuf, cx: &mut Context<Self>) {
+        self.relink_tags(&path, cx);
+        if Self::is_image_name(&file_name) {
+            self.set_image_path(Some(file_name), path, cx);
+            return;
+        }
+        self.load(file_name, path, None, cx);
+    }
+
+    pub fn open_folder(&mut self, name: SharedString, path: PathBuf, cx: &mut Context<Self>) {
+        self.relink_tags(&path, cx);
+        self.reset();
+        self.file_name = Some(name);
+        self.file_path = Some(path);
+        self.folder = true;
+        cx.notify();
+    }
+
+    /// Picks up the tags of `path` if it was renamed outside the app.
+    fn relink_tags(&mut self, path: &Path, cx: &mut Context<Self>) {
+        let Some(orphans) = self.tags.read(cx).relink_candidates(path) else {
+            return;
+        };
+        let task = cx.background_spawn({
+            let path = path.to_path_buf();
+            async move { orphans.find(&path) }
+        });
+        cx.spawn(async move |this, cx| {
+            let Some(relink) = task.await else {
+                return;
+            };
+            if let Err(err) = this.update(cx, |this, cx| {
+                update_tags(&this.tags, cx, |db| {
+                    db.apply_relink(relink);
+                })
+            }) {
+                eprintln!("Relink tags failed: {err}");
+            }
+        })
+        .detach();
+    }
+
+    /// Tags `path` once its contents are hashed, so it can be recognized
+    /// after a rename.
+    fn add_tag(&mut self, path: PathBuf, name: String, cx: &mut Context<Self>) {
+        let task = cx.background_spawn({
+            let path = path.clone();
+            async move { tags::identify(&path) }
+        });
+        cx.spawn(async move |this, cx| {
+            let identity = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                update_tags(&this.tags, cx, |db| db.add(&path, &name, identity))
+            }) {
+                eprintln!("Add tag failed: {err}");
+            }
+        })
+        .detach();
+    }
+
+    /// Reads the current file again, decoded as `encoding` instead of the
+    /// detected one.
+    fn reopen_with_encoding(&mut self, encoding: &'static Encoding, cx: &mut Context<Self>) {
+        let (Some(file_name), Some(path)) = (self.file_name.clone(), self.file_path.clone()) else {
+            return;
+        };
+        self.load(file_name, path, Some(encoding), cx);
+    }
+
+    /// Reads the current file again after it was changed from here, keeping
+    /// the typography panel open.
+    fn reload(&mut self, cx: &mut Context<Self>) {
+        let (Some(file_name), Some(path)) = (self.file_name.clone(), self.file_path.clone()) else {
+            return;
+        };
+        let encoding = self
+            .text
+            .as_ref()
+            .filter(|text| text.encoding_overridden)
+            .map(|text| text.file.encoding());
+        let typography = self.typography.take();
+        self.load(file_name, path, encoding, cx);
+        self.typography = typography;
+    }
+
+    fn toggle_typography(&mut self, cx: &mut Context<Self>) {
+        if self.typography.take().is_some() {
+            cx.notify();
+            return;
+        }
+        let (Some(file_name), Some(path)) = (self.file_name.clone(), self.file_path.clone()) else {
+            return;
+        };
+        let panel = cx.new(|cx| TypographyPanel::new(file_name, path, cx));
+        let subscription = cx.subscribe(&panel, |this, _, event: &TypographyPanelEvent, cx| match event {
+            TypographyPanelEvent::GoToLine(line) => {
+                this.set_following(false, cx);
+                this.show_source = true;
+                this.list_scroll.scroll_to_item(*line, ScrollStrategy::Center);
+                cx.notify();
+            }
+            TypographyPanelEvent::Saved => this.reload(cx),
+        });
+        self.typography = Some((panel, subscription));
+        cx.notify();
+    }
+
+    fn load(
+        &mut self,
+        file_name: SharedString,
+        path: PathBuf,
+        encoding: Option<&'static Encoding>,
+        cx: &mut Context<Self>,
+    ) {
+        self.reset();
+        self.file_name = Some(file_name.clone());
+        self.file_path = Some(path.clone());
+        self.loading = true;
+        cx.notify();
+
+        let task = cx.background_spawn(async move { load_file(&path, &file_name, encoding) });
+        self._load_task = Some(cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.loading = false;
+                match result {
+                    Ok(Loaded::Text(text)) => this.text = Some(text),
+                    Ok(Loaded::Binary(file)) => {
+                        this.hex = Some(HexDocument::new(file));
+                    }
+                    Err(err) => this.error = Some(format!("Failed to read file: {err}").into()),
+                }
+                cx.notify();
+            }) {
+                eprintln!("Update text failed: {err}");
+            }
+        }));
+    }
+
     pub fn set_image_path(&mut self, file_name: Option<SharedString>, path: PathBuf, cx: &mut Context<Self>) {
+        self.reset();
         self.file_name = file_name;
-        self.file_path = Some(path);
-        self.content = None;
+        self.file_path = Some(path.clone());
         self.loading = true;
-        self.error = None;
-        self.image_grid = None;
-        self.image_cols = 0;
-        self.image_rows = 0;
         cx.notify();
 
-        let handle = cx.entity().downgrade();
-        let path2 = self.file_path.clone().unwrap();
+        let cancelled = self.cancel.clone();
+        let (first_tx, mut first_rx) = futures::channel::mpsc::unbounded();
         let task = cx.background_spawn(async move {
-            image::open(&path2)
-                .map_err(|e| e.to_string())
-                .and_then(|img| {
-                    let img = img.to_rgb8();
-                    let (w, h) = (img.width() as usize, img.height() as usize);
-                    let target_cols = w.min(256);
-                    let scale = (w as f32 / target_cols as f32).max(1.0);
-                    let target_rows = ((h as f32) / scale).round() as usize;
-                    let mut grid: Vec<Vec<(u8, u8, u8)>> = Vec::with_capacity(target_rows);
-                    for row in 0..target_rows {
-                        let mut rvec = Vec::with_capacity(target_cols);
-                        let src_y = ((row as f32) * scale) as usize;
-                        for col in 0..target_cols {
-                            let src_x = ((col as f32) * scale) as usize;
-                            let idx = (src_y.min(h - 1) * w + src_x.min(w - 1)) * 3;
-                            let p = &img.as_raw()[idx..idx + 3];
-                            rvec.push((p[0], p[1], p[2]));
-                        }
-                        grid.push(rvec);
-                    }
-                    Ok((target_cols, target_rows, grid))
+            decode_image(&path, &cancelled, move |first| {
+                if let Err(err) = first_tx.unbounded_send(first) {
+                    eprintln!("Nobody waits for the first frame: {err}");
+                }
+            })
+        });
+        self._load_task = Some(cx.spawn(async move |this, cx| {
+            // An animation shows its first frame while the rest decode.
+            if let Some(first) = first_rx.next().await
+                && let Err(err) = this.update(cx, |this, cx| {
+                    this.loading = false;
+                    let decoded = DecodedImage { image: first, truncated: false };
+                    this.image = Some(cx.new(|cx| ImageView::new(decoded, cx)));
+                    cx.notify();
                 })
+            {
+                eprintln!("Update image failed: {err}");
+                return;
+            }
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.loading = false;
+                match result {
+                    Ok(decoded) => match &this.image {
+                        Some(view) => view.update(cx, |view, cx| view.set_image(decoded, cx)),
+                        None => this.image = Some(cx.new(|cx| ImageView::new(decoded, cx))),
+                    },
+                    Err(err) => {
+                        this.image = None;
+                        this.error = Some(err);
+                    }
+                }
+                cx.notify();
+            }) {
+                eprintln!("Update image failed: {err}");
+            }
+        }));
+    }
+
+    /// Drops everything shown for the previous file and stops its background
+    /// work.
+    fn reset(&mut self) {
+        self.loading = false;
+        self.error = None;
+        self.image = None;
+        self.typography = None;
+        self.folder = false;
+        self.text = None;
+        self.hex = None;
+        self.hex_status = None;
+        self._search_task = None;
+        self.encoding_menu = false;
+        self.following = false;
+        self._follow_task = None;
+        self._load_task = None;
+        self.cancel_background_work();
+        self.list_scroll = UniformListScrollHandle::new();
+    }
+
+    fn go_to_offset(&mut self, cx: &mut Context<Self>) {
+        let Some(hex) = self.hex.as_mut() else {
+            return;
+        };
+        let input = self.offset_input.read(cx).value();
+        match binary::parse_offset(&input) {
+            Some(offset) if offset < hex.file.len() => {
+                hex.found = Some(offset..offset + 1);
+                hex.pattern = None;
+                self.hex_status = None;
+                self.list_scroll
+                    .scroll_to_item(offset as usize / BYTES_PER_ROW, ScrollStrategy::Center);
+            }
+            Some(_) => self.hex_status = Some("Offset is past the end of the file".into()),
+            None => self.hex_status = Some("Enter an offset like 0x1F00 or 8000".into()),
+        }
+        cx.notify();
+    }
+
+    /// Searches for the pattern after the current match, so pressing enter
+    /// again steps through every occurrence.
+    fn find_next(&mut self, cx: &mut Context<Self>) {
+        let Some(hex) = self.hex.as_ref() else {
+            return;
+        };
+        let input = self.search_input.read(cx).value();
+        let Some(pattern) = binary::parse_pattern(&input) else {
+            self.hex_status = Some("Enter hex bytes like 89 50 4E 47, or text in quotes".into());
+            cx.notify();
+            return;
+        };
+        let from = match &hex.found {
+            Some(found) if hex.pattern.as_ref() == Some(&pattern) => found.start + 1,
+            _ => 0,
+        };
+        let file = hex.file.clone();
+        let cancelled = self.cancel.clone();
+        let task = cx.background_spawn({
+            let pattern = pattern.clone();
+            async move { file.find(&pattern, from, &cancelled) }
         });
-        cx.spawn(async move |_, cx| {
-            let res = task.await;
-            if let Some(cv) = handle.upgrade() {
-                let _ = cv.update(cx, |this, cx| {
-                    match res {
-                        Ok((c, r, g)) => {
-                            this.loading = false;
-                            this.error = None;
-                            this.image_cols = c;
-                            this.image_rows = r;
-                            this.image_grid = Some(g);
-                        }
-                        Err(err) => {
-                            this.loading = false;
-                            this.error = Some(SharedString::from(err));
-                            this.image_cols = 0;
-                            this.image_rows = 0;
-                            this.image_grid = None;
+        self.hex_status = Some("Searching...".into());
+        self._search_task = Some(cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                let Some(hex) = this.hex.as_mut() else {
+                    return;
+                };
+                this.hex_status = match result {
+                    Ok(Some(offset)) => {
+                        hex.found = Some(offset..offset + pattern.len() as u64);
+                        hex.pattern = Some(pattern);
+                        this.list_scroll
+                            .scroll_to_item(offset as usize / BYTES_PER_ROW, ScrollStrategy::Center);
+                        Some(format!("Found at 0x{offset:X}").into())
+                    }
+                    Ok(None) => Some("Pattern not found".into()),
+                    Err(err) => Some(format!("Search failed: {err}").into()),
+                };
+                cx.notify();
+            }) {
+                eprintln!("Update search result failed: {err}");
+            }
+        }));
+        cx.notify();
+    }
+
+    fn scroll_to_end(&self) {
+        if let Some(text) = &self.text {
+            self.list_scroll
+                .scroll_to_item(text.line_count.saturating_sub(1), ScrollStrategy::Top);
+        }
+    }
+
+    /// Like `tail -f`: re-reads the end of the file periodically and keeps
+    /// the last line in view while it grows.
+    fn set_following(&mut self, following: bool, cx: &mut Context<Self>) {
+        self.following = following;
+        self._follow_task = None;
+        if following {
+            self.show_source = true;
+            self.scroll_to_end();
+            self._follow_task = Some(cx.spawn(async move |this, cx| {
+                loop {
+                    cx.background_executor().timer(FOLLOW_INTERVAL).await;
+                    let Ok(Some(file)) = this.update(cx, |this, _| this.text.as_ref().map(|text| text.file.clone())) else {
+                        break;
+                    };
+                    let result = cx
+                        .background_spawn(async move { file.refresh().map(|changed| (changed, file.line_count())) })
+                        .await;
+                    let updated = this.update(cx, |this, cx| match result {
+                        Ok((true, line_count)) => {
+                            if let Some(text) = this.text.as_mut() {
+                                // A shrinking file was truncated or rotated;
+                                // its old highlights no longer line up.
+                                if line_count < text.line_count {
+                                    text.highlighter = None;
+                                }
+                                text.set_line_count(line_count);
+                            }
+                            this.scroll_to_end();
+                            cx.notify();
                         }
+                        Ok((false, _)) => {}
+                        Err(err) => eprintln!("Failed to follow file: {err}"),
+                    });
+                    if updated.is_err() {
+                        break;
                     }
-                    cx.notify();
+                }
+            }));
+        }
+        cx.notify();
+    }
+
+    fn cancel_background_work(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.cancel = Arc::new(AtomicBool::new(false));
+    }
+
+    /// Reads a page of lines in the background into the open document, as
+    /// long as it is still open.
+    fn load_page(&mut self, page: usize, cx: &mut Context<Self>) {
+        let Some(text) = self.text.as_ref() else {
+            return;
+        };
+        let file = text.file.clone();
+        let task = cx.background_spawn({
+            let file = file.clone();
+            let start = page * PAGE_LINES;
+            async move { file.lines(start..start + PAGE_LINES) }
+        });
+        cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                let Some(text) = this.text.as_mut().filter(|text| Arc::ptr_eq(&text.file, &file)) else {
+                    return;
+                };
+                // Dropped as stale while it was being read.
+                if !text.pages_loading.remove(&page) {
+                    return;
+                }
+                let lines = result.unwrap_or_else(|err| {
+                    // Kept empty rather than read again on every frame.
+                    eprintln!("Failed to read lines: {err}");
+                    Vec::new()
+                });
+                text.pages.insert(page, lines);
+                cx.notify();
+            }) {
+                eprintln!("Update text lines failed: {err}");
+            }
+        })
+        .detach();
+    }
+
+    fn load_hex_page(&mut self, page: usize, cx: &mut Context<Self>) {
+        let Some(hex) = self.hex.as_ref() else {
+            return;
+        };
+        let file = hex.file.clone();
+        let task = cx.background_spawn({
+            let file = file.clone();
+            let start = (page * HEX_PAGE_ROWS * BYTES_PER_ROW) as u64;
+            async move { file.read(start, HEX_PAGE_ROWS * BYTES_PER_ROW) }
+        });
+        cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                let Some(hex) = this.hex.as_mut().filter(|hex| Arc::ptr_eq(&hex.file, &file)) else {
+                    return;
+                };
+                if !hex.pages_loading.remove(&page) {
+                    return;
+                }
+                let bytes = result.unwrap_or_else(|err| {
+                    // Kept empty rather than read again on every frame.
+                    eprintln!("Failed to read bytes: {err}");
+                    Vec::new()
                 });
+                hex.pages.insert(page, bytes);
+                cx.notify();
+            }) {
+                eprintln!("Update hex rows failed: {err}");
             }
         })
         .detach();
     }
 
+    fn render_text(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let line_count = self.text.as_ref().map(|text| text.line_count).unwrap_or(0);
+        let gutter = px(8.0 * (line_count.max(1).ilog10() as f32 + 2.0));
+        uniform_list(
+            "text-lines",
+            line_count,
+            cx.processor(move |this, range: Range<usize>, _window, cx| {
+                let Some(text) = this.text.as_mut() else {
+                    return Vec::new();
+                };
+                for page in text.missing_pages(&range) {
+                    this.load_page(page, cx);
+                }
+                let Some(text) = this.text.as_ref() else {
+                    return Vec::new();
+                };
+                let panel = this.typography.as_ref().map(|(panel, _)| panel.read(cx));
+                range
+                    .map(|ix| {
+                        // Blank until its page has been read.
+                        let line = expand_tabs(text.line(ix).unwrap_or_default());
+                        let highlights = text.line_highlights(ix, line.len(), cx);
+                        h_flex()
+                            .h(px(20.0))
+                            .whitespace_nowrap()
+                            .child(
+                                div()
+                                    .w(gutter)
+                                    .flex_shrink_0()
+                                    .pr_3()
+                                    .text_right()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .when(panel.is_some_and(|panel| panel.has_issue_on(ix)), |this| {
+                                        this.border_l_2()
+                                            .border_color(cx.theme().warning)
+                                            .text_color(cx.theme().warning)
+                                    })
+                                    .child((ix + 1).to_string()),
+                            )
+                            .child(StyledText::new(line).with_highlights(highlights))
+                    })
+                    .collect()
+            }),
+        )
+        .track_scroll(self.list_scroll.clone())
+        .flex_1()
+        .p_4()
+        .font_family(MONO_FONT)
+        .text_sm()
+    }
+
+    fn render_hex(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let row_count = self.hex.as_ref().map(|hex| hex.file.row_count()).unwrap_or(0);
+        let toolbar = h_flex()
+            .gap_2()
+            .px_3()
+            .py_1()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .text_sm()
+            .child(div().w(px(160.0)).child(Input::new(&self.offset_input).small()))
+            .child(
+                Button::new("go-to-offset")
+                    .label("Go")
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, _, cx| this.go_to_offset(cx))),
+            )
+            .child(div().w(px(260.0)).child(Input::new(&self.search_input).small()))
+            .child(
+                Button::new("find-next")
+                    .label("Find next")
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, _, cx| this.find_next(cx))),
+            )
+            .children(
+                self.hex_status
+                    .clone()
+                    .map(|status| div().text_color(cx.theme().muted_foreground).child(status)),
+            );
+
+        let list = uniform_list(
+            "hex-rows",
+            row_count,
+            cx.processor(|this, range: Range<usize>, _window, cx| {
+                let Some(hex) = this.hex.as_mut() else {
+                    return Vec::new();
+                };
+                for page in hex.missing_pages(&range) {
+                    this.load_hex_page(page, cx);
+                }
+                let Some(hex) = this.hex.as_ref() else {
+                    return Vec::new();
+                };
+                range
+                    // Blank until its page has been read.
+                    .map(|ix| render_hex_row(ix, hex.row(ix).unwrap_or_default(), hex.found.clone(), cx))
+                    .collect()
+            }),
+        )
+        .track_scroll(self.list_scroll.clone())
+        .flex_1()
+        .p_4()
+        .font_family(MONO_FONT)
+        .text_sm();
+
+        v_flex().size_full().child(toolbar).child(list)
+    }
+
+    /// The preview of a Markdown file. `TextView` renders what it can; the
+    /// blocks with local links are rendered here, so those open in the viewer.
+    fn render_markdown(&self, preview: &Preview, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        // Keyed by viewer so each tab keeps its own parsed documents.
+        let key = SharedString::from(format!("markdown-{}", cx.entity_id()));
+        let mut next_id = 0;
+        let mut blocks = Vec::with_capacity(preview.blocks.len());
+        for block in &preview.blocks {
+            blocks.push(self.render_block(block, &key, &mut next_id, window, cx));
+        }
+        v_flex().id("markdown").size_full().overflow_y_scroll().p_4().gap_3().children(blocks)
+    }
+
+    fn render_block(
+        &self,
+        block: &Block,
+        key: &SharedString,
+        next_id: &mut usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        *next_id += 1;
+        let id = (key.clone(), *next_id);
+        match block {
+            Block::Markdown(source) => TextView::markdown(id, source.clone(), window, cx)
+                .selectable(true)
+                .into_any_element(),
+            Block::Heading(depth, inline) => {
+                let heading = div().font_weight(FontWeight::SEMIBOLD).child(self.render_inline(id, inline, cx));
+                match depth {
+                    1 => heading.text_2xl(),
+                    2 => heading.text_xl(),
+                    3 => heading.text_lg(),
+                    _ => heading,
+                }
+                .into_any_element()
+            }
+            Block::Paragraph(inline) => self.render_inline(id, inline, cx).into_any_element(),
+            Block::List { start, items } => {
+                let mut rows = Vec::with_capacity(items.len());
+                for (ix, item) in items.iter().enumerate() {
+                    let marker = match start {
+                        Some(start) => format!("{}.", *start as usize + ix),
+                        None => "•".to_string(),
+                    };
+                    let mut children = Vec::with_capacity(item.len());
+                    for block in item {
+                        children.push(self.render_block(block, key, next_id, window, cx));
+                    }
+                    rows.push(
+                        h_flex()
+                            .items_start()
+                            .gap_2()
+                            .child(div().flex_shrink_0().child(marker))
+                            .child(v_flex().flex_1().gap_1().children(children)),
+                    );
+                }
+                v_flex().gap_1().children(rows).into_any_element()
+            }
+            Block::Quote(quoted) => {
+                let mut children = Vec::with_capacity(quoted.len());
+                for block in quoted {
+                    children.push(self.render_block(block, key, next_id, window, cx));
+                }
+                v_flex()
+                    .gap_2()
+                    .pl_3()
+                    .border_l_4()
+                    .border_color(cx.theme().border)
+                    .text_color(cx.theme().muted_foreground)
+                    .children(children)
+                    .into_any_element()
+            }
+        }
+    }
+
+    /// Text with its links inline: local ones open in the viewer, the rest
+    /// with the system.
+    fn render_inline(&self, id: impl Into<ElementId>, inline: &Inline, cx: &mut Context<Self>) -> impl IntoElement {
+        let marks = inline.marks.iter().map(|(range, mark)| {
+            let style = match mark {
+                Mark::Strong => HighlightStyle {
+                    font_weight: Some(FontWeight::BOLD),
+                    ..Default::default()
+                },
+                Mark::Emphasis => HighlightStyle {
+                    font_style: Some(FontStyle::Italic),
+                    ..Default::default()
+                },
+                Mark::Delete => HighlightStyle {
+                    strikethrough: Some(StrikethroughStyle {
+                        thickness: px(1.0),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                Mark::Code => HighlightStyle {
+                    background_color: Some(cx.theme().muted),
+                    ..Default::default()
+                },
+            };
+            (range.clone(), style)
+        });
+        let link_style = HighlightStyle {
+            color: Some(cx.theme().link),
+            underline: Some(UnderlineStyle {
+                thickness: px(1.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let links = inline.links.iter().map(|(range, _)| (range.clone(), link_style));
+        let text = StyledText::new(inline.text.clone()).with_highlights(combine_highlights(marks, links));
+
+        let ranges = inline.links.iter().map(|(range, _)| range.clone()).collect();
+        let targets = inline.links.iter().map(|(_, target)| target.clone()).collect::<Vec<_>>();
+        let viewer = cx.entity().downgrade();
+        InteractiveText::new(id, text).on_click(ranges, move |ix, _, cx| match &targets[ix] {
+            Target::Local(path) => {
+                if let Err(err) = viewer.update(cx, |_, cx| cx.emit(ContentViewerEvent::Open(path.clone()))) {
+                    eprintln!("Open markdown link failed: {err}");
+                }
+            }
+            Target::Remote(url) => cx.open_url(url),
+        })
+    }
+
+    /// The open file's tags, each removable, and a field to add more.
+    fn render_tags(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let path = self.file_path.clone()?;
+        let chips = self.tags.read(cx).tags_for(&path).into_iter().enumerate().map(|(ix, tag)| {
+            let name = tag.name.clone();
+            let path = path.clone();
+            let tags = self.tags.clone();
+            tag_chip(ix, tag, move |_, cx| update_tags(&tags, cx, |db| db.remove(&path, &name)))
+        });
+        Some(
+            h_flex()
+                .gap_1()
+                .children(chips)
+                .child(div().w(px(120.0)).child(Input::new(&self.tag_input).xsmall())),
+        )
+    }
+
     pub fn is_image_name(name: &SharedString) -> bool {
         let s = name.to_string().to_lowercase();
-        s.ends_with(".png") || s.ends_with(".jpg") || s.ends_with(".jpeg") || s.ends_with(".gif") || s.ends_with(".bmp") || s.ends_with(".webp")
+        s.ends_with(".png") || s.ends_with(".jpg") || s.ends_with(".jpeg") || s.ends_with(".gif") || s.ends_with(".apng") || s.ends_with(".bmp") || s.ends_with(".webp")
     }
 }
 
 impl Render for ContentViewer {
-    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let header = if let Some(file_name) = &self.file_name {
-            div()
+            h_flex()
+                .justify_between()
                 .p_3()
                 .border_b_1()
                 .border_color(cx.theme().border)
                 .rounded(cx.theme().radius)
-                .child(div().child(format!("📄 {}", file_name)))
+                .child(
+                    h_flex()
+                        .gap_2()
+                        .child(format!("{} {}", if self.folder { "📁" } else { "📄" }, file_name))
+                        .children(self.render_tags(cx)),
+                )
+                .children(self.text.as_ref().map(|text| {
+                    let showing_lines = text.markdown.is_none() || self.show_source;
+                    h_flex()
+                        .gap_2()
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child(format!("{} · {} lines", text.language.name(), text.line_count))
+                        .child(
+                            Button::new("encoding")
+                                .label(if text.encoding_overridden {
+                                    format!("{} (manual)", text.file.encoding().name())
+                                } else {
+                                    text.file.encoding().name().to_string()
+                                })
+                                .small()
+                                .ghost()
+                                .selected(self.encoding_menu)
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.encoding_menu = !this.encoding_menu;
+                                    cx.notify();
+                                })),
+                        )
+                        .when(showing_lines, |this| {
+                            this.child(
+                                Button::new("scroll-top")
+                                    .label("Top")
+                                    .small()
+                                    .ghost()
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.set_following(false, cx);
+                                        this.list_scroll.scroll_to_item(0, ScrollStrategy::Top);
+                                    })),
+                            )
+                            .child(
+                                Button::new("scroll-end")
+                                    .label("End")
+                                    .small()
+                                    .ghost()
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.scroll_to_end();
+                                        cx.notify();
+                                    })),
+                            )
+                        })
+                        .child(
+                            Button::new("follow")
+                                .label("Follow")
+                                .small()
+                                .ghost()
+                                .selected(self.following)
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    let following = !this.following;
+                                    this.set_following(following, cx);
+                                })),
+                        )
+                        .child(
+                            Button::new("typography")
+                                .label("Typography")
+                                .small()
+                                .ghost()
+                                .selected(self.typography.is_some())
+                                .on_click(cx.listener(|this, _, _, cx| this.toggle_typography(cx))),
+                        )
+                        .when(text.markdown.is_some(), |this| {
+                            this.child(
+                                Button::new("markdown-mode")
+                                    .label(if self.show_source { "Preview" } else { "Source" })
+                                    .small()
+                                    .ghost()
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.show_source = !this.show_source;
+                                        if !this.show_source {
+                                            this.set_following(false, cx);
+                                        }
+                                        cx.notify();
+                                    })),
+                            )
+                        })
+                }))
+                .children(self.hex.as_ref().map(|hex| {
+                    h_flex()
+                        .gap_2()
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child(format!("Binary · {} bytes", hex.file.len()))
+                        // Text in an encoding the sniffing doesn't know can
+                        // look binary.
+                        .child(
+                            Button::new("encoding")
+                                .label("Open as Text…")
+                                .small()
+                                .ghost()
+                                .selected(self.encoding_menu)
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.encoding_menu = !this.encoding_menu;
+                                    cx.notify();
+                                })),
+                        )
+                }))
         } else {
-            div()
+            h_flex()
                 .p_3()
                 .border_b_1()
                 .border_color(cx.theme().border)
@@ -118,46 +1035,187 @@ impl Render for ContentViewer {
                 .child(div().child("No file selected"))
         };
 
+        let showing_file = self.text.is_some() || self.hex.is_some();
+        let encoding_menu = (showing_file && self.encoding_menu).then(|| {
+            let current = self.text.as_ref().map(|text| text.file.encoding());
+            h_flex()
+                .flex_wrap()
+                .gap_1()
+                .px_3()
+                .py_1()
+                .border_b_1()
+                .border_color(cx.theme().border)
+                .children(charset::CHOICES.iter().enumerate().map(|(ix, encoding)| {
+                    let encoding = *encoding;
+                    Button::new(("encoding-choice", ix))
+                        .label(encoding.name())
+                        .small()
+                        .ghost()
+                        .selected(Some(encoding) == current)
+                        .on_click(cx.listener(move |this, _, _, cx| this.reopen_with_encoding(encoding, cx)))
+                }))
+        });
+
         let body = if self.loading {
             div().size_full().flex().items_center().justify_center().child("Loading...")
         } else if let Some(err) = &self.error {
             div().size_full().flex().items_center().justify_center().child(format!("Error: {}", err))
-        } else if let Some(content) = &self.content {
-            div()
-                .flex_1()
-                .p_4()
-                .child(
-                    div()
-                        .font_family("Consolas, Monaco, 'Courier New', monospace")
-                        .text_sm()
-                        .whitespace_normal()
-                        .child(content.clone()),
-                )
-        } else if let Some(grid) = &self.image_grid {
-            let tile = {
-                let t = (1024.0 / (self.image_cols.max(1) as f32)).clamp(2.0, 8.0);
-                px(t)
-            };
-            let mut column_divs = Vec::new();
-            for row in grid.iter() {
-                let mut row_div = div().flex().gap(px(0.0));
-                for (r, g, b) in row.iter() {
-                    let color = ((*r as u32) << 16) | ((*g as u32) << 8) | (*b as u32);
-                    row_div = row_div.child(div().w(tile).h(tile).bg(gpui::rgb(color)));
-                }
-                column_divs.push(row_div);
-            }
-            let mut container = div().flex_col().p_4().gap(px(0.0));
-            for rd in column_divs {
-                container = container.child(rd);
+        } else if let Some(text) = &self.text {
+            match &text.markdown {
+                Some(preview) if !self.show_source => {
+                    div().size_full().flex().child(self.render_markdown(preview, window, cx))
+                }
+                _ => div().size_full().flex().child(self.render_text(cx)),
             }
-            container
+        } else if self.hex.is_some() {
+            div().size_full().flex().child(self.render_hex(cx))
+        } else if let Some(image) = &self.image {
+            div().size_full().child(image.clone())
+        } else if self.folder {
+            div().size_full().flex().items_center().justify_center().child("Tag the folder above, or double-click it in the tree to open it")
         } else {
             div().size_full().flex().items_center().justify_center().child("Select a file to view its contents")
         };
 
-        div().flex_1().flex_col().child(header).child(body)
+        let body = match &self.typography {
+            Some((panel, _)) => h_flex().size_full().child(body).child(
+                div()
+                    .w(px(360.0))
+                    .h_full()
+                    .flex_shrink_0()
+                    .border_l_1()
+                    .border_color(cx.theme().border)
+                    .child(panel.clone()),
+            ),
+            None => div().size_full().child(body),
+        };
+
+        div().flex_1().flex_col().child(header).children(encoding_menu).child(body)
+    }
+}
+
+/// Sniffs the start of the file to decide between the text and hex views,
+/// and which encoding to read text in unless one was `forced`.
+fn load_file(path: &Path, file_name: &str, forced: Option<&'static Encoding>) -> io::Result<Loaded> {
+    let mut sample = Vec::with_capacity(SNIFF_LEN);
+    fs::File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut sample)?;
+    let encoding = forced.unwrap_or_else(|| charset::detect(&sample));
+    // UTF-16 is full of NUL bytes but still text.
+    let utf16 = encoding == UTF_16LE || encoding == UTF_16BE;
+    if forced.is_none() && !utf16 && binary::is_binary(&sample) {
+        return HexFile::open(path).map(Loaded::Binary);
+    }
+    load_text(path, file_name, encoding, forced.is_some()).map(Loaded::Text)
+}
+
+fn load_text(
+    path: &Path,
+    file_name: &str,
+    encoding: &'static Encoding,
+    encoding_overridden: bool,
+) -> io::Result<TextDocument> {
+    let file = TextFile::open(path, encoding)?;
+    let first_line = file.lines(0..1)?.into_iter().next().unwrap_or_default();
+    let language = syntax::detect(file_name, &first_line);
+    let (highlighter, line_starts, markdown) = if file.len() <= FULL_READ_LIMIT {
+        let content = charset::decode(&fs::read(path)?, encoding);
+        // Highlighted as displayed, so the ranges fit the rendered lines.
+        let lines = content.lines().map(expand_tabs).collect::<Vec<_>>();
+        let mut line_starts = Vec::with_capacity(lines.len() + 1);
+        let mut start = 0;
+        for line in &lines {
+            line_starts.push(start);
+            start += line.len() + 1;
+        }
+        line_starts.push(start);
+        let mut highlighter = SyntaxHighlighter::new(language.name());
+        highlighter.update(None, &Rope::from(lines.join("\n").as_str()));
+        let markdown = path
+            .parent()
+            .filter(|_| language == Language::Markdown)
+            .map(|dir| markdown::prepare(&content, dir));
+        (Some(Box::new(highlighter)), line_starts, markdown)
+    } else {
+        (None, Vec::new(), None)
+    };
+    Ok(TextDocument {
+        line_count: file.line_count(),
+        file: Arc::new(file),
+        language,
+        highlighter,
+        line_starts,
+        markdown,
+        encoding_overridden,
+        pages: HashMap::new(),
+        pages_loading: HashSet::new(),
+    })
+}
+
+fn render_hex_row(ix: usize, row: &[u8], found: Option<Range<u64>>, cx: &App) -> impl IntoElement + use<> {
+    let offset = (ix * BYTES_PER_ROW) as u64;
+    let mut hex = String::with_capacity(BYTES_PER_ROW * 3 + 1);
+    let mut ascii = String::with_capacity(BYTES_PER_ROW);
+    for (col, byte) in row.iter().enumerate() {
+        if col == BYTES_PER_ROW / 2 {
+            hex.push(' ');
+        }
+        hex.push_str(&format!("{byte:02x} "));
+        ascii.push(if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' });
+    }
+
+    let mut hex_highlights = Vec::new();
+    let mut ascii_highlights = Vec::new();
+    if let Some(found) = found {
+        let style = HighlightStyle {
+            background_color: Some(cx.theme().selection),
+            ..Default::default()
+        };
+        let start = found.start.max(offset);
+        let end = found.end.min(offset + row.len() as u64);
+        if start < end {
+            let (first, last) = ((start - offset) as usize, (end - offset) as usize - 1);
+            let column = |col: usize| col * 3 + usize::from(col >= BYTES_PER_ROW / 2);
+            hex_highlights.push((column(first)..column(last) + 2, style));
+            ascii_highlights.push((first..last + 1, style));
+        }
+    }
+
+    h_flex()
+        .h(px(20.0))
+        .gap_4()
+        .whitespace_nowrap()
+        .child(
+            div()
+                .text_color(cx.theme().muted_foreground)
+                .child(format!("{offset:08x}")),
+        )
+        .child(
+            div()
+                .w(px(8.0 * (BYTES_PER_ROW * 3 + 1) as f32))
+                .child(StyledText::new(hex).with_highlights(hex_highlights)),
+        )
+        .child(StyledText::new(ascii).with_highlights(ascii_highlights))
+}
+
+fn expand_tabs(line: &str) -> String {
+    if !line.contains('\t') {
+        return line.to_string();
+    }
+    let mut expanded = String::with_capacity(line.len() + TAB_WIDTH);
+    let mut column = 0;
+    for ch in line.chars() {
+        if ch == '\t' {
+            let spaces = TAB_WIDTH - column % TAB_WIDTH;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(ch);
+            column += 1;
+        }
     }
+    expanded
 }
 
 #[cfg(test)]
@@ -165,11 +1223,66 @@ mod tests {
     use super::*;
     #[test]
     fn image_ext_recognition() {
-        for name in ["a.PNG", "b.jpg", "c.JPEG", "d.gif", "e.bmp", "f.webp"] {
+        for name in ["a.PNG", "b.jpg", "c.JPEG", "d.gif", "e.bmp", "f.webp", "g.apng"] {
             assert!(ContentViewer::is_image_name(&SharedString::from(name.to_string())));
         }
         for name in ["a.txt", "b.md", "c.rs"] {
             assert!(!ContentViewer::is_image_name(&SharedString::from(name.to_string())));
         }
     }
+
+    #[test]
+    fn pages_go_stale_from_the_old_last_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "line\n".repeat(PAGE_LINES * 2)).unwrap();
+        let mut text = load_text(&path, "app.log", encoding_rs::UTF_8, false).unwrap();
+
+        assert_eq!(text.missing_pages(&(10..PAGE_LINES + 10)), [0, 1]);
+        assert!(text.missing_pages(&(0..10)).is_empty());
+        text.pages_loading.clear();
+        text.pages.insert(0, vec!["line".to_string(); PAGE_LINES]);
+        text.pages.insert(1, vec!["line".to_string(); PAGE_LINES]);
+        assert_eq!(text.line(PAGE_LINES), Some("line"));
+
+        text.set_line_count(PAGE_LINES * 2 + 5);
+        assert!(text.pages.contains_key(&0) && !text.pages.contains_key(&1));
+        text.set_line_count(3);
+        assert!(text.pages.is_empty());
+    }
+
+    #[test]
+    fn hex_rows_come_from_their_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, [7u8; BYTES_PER_ROW * 3 - 4]).unwrap();
+        let file = HexFile::open(&path).unwrap();
+        let page = file.read(0, HEX_PAGE_ROWS * BYTES_PER_ROW).unwrap();
+        let mut hex = HexDocument::new(file);
+
+        assert_eq!(hex.missing_pages(&(0..3)), [0]);
+        assert!(hex.missing_pages(&(1..2)).is_empty());
+        assert_eq!(hex.row(0), None);
+        hex.pages.insert(0, page);
+        assert_eq!(hex.row(1), Some(&[7u8; BYTES_PER_ROW][..]));
+        assert_eq!(hex.row(2).map(<[u8]>::len), Some(BYTES_PER_ROW - 4));
+        assert_eq!(hex.row(3), None);
+    }
+
+    #[test]
+    fn highlights_files_read_whole_as_displayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        fs::write(&path, "fn main() {\n\tlet x = 1;\n}\n").unwrap();
+        let text = load_text(&path, "main.rs", encoding_rs::UTF_8, false).unwrap();
+
+        assert_eq!(text.language, Language::Rust);
+        // The tab counts as the spaces it is shown as.
+        assert_eq!(text.line_starts, [0, 12, 27, 29]);
+        let styles = text
+            .highlighter
+            .unwrap()
+            .styles(&(12..26), &gpui_component::highlighter::HighlightTheme::default_dark());
+        assert!(styles.iter().any(|(range, style)| range == &(16..19) && style.color.is_some()));
+    }
 }
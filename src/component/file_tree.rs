@@ -1,28 +1,83 @@
 use autocorrect::ignorer::Ignorer;
-use gpui::{actions, px, App, AppContext, Context, Entity, InteractiveElement, IntoElement, KeyBinding, ParentElement, Render, Styled, Window};
+use gpui::{actions, div, px, App, AppContext, ClickEvent, Context, Entity, EventEmitter, FocusHandle, Focusable, InteractiveElement, IntoElement, KeyBinding, MouseButton, MouseDownEvent, ParentElement, Pixels, Point, Render, SharedString, Styled, Subscription, Window, prelude::FluentBuilder};
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::input::{Input, InputEvent, InputState};
 use gpui_component::label::Label;
-use gpui_component::{h_flex, v_flex, ActiveTheme, IconName};
+use gpui_component::{h_flex, v_flex, ActiveTheme, IconName, Sizable};
 use gpui_component::list::ListItem;
 use gpui_component::tree::{TreeItem, TreeState, tree};
+use std::collections::HashSet;
 use std::fs::read_dir;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::component::popup_menu;
 
 const CONTEXT: &str = "FileTree";
+// Placeholder rows are keyed as a child of their folder so renaming the folder
+// rebases them like any other descendant. NUL can't appear in a real file name.
+const LOADING_ID: &str = "\0loading";
+const ERROR_ID: &str = "\0error";
 
-actions!(toshokan, [Rename, SelectItem]);
+actions!(toshokan, [Rename, CancelRename, SelectItem]);
 
-fn init(cx: &mut App) {
+pub(crate) fn init(cx: &mut App) {
     cx.bind_keys([
         KeyBinding::new("enter", Rename, Some(CONTEXT)),
+        KeyBinding::new("escape", CancelRename, Some(CONTEXT)),
         KeyBinding::new("space", SelectItem, Some(CONTEXT)),
     ])
 }
 
+struct RenameState {
+    path: PathBuf,
+    input: Entity<InputState>,
+    error: Option<SharedString>,
+    _subscription: Subscription,
+}
+
+/// The right-click menu, opened on `path` at `position` in the window.
+struct ContextMenu {
+    path: PathBuf,
+    position: Point<Pixels>,
+}
+
+#[derive(Clone)]
+pub enum FileTreeEvent {
+    Select(PathBuf),
+    Open(PathBuf),
+    Loaded(PathBuf),
+    /// Run autocorrect over the files in a folder.
+    Autocorrect(PathBuf),
+    OpenInNewTab(PathBuf),
+    /// Show everything again, rather than just the tagged files.
+    ClearFilter,
+}
+
 pub struct FileTree {
+    root: PathBuf,
+    ignorer: Ignorer,
     tree_state: Entity<TreeState>,
+    /// Focused after a rename; the tree's own handle isn't reachable.
+    focus_handle: FocusHandle,
+    items: Vec<TreeItem>,
+    loading: HashSet<SharedString>,
     selected_item: Option<TreeItem>,
+    rename: Option<RenameState>,
+    context_menu: Option<ContextMenu>,
+    /// Selected once the folder being loaded shows it.
+    pending_selection: Option<PathBuf>,
+    /// Entries marked by a directory comparison.
+    highlighted: HashSet<PathBuf>,
+    /// Folders to expand as soon as they are read, from a restored session.
+    pending_expanded: HashSet<PathBuf>,
+    /// The tagged paths picked in the Tags view; only they, the folders
+    /// leading to them and what's inside them are shown.
+    filter: Option<HashSet<PathBuf>>,
 }
 
+impl EventEmitter<FileTreeEvent> for FileTree {}
+
 impl FileTree {
     pub fn view(window: &mut Window, cx: &mut App) -> Entity<Self> {
         cx.new(|cx| Self::new(window, cx))
@@ -30,39 +85,298 @@ impl FileTree {
 
     fn new(_: &mut Window, cx: &mut Context<Self>) -> Self {
         let tree_state = cx.new(|cx| TreeState::new(cx));
+        // The tree toggles folders itself and only notifies, so expansions
+        // are picked up here.
+        cx.observe(&tree_state, |this, _, cx| this.load_expanded(cx)).detach();
+        let root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
 
-        Self::load_files(tree_state.clone(), PathBuf::from("./"), cx);
-
-        Self {
+        let mut this = Self {
+            ignorer: Ignorer::new(&root.to_string_lossy()),
+            root,
             tree_state,
+            focus_handle: cx.focus_handle(),
+            items: Vec::new(),
+            loading: HashSet::new(),
             selected_item: None,
-        }
+            rename: None,
+            context_menu: None,
+            pending_selection: None,
+            highlighted: HashSet::new(),
+            pending_expanded: HashSet::new(),
+            filter: None,
+        };
+        this.load_files(cx);
+        this
     }
 
-    fn load_files(state: Entity<TreeState>, path: PathBuf, cx: &mut App) {
-        cx.spawn(async move |cx| {
-            let ignorer = Ignorer::new(&path.to_string_lossy());
-            let items = build_file_items(&ignorer, &path, &path);
-            if let Err(err) = state.update(cx, |state, cx| {
-                state.set_items(items, cx);
+    /// Reads only the root's direct children; folders get a "Loading…" row
+    /// and are read when first expanded, see [`FileTree::load_children`].
+    fn load_files(&mut self, cx: &mut Context<Self>) {
+        let root = self.root.clone();
+        let task = cx.background_spawn({
+            let root = root.clone();
+            async move { read_entries(&root) }
+        });
+        cx.spawn(async move |this, cx| {
+            let entries = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                // Navigated elsewhere while this directory was being read.
+                if this.root != root {
+                    return;
+                }
+                this.ignorer = Ignorer::new(&root.to_string_lossy());
+                this.items = this.build_items(&root, entries);
+                this.loading.clear();
+                this.rename = None;
+                this.context_menu = None;
+                let selected = this
+                    .pending_selection
+                    .take()
+                    .and_then(|path| find_item(&this.items, &path.to_string_lossy()).cloned());
+                let selected_id = selected.as_ref().map(|item| item.id.to_string());
+                this.selected_item = selected;
+                this.sync_items(selected_id.as_deref(), cx);
+                cx.emit(FileTreeEvent::Loaded(root));
             }) {
                 eprintln!("FileTree set_items failed: {err}");
             }
         }).detach();
     }
 
-    fn on_action_rename(&mut self, _: &Rename, _: &mut Window, cx: &mut Context<Self>) {
-        if let Some(entry) = self.tree_state.read(cx).selected_entry() {
-            let _ = entry.item();
-            todo!()
+    fn load_children(&mut self, id: SharedString, cx: &mut Context<Self>) {
+        if !self.loading.insert(id.clone()) {
+            return;
+        }
+        let dir = PathBuf::from(id.to_string());
+        let task = cx.background_spawn({
+            let dir = dir.clone();
+            async move { read_entries(&dir) }
+        });
+        cx.spawn(async move |this, cx| {
+            let entries = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                if !this.loading.remove(&id) {
+                    return;
+                }
+                let children = this.build_items(&dir, entries);
+                let Some(item) = find_item_mut(&mut this.items, &id) else {
+                    return;
+                };
+                item.children = children;
+                let selected = this.selected_id(cx);
+                this.sync_items(selected.as_deref(), cx);
+                cx.emit(FileTreeEvent::Loaded(dir));
+            }) {
+                eprintln!("FileTree load children failed: {err}");
+            }
+        }).detach();
+    }
+
+    /// Starts reading every expanded folder that still shows the loading
+    /// placeholder.
+    fn load_expanded(&mut self, cx: &mut Context<Self>) {
+        fn collect(items: &[TreeItem], ids: &mut Vec<SharedString>) {
+            for item in items.iter().filter(|item| item.is_expanded()) {
+                if item.children.first().is_some_and(|child| child.id.ends_with(LOADING_ID)) {
+                    ids.push(item.id.clone());
+                } else {
+                    collect(&item.children, ids);
+                }
+            }
+        }
+        let mut ids = Vec::new();
+        collect(&self.items, &mut ids);
+        for id in ids {
+            self.load_children(id, cx);
+        }
+    }
+
+    fn build_items(&self, dir: &Path, entries: io::Result<Vec<(PathBuf, bool)>>) -> Vec<TreeItem> {
+        let entries = match entries {
+            Ok(entries) => entries,
+            Err(err) => return vec![placeholder(dir, ERROR_ID, format!("⚠ {err}"))],
+        };
+        let mut items = entries
+            .into_iter()
+            .filter(|(path, _)| !is_ignored(&self.ignorer, &self.root, path))
+            .map(|(path, is_dir)| new_item(&path, is_dir).expanded(self.pending_expanded.contains(&path)))
+            .collect::<Vec<_>>();
+        sort_items(&mut items);
+        items
+    }
+
+    fn on_action_rename(&mut self, _: &Rename, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(path) = self.target_path(cx) {
+            self.start_rename(&path, window, cx);
+        }
+    }
+
+    pub fn start_rename(&mut self, path: &Path, window: &mut Window, cx: &mut Context<Self>) {
+        if self.rename.is_some() {
+            return;
+        }
+        let id = path.to_string_lossy().to_string();
+        let Some(item) = find_item(&self.items, &id).cloned() else {
+            return;
+        };
+        self.sync_items(Some(&id), cx);
+
+        let label = item.label.clone();
+        let input = cx.new(|cx| InputState::new(window, cx).default_value(label));
+        let _subscription = cx.subscribe_in(
+            &input,
+            window,
+            |this, _, event: &InputEvent, window, cx| match event {
+                InputEvent::PressEnter { .. } => this.commit_rename(window, cx),
+                InputEvent::Blur => this.cancel_rename(&CancelRename, window, cx),
+                _ => {}
+            },
+        );
+        window.focus(&input.read(cx).focus_handle(cx));
+        self.rename = Some(RenameState {
+            path: PathBuf::from(item.id.to_string()),
+            input,
+            error: None,
+            _subscription,
+        });
+        cx.notify();
+    }
+
+    fn cancel_rename(&mut self, _: &CancelRename, _: &mut Window, cx: &mut Context<Self>) {
+        if self.rename.take().is_some() {
+            cx.notify();
         }
     }
 
+    fn commit_rename(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(rename) = self.rename.as_mut() else {
+            return;
+        };
+        let name = rename.input.read(cx).value().trim().to_string();
+        let old_path = rename.path.clone();
+        if old_path.file_name().is_some_and(|old| old == name.as_str()) {
+            self.cancel_rename(&CancelRename, window, cx);
+            return;
+        }
+
+        let result = validate_new_name(&old_path, &name)
+            .and_then(|new_path| {
+                std::fs::rename(&old_path, &new_path)
+                    .map(|_| new_path)
+                    .map_err(|err| format!("Rename failed: {err}"))
+            });
+        match result {
+            Ok(new_path) => {
+                self.rename = None;
+                self.apply_rename(&old_path, &new_path, cx);
+                window.focus(&self.focus_handle);
+            }
+            Err(err) => rename.error = Some(err.into()),
+        }
+        cx.notify();
+    }
+
+    /// Patch the renamed item (and the ids of its descendants) in place, so the
+    /// tree keeps its expansion state without walking the directory again.
+    pub fn apply_rename(&mut self, old_path: &Path, new_path: &Path, cx: &mut Context<Self>) {
+        if !rename_item(&mut self.items, old_path, new_path) {
+            return;
+        }
+        let new_id = new_path.to_string_lossy().to_string();
+        if self
+            .selected_item
+            .as_ref()
+            .is_some_and(|item| Path::new(item.id.as_ref()) == old_path)
+        {
+            self.selected_item = find_item(&self.items, &new_id).cloned();
+        }
+        self.sync_items(Some(&new_id), cx);
+    }
+
+    pub fn add_path(&mut self, path: &Path, cx: &mut Context<Self>) {
+        let id = path.to_string_lossy().to_string();
+        if find_item(&self.items, &id).is_some() || is_ignored(&self.ignorer, &self.root, path) {
+            return;
+        }
+        let Some(parent) = path.parent() else {
+            return;
+        };
+        let siblings = if parent == self.root {
+            &mut self.items
+        } else {
+            match find_item_mut(&mut self.items, &parent.to_string_lossy()) {
+                Some(parent) => &mut parent.children,
+                None => return,
+            }
+        };
+        // An unread folder picks the new entry up when it is expanded.
+        if siblings.iter().any(|item| is_placeholder(&item.id)) {
+            return;
+        }
+        siblings.push(new_item(path, path.is_dir()));
+        sort_items(siblings);
+        let selected = self.selected_id(cx);
+        self.sync_items(selected.as_deref(), cx);
+    }
+
+    pub fn remove_path(&mut self, path: &Path, cx: &mut Context<Self>) {
+        if !remove_item(&mut self.items, &path.to_string_lossy()) {
+            return;
+        }
+        if self
+            .selected_item
+            .as_ref()
+            .is_some_and(|item| Path::new(item.id.as_ref()).starts_with(path))
+        {
+            self.selected_item = None;
+        }
+        let selected = self.selected_id(cx);
+        self.sync_items(selected.as_deref(), cx);
+        cx.notify();
+    }
+
+    fn selected_id(&self, cx: &App) -> Option<String> {
+        self.tree_state
+            .read(cx)
+            .selected_entry()
+            .map(|entry| entry.item().id.to_string())
+    }
+
+    pub fn set_filter(&mut self, filter: Option<HashSet<PathBuf>>, cx: &mut Context<Self>) {
+        if self.filter == filter {
+            return;
+        }
+        self.filter = filter;
+        let selected = self.selected_id(cx);
+        self.sync_items(selected.as_deref(), cx);
+        cx.notify();
+    }
+
+    fn sync_items(&mut self, selected_id: Option<&str>, cx: &mut Context<Self>) {
+        let items = match &self.filter {
+            Some(filter) => filter_items(&self.items, filter),
+            None => self.items.clone(),
+        };
+        let selected = selected_id.and_then(|id| visible_index(&items, id));
+        self.tree_state.update(cx, |state, cx| {
+            state.set_items(items, cx);
+            state.set_selected_index(selected, cx);
+        });
+    }
+
     fn on_action_select_item(&mut self, _: &SelectItem, _: &mut Window, cx: &mut Context<Self>) {
         if let Some(entry) = self.tree_state.read(cx).selected_entry() {
-            self.selected_item = Some(entry.item().clone());
-            cx.notify();
+            self.select_item(entry.item().clone(), cx);
+        }
+    }
+
+    fn select_item(&mut self, item: TreeItem, cx: &mut Context<Self>) {
+        if is_placeholder(&item.id) {
+            return;
         }
+        cx.emit(FileTreeEvent::Select(PathBuf::from(item.id.to_string())));
+        self.selected_item = Some(item);
+        cx.notify();
     }
 
     pub fn selected_path(&self) -> Option<PathBuf> {
@@ -71,10 +385,125 @@ impl FileTree {
             .map(|item| PathBuf::from(item.id.to_string()))
     }
 
+    /// The row file operations act on: the keyboard cursor if there is one,
+    /// otherwise the last clicked item.
+    pub fn target_path(&self, cx: &App) -> Option<PathBuf> {
+        self.selected_id(cx)
+            .map(PathBuf::from)
+            .or_else(|| self.selected_path())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
     pub fn set_root_path(&mut self, path: PathBuf, cx: &mut Context<Self>) {
-        let state = self.tree_state.clone();
-        // Context<T> 可用作 &mut App 传递到 load_files
-        Self::load_files(state, path, cx);
+        self.root = path;
+        self.highlighted.clear();
+        self.pending_expanded.clear();
+        self.load_files(cx);
+    }
+
+    /// Expands `paths` as the folders containing them are read.
+    pub fn expand_on_load(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.pending_expanded.extend(paths);
+    }
+
+    /// Expanded folders, parents before their children.
+    pub fn expanded_paths(&self) -> Vec<PathBuf> {
+        fn walk(items: &[TreeItem], paths: &mut Vec<PathBuf>) {
+            for item in items.iter().filter(|item| item.is_expanded()) {
+                paths.push(PathBuf::from(item.id.to_string()));
+                walk(&item.children, paths);
+            }
+        }
+        let mut paths = Vec::new();
+        walk(&self.items, &mut paths);
+        paths
+    }
+
+    pub fn set_highlighted(&mut self, paths: HashSet<PathBuf>, cx: &mut Context<Self>) {
+        self.highlighted = paths;
+        cx.notify();
+    }
+
+    /// Like [`FileTree::set_root_path`], then selects `selected` once the
+    /// folder is read, if it is one of its direct entries.
+    pub fn set_root_path_selecting(&mut self, path: PathBuf, selected: Option<PathBuf>, cx: &mut Context<Self>) {
+        self.pending_selection = selected;
+        self.set_root_path(path, cx);
+    }
+
+    fn render_context_menu(&self, menu: &ContextMenu, cx: &mut Context<Self>) -> impl IntoElement {
+        let path = menu.path.clone();
+        let is_dir = path.is_dir();
+        popup_menu(
+            Some(menu.position),
+            v_flex()
+                .on_mouse_down_out(cx.listener(|this, _, _, cx| {
+                    this.context_menu = None;
+                    cx.notify();
+                }))
+                .child(
+                    Button::new("context-rename")
+                        .label("Rename")
+                        .small()
+                        .ghost()
+                        .w_full()
+                        .on_click(cx.listener({
+                            let path = path.clone();
+                            move |this, _, window, cx| {
+                                this.context_menu = None;
+                                this.start_rename(&path, window, cx);
+                            }
+                        })),
+                )
+                .when(is_dir, |this| {
+                    this.child(
+                        Button::new("context-open")
+                            .label("Open")
+                            .small()
+                            .ghost()
+                            .w_full()
+                            .on_click(cx.listener({
+                                let path = path.clone();
+                                move |this, _, _, cx| {
+                                    this.context_menu = None;
+                                    cx.emit(FileTreeEvent::Open(path.clone()));
+                                    cx.notify();
+                                }
+                            })),
+                    )
+                    .child(
+                        Button::new("context-new-tab")
+                            .label("Open in New Tab")
+                            .small()
+                            .ghost()
+                            .w_full()
+                            .on_click(cx.listener({
+                                let path = path.clone();
+                                move |this, _, _, cx| {
+                                    this.context_menu = None;
+                                    cx.emit(FileTreeEvent::OpenInNewTab(path.clone()));
+                                    cx.notify();
+                                }
+                            })),
+                    )
+                    .child(
+                        Button::new("context-autocorrect")
+                            .label("Autocorrect Folder…")
+                            .small()
+                            .ghost()
+                            .w_full()
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.context_menu = None;
+                                cx.emit(FileTreeEvent::Autocorrect(path.clone()));
+                                cx.notify();
+                            })),
+                    )
+                }),
+            cx,
+        )
     }
 }
 
@@ -84,15 +513,33 @@ impl Render for FileTree {
         v_flex()
             .id("file-tree-view")
             .key_context(CONTEXT)
+            .track_focus(&self.focus_handle)
             .on_action(cx.listener(Self::on_action_rename))
+            .on_action(cx.listener(Self::cancel_rename))
             .on_action(cx.listener(Self::on_action_select_item))
             .gap_5()
             .size_full()
+            .when(self.filter.is_some(), |this| {
+                this.child(
+                    h_flex()
+                        .justify_between()
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child("Showing tagged files only")
+                        .child(
+                            Button::new("clear-tag-filter")
+                                .label("Show All")
+                                .xsmall()
+                                .ghost()
+                                .on_click(cx.listener(|_, _, _, cx| cx.emit(FileTreeEvent::ClearFilter))),
+                        ),
+                )
+            })
             .child(
                 tree(
                     &self.tree_state,
                     move |idx, entry, _, _, cx| {
-                        view.update(cx, |_, cx| {
+                        view.update(cx, |this, cx| {
                             let item = entry.item();
                             let icon = if !entry.is_folder() {
                                 IconName::File
@@ -107,14 +554,55 @@ impl Render for FileTree {
                                 .rounded(cx.theme().radius)
                                 .px_3()
                                 .pl(px(16.) * entry.depth() + px(12.))
-                                .child(
-                                    h_flex().gap_2().child(icon).child(item.label.clone())
-                                )
+                                .child(match this.rename.as_ref().filter(|rename| {
+                                    rename.path.as_path() == Path::new(item.id.as_ref())
+                                }) {
+                                    Some(rename) => h_flex()
+                                        .gap_2()
+                                        .child(icon)
+                                        .child(
+                                            v_flex()
+                                                .flex_1()
+                                                .child(Input::new(&rename.input).small())
+                                                .children(rename.error.clone().map(|err| {
+                                                    div()
+                                                        .text_xs()
+                                                        .text_color(cx.theme().danger)
+                                                        .child(err)
+                                                })),
+                                        ),
+                                    None => h_flex()
+                                        .w_full()
+                                        .gap_2()
+                                        .when(this.highlighted.contains(Path::new(item.id.as_ref())), |this| {
+                                            this.text_color(cx.theme().warning)
+                                        })
+                                        .child(icon)
+                                        .child(item.label.clone())
+                                        .on_mouse_down(MouseButton::Right, cx.listener({
+                                            let path = PathBuf::from(item.id.to_string());
+                                            move |this, event: &MouseDownEvent, _, cx| {
+                                                if is_placeholder(&path.to_string_lossy()) {
+                                                    return;
+                                                }
+                                                this.context_menu = Some(ContextMenu {
+                                                    path: path.clone(),
+                                                    position: event.position,
+                                                });
+                                                cx.notify();
+                                            }
+                                        })),
+                                })
                                 .on_click(cx.listener({
                                     let item = item.clone();
-                                    move |this, _, _, cx| {
-                                        this.selected_item = Some(item.clone());
-                                        cx.notify()
+                                    // The tree expands a folder on the same click.
+                                    move |this, event: &ClickEvent, _, cx| {
+                                        let path = PathBuf::from(item.id.to_string());
+                                        if event.click_count() > 1 && path.is_dir() {
+                                            cx.emit(FileTreeEvent::Open(path));
+                                        } else {
+                                            this.select_item(item.clone(), cx);
+                                        }
                                     }
                                 }))
                         })
@@ -125,6 +613,7 @@ impl Render for FileTree {
                     .rounded(cx.theme().radius)
                     .h_full()
             )
+            .children(self.context_menu.as_ref().map(|menu| self.render_context_menu(menu, cx)))
             .child(
                 h_flex()
                     .w_full()
@@ -145,29 +634,159 @@ impl Render for FileTree {
     }
 }
 
-fn build_file_items(ignorer: &Ignorer, root: &PathBuf, path: &PathBuf) -> Vec<TreeItem> {
-    let mut items = Vec::new();
-    if let Ok(entries) = read_dir(path) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            let relative_path = path.strip_prefix(root).unwrap_or(&path);
-            if ignorer.is_ignored(&relative_path.to_string_lossy()) || relative_path.ends_with(".git") {
-                continue;
+/// `name` is expected to be trimmed already.
+fn validate_new_name(old_path: &Path, name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() {
+        return Err("Name cannot be empty".into());
+    }
+    if name == "." || name == ".." {
+        return Err(format!("\"{name}\" is not a valid name"));
+    }
+    if name.contains(['/', '\\']) {
+        return Err("Name cannot contain path separators".into());
+    }
+    let new_path = old_path.with_file_name(name);
+    // `exists` follows symlinks, so a dangling link would slip through without the metadata check.
+    if new_path.exists() || new_path.symlink_metadata().is_ok() {
+        return Err(format!("\"{name}\" already exists"));
+    }
+    Ok(new_path)
+}
+
+fn rename_item(items: &mut [TreeItem], old_path: &Path, new_path: &Path) -> bool {
+    let old_id = old_path.to_string_lossy();
+    if let Some(item) = items.iter_mut().find(|item| item.id.as_ref() == old_id) {
+        item.label = new_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+            .into();
+        rebase_item(item, old_path, new_path);
+        sort_items(items);
+        return true;
+    }
+    items
+        .iter_mut()
+        .any(|item| rename_item(&mut item.children, old_path, new_path))
+}
+
+fn rebase_item(item: &mut TreeItem, old_path: &Path, new_path: &Path) {
+    let path = PathBuf::from(item.id.to_string());
+    if let Ok(rest) = path.strip_prefix(old_path) {
+        item.id = new_path.join(rest).to_string_lossy().to_string().into();
+    }
+    for child in item.children.iter_mut() {
+        rebase_item(child, old_path, new_path);
+    }
+}
+
+fn find_item_mut<'a>(items: &'a mut [TreeItem], id: &str) -> Option<&'a mut TreeItem> {
+    for item in items.iter_mut() {
+        if item.id.as_ref() == id {
+            return Some(item);
+        }
+        if let Some(found) = find_item_mut(&mut item.children, id) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+fn remove_item(items: &mut Vec<TreeItem>, id: &str) -> bool {
+    if let Some(ix) = items.iter().position(|item| item.id.as_ref() == id) {
+        items.remove(ix);
+        return true;
+    }
+    items.iter_mut().any(|item| remove_item(&mut item.children, id))
+}
+
+fn find_item<'a>(items: &'a [TreeItem], id: &str) -> Option<&'a TreeItem> {
+    items.iter().find_map(|item| {
+        if item.id.as_ref() == id {
+            Some(item)
+        } else {
+            find_item(&item.children, id)
+        }
+    })
+}
+
+/// The rows to show under a tag filter. Placeholders stay so the folders
+/// leading to tagged files can still be expanded and read.
+fn filter_items(items: &[TreeItem], filter: &HashSet<PathBuf>) -> Vec<TreeItem> {
+    items
+        .iter()
+        .filter_map(|item| {
+            let path = Path::new(item.id.as_ref());
+            if filter.contains(path) || is_placeholder(&item.id) {
+                return Some(item.clone());
+            }
+            if !filter.iter().any(|tagged| tagged.starts_with(path)) {
+                return None;
+            }
+            let mut item = item.clone();
+            item.children = filter_items(&item.children, filter);
+            Some(item)
+        })
+        .collect()
+}
+
+/// Index of `id` among the rows the tree currently shows, i.e. skipping the
+/// children of collapsed folders.
+fn visible_index(items: &[TreeItem], id: &str) -> Option<usize> {
+    fn walk(items: &[TreeItem], id: &str, ix: &mut usize) -> bool {
+        for item in items {
+            if item.id.as_ref() == id {
+                return true;
             }
-            let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("Unknown").to_string();
-            let id = path.to_string_lossy().to_string();
-            if path.is_dir() {
-                let children = build_file_items(ignorer, &root, &path);
-                items.push(TreeItem::new(id, file_name).children(children));
-            } else {
-                items.push(TreeItem::new(id, file_name));
+            *ix += 1;
+            if item.is_expanded() && walk(&item.children, id, ix) {
+                return true;
             }
         }
+        false
     }
+    let mut ix = 0;
+    walk(items, id, &mut ix).then_some(ix)
+}
+
+fn sort_items(items: &mut [TreeItem]) {
     items.sort_by(|a, b| {
         b.is_folder()
             .cmp(&a.is_folder())
             .then(a.label.cmp(&b.label))
     });
-    items
+}
+
+pub(crate) fn is_ignored(ignorer: &Ignorer, root: &Path, path: &Path) -> bool {
+    let relative_path = path.strip_prefix(root).unwrap_or(path);
+    ignorer.is_ignored(&relative_path.to_string_lossy())
+        || relative_path.components().any(|c| c.as_os_str() == ".git")
+}
+
+fn read_entries(dir: &Path) -> io::Result<Vec<(PathBuf, bool)>> {
+    let mut entries = Vec::new();
+    for entry in read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let is_dir = path.is_dir();
+        entries.push((path, is_dir));
+    }
+    Ok(entries)
+}
+
+fn new_item(path: &Path, is_dir: bool) -> TreeItem {
+    let id = path.to_string_lossy().to_string();
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("Unknown").to_string();
+    if is_dir {
+        TreeItem::new(id, file_name).children(vec![placeholder(path, LOADING_ID, "Loading…")])
+    } else {
+        TreeItem::new(id, file_name)
+    }
+}
+
+fn placeholder(dir: &Path, kind: &str, label: impl Into<SharedString>) -> TreeItem {
+    TreeItem::new(dir.join(kind).to_string_lossy().to_string(), label)
+}
+
+fn is_placeholder(id: &str) -> bool {
+    id.ends_with(LOADING_ID) || id.ends_with(ERROR_ID)
 }
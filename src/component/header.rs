@@ -1,46 +1,477 @@
 use gpui::{
-    Context, CursorStyle, EventEmitter, FocusHandle, InteractiveElement, IntoElement, MouseButton, ParentElement,
-    Render, SharedString, Styled, Window, div, prelude::*, px,
+    App, ClickEvent, Context, Entity, EventEmitter, InteractiveElement, IntoElement, KeyBinding, MouseButton,
+    Focusable, MouseDownEvent, ParentElement, Pixels, Point, Render, SharedString, Styled, Subscription, Task, Window,
+    actions, div, prelude::*, px,
 };
-use gpui_component::ActiveTheme;
-use std::path::PathBuf;
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::input::{Input, InputEvent, InputState};
+use gpui_component::{h_flex, v_flex, ActiveTheme, Selectable, Sizable};
+use std::env;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+use std::time::Duration;
+
+use crate::component::popup_menu;
+use crate::path_complete;
+
+const PATH_INPUT_CONTEXT: &str = "PathInput";
+
+/// Paths with more segments than this show their middle ones in a menu.
+const MAX_SEGMENTS: usize = 5;
+/// Segments kept at the end of a collapsed path.
+const TAIL_SEGMENTS: usize = 3;
+
+actions!(toshokan, [CompletePath, NextCompletion, PreviousCompletion, CancelPathEdit]);
+
+pub(crate) fn init(cx: &mut App) {
+    // Bound on the input itself so they win over its own keys.
+    let context = format!("{PATH_INPUT_CONTEXT} > Input");
+    cx.bind_keys([
+        KeyBinding::new("tab", CompletePath, Some(&context)),
+        KeyBinding::new("down", NextCompletion, Some(&context)),
+        KeyBinding::new("up", PreviousCompletion, Some(&context)),
+        KeyBinding::new("escape", CancelPathEdit, Some(&context)),
+    ])
+}
+
+/// How long the back and forward buttons are held before they show their
+/// history instead.
+const LONG_PRESS: Duration = Duration::from_millis(400);
 
 #[derive(Clone)]
 pub enum HeaderEvent {
     NavigateTo(PathBuf),
+    /// Go this many steps back in the history.
+    GoBack(usize),
+    GoForward(usize),
+}
+
+/// A dropdown of places to jump to: a segment's siblings, the segments
+/// collapsed out of a long path, or the history behind a back or forward
+/// button.
+struct PathMenu {
+    position: Point<Pixels>,
+    entries: Vec<(SharedString, HeaderEvent)>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum HistoryButton {
+    Back,
+    Forward,
 }
 
 pub struct Header {
     pub current_path: PathBuf,
-    pub path_input: SharedString,
-    pub focus_handle: FocusHandle,
+    pub path_input: Entity<InputState>,
     pub is_editing: bool,
+    /// Folders matching what is typed, with the one picked by the arrow keys.
+    completions: Vec<PathBuf>,
+    completion_ix: Option<usize>,
+    _completion_task: Option<Task<()>>,
+    menu: Option<PathMenu>,
+    _menu_task: Option<Task<()>>,
+    /// Where the back and forward buttons lead, nearest first.
+    back: Vec<PathBuf>,
+    forward: Vec<PathBuf>,
+    /// A history button being held down, until it counts as a long press.
+    pressed: Option<(HistoryButton, Task<()>)>,
+    _subscriptions: Vec<Subscription>,
 }
 
 impl EventEmitter<HeaderEvent> for Header {}
 
 impl Header {
     pub fn view(
-        _window: &mut Window,
+        window: &mut Window,
         cx: &mut Context<Self>,
         current_path: Option<PathBuf>,
     ) -> Self {
         let path =
             current_path.unwrap_or(std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")));
+        let path_input = cx.new(|cx| InputState::new(window, cx).placeholder("Path, ~ and $VARS are expanded"));
+        let _subscriptions = vec![cx.subscribe_in(
+            &path_input,
+            window,
+            |this, _, event: &InputEvent, _, cx| match event {
+                InputEvent::Change => this.update_completions(cx),
+                InputEvent::PressEnter { .. } => this.commit_edit(cx),
+                InputEvent::Blur => this.cancel_edit(cx),
+                _ => {}
+            },
+        )];
         Self {
-            current_path: path.clone(),
-            path_input: SharedString::from(path.to_string_lossy().to_string()),
-            focus_handle: cx.focus_handle(),
+            current_path: path,
+            path_input,
             is_editing: false,
+            completions: Vec::new(),
+            completion_ix: None,
+            _completion_task: None,
+            menu: None,
+            _menu_task: None,
+            back: Vec::new(),
+            forward: Vec::new(),
+            pressed: None,
+            _subscriptions,
         }
     }
 
     pub fn set_path(&mut self, path: PathBuf) {
-        self.current_path = path.clone();
-        self.path_input = SharedString::from(path.to_string_lossy().to_string());
+        self.current_path = path;
+        self.menu = None;
+    }
+
+    pub fn set_history(&mut self, back: Vec<PathBuf>, forward: Vec<PathBuf>, cx: &mut Context<Self>) {
+        self.back = back;
+        self.forward = forward;
+        cx.notify();
+    }
+
+    /// A short press goes one step; holding the button, or right-clicking
+    /// it, lists the whole history in that direction.
+    fn press_history(&mut self, button: HistoryButton, position: Point<Pixels>, cx: &mut Context<Self>) {
+        let task = cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(LONG_PRESS).await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                if this.pressed.take().is_some() {
+                    this.show_history(button, position, cx);
+                }
+            }) {
+                eprintln!("Show history failed: {err}");
+            }
+        });
+        self.pressed = Some((button, task));
+    }
+
+    fn release_history(&mut self, button: HistoryButton, cx: &mut Context<Self>) {
+        if self.pressed.take().is_some_and(|(pressed, _)| pressed == button) {
+            cx.emit(match button {
+                HistoryButton::Back => HeaderEvent::GoBack(1),
+                HistoryButton::Forward => HeaderEvent::GoForward(1),
+            });
+        }
+    }
+
+    fn cancel_history_press(&mut self, button: HistoryButton) {
+        if self.pressed.as_ref().is_some_and(|(pressed, _)| *pressed == button) {
+            self.pressed = None;
+        }
+    }
+
+    fn show_history(&mut self, button: HistoryButton, position: Point<Pixels>, cx: &mut Context<Self>) {
+        let paths = match button {
+            HistoryButton::Back => &self.back,
+            HistoryButton::Forward => &self.forward,
+        };
+        let entries = paths
+            .iter()
+            .enumerate()
+            .map(|(ix, path)| {
+                let event = match button {
+                    HistoryButton::Back => HeaderEvent::GoBack(ix + 1),
+                    HistoryButton::Forward => HeaderEvent::GoForward(ix + 1),
+                };
+                (SharedString::from(path.to_string_lossy().to_string()), event)
+            })
+            .collect();
+        self.menu = Some(PathMenu { position, entries });
+        cx.notify();
+    }
+
+    fn render_history_button(&self, button: HistoryButton, cx: &mut Context<Self>) -> impl IntoElement {
+        let (id, label, enabled) = match button {
+            HistoryButton::Back => ("history-back", "◀", !self.back.is_empty()),
+            HistoryButton::Forward => ("history-forward", "▶", !self.forward.is_empty()),
+        };
+        let hover_bg = cx.theme().muted;
+        div()
+            .id(id)
+            .px_2()
+            .py_1()
+            .rounded(cx.theme().radius)
+            .child(label)
+            .map(|this| {
+                if !enabled {
+                    return this.text_color(cx.theme().muted_foreground);
+                }
+                this.cursor_pointer()
+                    .hover(move |this| this.bg(hover_bg))
+                    .on_mouse_down(
+                        MouseButton::Left,
+                        cx.listener(move |this, event: &MouseDownEvent, _, cx| {
+                            this.press_history(button, event.position, cx)
+                        }),
+                    )
+                    .on_mouse_up(
+                        MouseButton::Left,
+                        cx.listener(move |this, _, _, cx| this.release_history(button, cx)),
+                    )
+                    // Letting go elsewhere neither navigates nor leaves the
+                    // long press pending.
+                    .on_mouse_up_out(
+                        MouseButton::Left,
+                        cx.listener(move |this, _, _, _| this.cancel_history_press(button)),
+                    )
+                    .on_mouse_down(
+                        MouseButton::Right,
+                        cx.listener(move |this, event: &MouseDownEvent, _, cx| {
+                            this.show_history(button, event.position, cx)
+                        }),
+                    )
+            })
+    }
+
+    fn start_edit(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let path = self.current_path.to_string_lossy().to_string();
+        self.path_input.update(cx, |input, cx| input.set_value(path, window, cx));
+        window.focus(&self.path_input.read(cx).focus_handle(cx));
+        self.is_editing = true;
+        self.menu = None;
+        self.update_completions(cx);
+    }
+
+    fn cancel_edit(&mut self, cx: &mut Context<Self>) {
+        self.is_editing = false;
+        self.completions.clear();
+        self.completion_ix = None;
+        self._completion_task = None;
+        cx.notify();
+    }
+
+    /// Navigates to the typed path, or to the completion picked with the
+    /// arrow keys. Anything that isn't a folder keeps the editor open.
+    fn commit_edit(&mut self, cx: &mut Context<Self>) {
+        let path = match self.completion_ix.and_then(|ix| self.completions.get(ix)) {
+            Some(path) => path.clone(),
+            None => PathBuf::from(path_complete::expand(&self.path_input.read(cx).value(), |name| env::var_os(name))),
+        };
+        if path.is_dir() {
+            self.cancel_edit(cx);
+            self.navigate(path, cx);
+        }
+    }
+
+    fn update_completions(&mut self, cx: &mut Context<Self>) {
+        let input = self.path_input.read(cx).value().to_string();
+        let task = cx.background_spawn(async move { path_complete::completions(&input, |name| env::var_os(name)) });
+        self._completion_task = Some(cx.spawn(async move |this, cx| {
+            let completions = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.completions = completions;
+                this.completion_ix = None;
+                cx.notify();
+            }) {
+                eprintln!("Update path completions failed: {err}");
+            }
+        }));
+    }
+
+    fn set_input(&mut self, value: String, window: &mut Window, cx: &mut Context<Self>) {
+        self.path_input.update(cx, |input, cx| input.set_value(value, window, cx));
+        self.update_completions(cx);
+    }
+
+    fn on_action_complete(&mut self, _: &CompletePath, window: &mut Window, cx: &mut Context<Self>) {
+        let completion = match self.completion_ix.and_then(|ix| self.completions.get(ix)) {
+            Some(path) => Some(format!("{}{MAIN_SEPARATOR}", path.to_string_lossy())),
+            None => path_complete::complete(&self.path_input.read(cx).value(), &self.completions, |name| {
+                env::var_os(name)
+            }),
+        };
+        if let Some(completion) = completion {
+            self.set_input(completion, window, cx);
+        }
+    }
+
+    fn on_action_next_completion(&mut self, _: &NextCompletion, _: &mut Window, cx: &mut Context<Self>) {
+        if self.completions.is_empty() {
+            return;
+        }
+        self.completion_ix = Some(match self.completion_ix {
+            Some(ix) => (ix + 1) % self.completions.len(),
+            None => 0,
+        });
+        cx.notify();
+    }
+
+    fn on_action_previous_completion(&mut self, _: &PreviousCompletion, _: &mut Window, cx: &mut Context<Self>) {
+        if self.completions.is_empty() {
+            return;
+        }
+        self.completion_ix = Some(match self.completion_ix {
+            Some(0) | None => self.completions.len() - 1,
+            Some(ix) => ix - 1,
+        });
+        cx.notify();
+    }
+
+    fn on_action_cancel(&mut self, _: &CancelPathEdit, _: &mut Window, cx: &mut Context<Self>) {
+        self.cancel_edit(cx);
     }
 
-    pub fn go_back(&mut self, cx: &mut Context<Self>) -> Option<PathBuf> {
+    fn render_completions(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let entries = self.completions.iter().enumerate().map(|(ix, path)| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            div()
+                .px_2()
+                .py_1()
+                .text_sm()
+                .rounded(cx.theme().radius)
+                .when(self.completion_ix == Some(ix), |this| this.bg(cx.theme().muted))
+                .child(name)
+                // Mouse down rather than click: the input loses focus, and
+                // closes the editor, before a click would land.
+                .on_mouse_down(MouseButton::Left, cx.listener({
+                    let path = path.clone();
+                    move |this, _, window, cx| {
+                        cx.stop_propagation();
+                        let value = format!("{}{MAIN_SEPARATOR}", path.to_string_lossy());
+                        this.set_input(value, window, cx);
+                    }
+                }))
+        });
+        popup_menu(
+            None,
+            v_flex().id("path-completions").max_h(px(320.)).overflow_y_scroll().children(entries),
+            cx,
+        )
+    }
+
+    fn navigate(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        self.set_path(path.clone());
+        cx.emit(HeaderEvent::NavigateTo(path));
+        cx.notify();
+    }
+
+    /// Opens the dropdown of directories next to `path`, i.e. the folders in
+    /// its parent.
+    fn show_siblings(&mut self, path: PathBuf, position: Point<Pixels>, cx: &mut Context<Self>) {
+        let Some(parent) = path.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        let task = cx.background_spawn(async move { sibling_dirs(&parent) });
+        self._menu_task = Some(cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                match result {
+                    Ok(dirs) => {
+                        let entries = dirs
+                            .into_iter()
+                            .map(|(name, path)| (name, HeaderEvent::NavigateTo(path)))
+                            .collect();
+                        this.menu = Some(PathMenu { position, entries });
+                    }
+                    Err(err) => eprintln!("Failed to list sibling folders: {err}"),
+                }
+                cx.notify();
+            }) {
+                eprintln!("Update sibling folders failed: {err}");
+            }
+        }));
+    }
+
+    fn render_breadcrumbs(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let segments = segments(&self.current_path);
+        let hidden = hidden_segments(segments.len());
+        let mut crumbs = Vec::new();
+        for (ix, (name, path)) in segments.iter().enumerate() {
+            if let Some(hidden) = hidden.as_ref().filter(|hidden| hidden.contains(&ix)) {
+                if ix == hidden.start {
+                    let entries = segments[hidden.clone()]
+                        .iter()
+                        .map(|(name, path)| (name.clone(), HeaderEvent::NavigateTo(path.clone())))
+                        .collect::<Vec<_>>();
+                    crumbs.push(
+                        Button::new("path-overflow")
+                            .label("…")
+                            .small()
+                            .ghost()
+                            .on_click(cx.listener(move |this, event: &ClickEvent, _, cx| {
+                                this.menu = Some(PathMenu {
+                                    position: event.position(),
+                                    entries: entries.clone(),
+                                });
+                                cx.notify();
+                            }))
+                            .into_any_element(),
+                    );
+                }
+                continue;
+            }
+            crumbs.push(
+                Button::new(("path-segment", ix))
+                    .label(name.clone())
+                    .small()
+                    .ghost()
+                    .selected(ix + 1 == segments.len())
+                    .on_click(cx.listener({
+                        let path = path.clone();
+                        move |this, _, _, cx| this.navigate(path.clone(), cx)
+                    }))
+                    .into_any_element(),
+            );
+            if path.parent().is_some() {
+                crumbs.push(
+                    Button::new(("path-siblings", ix))
+                        .label("›")
+                        .small()
+                        .ghost()
+                        .on_click(cx.listener({
+                            let path = path.clone();
+                            move |this, event: &ClickEvent, _, cx| {
+                                this.show_siblings(path.clone(), event.position(), cx)
+                            }
+                        }))
+                        .into_any_element(),
+                );
+            }
+        }
+        h_flex().gap_1().children(crumbs)
+    }
+
+    fn render_menu(&self, menu: &PathMenu, cx: &mut Context<Self>) -> impl IntoElement {
+        let entries = menu.entries.iter().enumerate().map(|(ix, (name, event))| {
+            Button::new(("path-menu-entry", ix))
+                .label(name.clone())
+                .small()
+                .ghost()
+                .w_full()
+                .selected(matches!(event, HeaderEvent::NavigateTo(path) if self.current_path.starts_with(path)))
+                .on_click(cx.listener({
+                    let event = event.clone();
+                    move |this, _, _, cx| match &event {
+                        HeaderEvent::NavigateTo(path) => this.navigate(path.clone(), cx),
+                        event => {
+                            this.menu = None;
+                            cx.emit(event.clone());
+                            cx.notify();
+                        }
+                    }
+                }))
+        });
+        popup_menu(
+            Some(menu.position),
+            v_flex()
+                .id("path-menu")
+                .max_h(px(360.))
+                .overflow_y_scroll()
+                .on_mouse_down_out(cx.listener(|this, _, _, cx| {
+                    this.menu = None;
+                    cx.notify();
+                }))
+                .children(entries)
+                .when(menu.entries.is_empty(), |this| {
+                    this.p_2().text_sm().text_color(cx.theme().muted_foreground).child("Nothing here")
+                }),
+            cx,
+        )
+    }
+
+    pub fn go_up(&mut self, cx: &mut Context<Self>) -> Option<PathBuf> {
         if let Some(parent) = self.current_path.parent() {
             let parent_path = parent.to_path_buf();
             self.set_path(parent_path.clone());
@@ -55,10 +486,8 @@ impl Header {
 
 impl Render for Header {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        let focus_handle = self.focus_handle.clone();
         let is_editing = self.is_editing;
-        let path_input = self.path_input.clone();
-        let can_go_back = self.current_path.parent().is_some();
+        let can_go_up = self.current_path.parent().is_some();
 
         div()
             .flex()
@@ -68,78 +497,106 @@ impl Render for Header {
             .min_h(px(48.0))
             .border_b_1()
             .border_color(cx.theme().border)
+            .child(self.render_history_button(HistoryButton::Back, cx))
+            .child(self.render_history_button(HistoryButton::Forward, cx))
             .child(
                 div()
                     .mr_3()
                     .px_2()
                     .py_1()
                     .rounded(cx.theme().radius)
-                    .child("← Back")
-                    .when(can_go_back, |s| {
+                    .child("↑ Up")
+                    .when(can_go_up, |s| {
                             s.on_mouse_down(MouseButton::Left, cx.listener(|this, _event, _window, cx| {
-                                this.go_back(cx);
+                                this.go_up(cx);
                             }))
                         }),
             )
-            .child(div().mr_2().child("Path:"))
-            .child(
-                div()
-                    .flex_1()
-                    .p_2()
-                    .border_1()
-                    .border_color(cx.theme().border)
-                    .rounded(cx.theme().radius)
-                    .child(path_input)
-                    .cursor(if is_editing {
-                        CursorStyle::IBeam
-                    } else {
-                        CursorStyle::Arrow
-                    })
-                    .track_focus(&focus_handle)
-                    .on_key_down(
-                        cx.listener(|this, event: &gpui::KeyDownEvent, _window, cx| {
-                            if this.is_editing {
-                                match event.keystroke.key.as_str() {
-                                    "enter" => {
-                                        let new_path = PathBuf::from(this.path_input.to_string());
-                                        if new_path.exists() && new_path.is_dir() {
-                                            this.set_path(new_path.clone());
-                                            this.is_editing = false;
-                                            cx.emit(HeaderEvent::NavigateTo(new_path));
-                                            cx.notify();
-                                        }
-                                    }
-                                    "escape" => {
-                                        this.is_editing = false;
-                                        this.set_path(this.current_path.clone());
-                                        cx.notify();
-                                    }
-                                    "backspace" => {
-                                        let mut text = this.path_input.to_string();
-                                        text.pop();
-                                        this.path_input = SharedString::from(text);
-                                        cx.notify();
-                                    }
-                                    key => {
-                                        if key.len() == 1 {
-                                            let mut text = this.path_input.to_string();
-                                            text.push_str(key);
-                                            this.path_input = SharedString::from(text);
-                                            cx.notify();
-                                        }
-                                    }
-                                }
-                            }
-                        }),
+            .map(|this| {
+                if is_editing {
+                    this.child(
+                        v_flex()
+                            .flex_1()
+                            .key_context(PATH_INPUT_CONTEXT)
+                            .on_action(cx.listener(Self::on_action_complete))
+                            .on_action(cx.listener(Self::on_action_next_completion))
+                            .on_action(cx.listener(Self::on_action_previous_completion))
+                            .on_action(cx.listener(Self::on_action_cancel))
+                            .child(Input::new(&self.path_input))
+                            .when(!self.completions.is_empty(), |this| {
+                                this.child(self.render_completions(cx))
+                            }),
                     )
-                    .on_mouse_down(
-                        MouseButton::Left,
-                        cx.listener(move |this, _event, window, cx| {
-                            this.is_editing = true;
-                            window.focus(&focus_handle);
-                            cx.notify();
-                        }),
-                    ),
-            )
+                } else {
+                    this.child(self.render_breadcrumbs(cx)).child(
+                        // The empty space after the breadcrumbs switches to
+                        // typing a path.
+                        div()
+                            .flex_1()
+                            .min_h(px(32.0))
+                            .on_mouse_down(
+                                MouseButton::Left,
+                                cx.listener(|this, _, window, cx| this.start_edit(window, cx)),
+                            ),
+                    )
+                }
+            })
+            .children(self.menu.as_ref().map(|menu| self.render_menu(menu, cx)))
+    }
+}
+
+/// Each ancestor of `path`, root first, with the name shown for it.
+fn segments(path: &Path) -> Vec<(SharedString, PathBuf)> {
+    let mut segments = path
+        .ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .map(|ancestor| {
+            let name = ancestor
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| ancestor.display().to_string());
+            (SharedString::from(name), ancestor.to_path_buf())
+        })
+        .collect::<Vec<_>>();
+    segments.reverse();
+    segments
+}
+
+/// The segments a long path collapses into the overflow menu: everything
+/// between the root and the last few.
+fn hidden_segments(count: usize) -> Option<Range<usize>> {
+    (count > MAX_SEGMENTS).then(|| 1..count - TAIL_SEGMENTS)
+}
+
+fn sibling_dirs(parent: &Path) -> io::Result<Vec<(SharedString, PathBuf)>> {
+    let mut dirs = fs::read_dir(parent)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .map(|path| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            (SharedString::from(name), path)
+        })
+        .collect::<Vec<_>>();
+    dirs.sort_by_key(|(name, _)| name.to_lowercase());
+    Ok(dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_the_middle_of_long_paths() {
+        let names = segments(Path::new("/home/me/src/toshokan/src/component"))
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["/", "home", "me", "src", "toshokan", "src", "component"]);
+        assert_eq!(hidden_segments(names.len()), Some(1..4));
+        assert_eq!(hidden_segments(MAX_SEGMENTS), None);
     }
 }
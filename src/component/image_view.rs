@@ -0,0 +1,442 @@
+use gpui::{
+    App, Bounds, Context, Corners, InteractiveElement, IntoElement, MouseButton, MouseDownEvent,
+    MouseMoveEvent, ParentElement, Pixels, Render, RenderImage, ScrollWheelEvent, SharedString,
+    Size, Styled, Task, Window, canvas, div, fill, point, prelude::FluentBuilder, px, size,
+};
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::{h_flex, v_flex, ActiveTheme, Selectable, Sizable};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, Frame, Frames};
+use std::cell::Cell;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Decoded animation frames kept in memory at most; later frames are left
+/// out rather than letting a long animation take gigabytes.
+const MAX_DECODED_BYTES: usize = 256 * 1024 * 1024;
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 32.0;
+/// Large enough that a full viewport takes a few hundred quads, not tens of
+/// thousands.
+const CHECKER_SIZE: f32 = 32.0;
+
+/// Shows a decoded image on a checkerboard, fitted to the viewport or at a
+/// fixed zoom, and lets the user zoom with the wheel and pan by dragging.
+/// Animated images play on their own frame timing and can be paused and
+/// stepped.
+pub struct DecodedImage {
+    pub image: Arc<RenderImage>,
+    /// Frames past [`MAX_DECODED_BYTES`] were left out.
+    pub truncated: bool,
+}
+
+pub struct ImageView {
+    image: Arc<RenderImage>,
+    truncated: bool,
+    frame: usize,
+    _playback: Option<Task<()>>,
+    /// `None` keeps the image fitted to the viewport as it resizes.
+    zoom: Option<f32>,
+    pan: (f32, f32),
+    drag_origin: Option<(f32, f32)>,
+    viewport: Rc<Cell<Bounds<Pixels>>>,
+}
+
+impl ImageView {
+    pub fn new(decoded: DecodedImage, cx: &mut Context<Self>) -> Self {
+        cx.on_release(|this, cx| release_image(this.image.clone(), cx)).detach();
+        let mut this = Self {
+            image: decoded.image,
+            truncated: decoded.truncated,
+            frame: 0,
+            _playback: None,
+            zoom: None,
+            pan: (0.0, 0.0),
+            drag_origin: None,
+            viewport: Rc::new(Cell::new(Bounds::default())),
+        };
+        if this.frame_count() > 1 {
+            this.play(cx);
+        }
+        this
+    }
+
+    /// Swaps in the fully decoded animation for its first frame, keeping the
+    /// zoom and pan.
+    pub fn set_image(&mut self, decoded: DecodedImage, cx: &mut Context<Self>) {
+        release_image(std::mem::replace(&mut self.image, decoded.image), cx);
+        self.truncated = decoded.truncated;
+        self.frame = 0;
+        self._playback = None;
+        if self.frame_count() > 1 {
+            self.play(cx);
+        }
+        cx.notify();
+    }
+
+    fn frame_count(&self) -> usize {
+        self.image.frame_count()
+    }
+
+    fn is_playing(&self) -> bool {
+        self._playback.is_some()
+    }
+
+    fn play(&mut self, cx: &mut Context<Self>) {
+        self._playback = Some(cx.spawn(async move |this, cx| {
+            loop {
+                let Ok(delay) = this.update(cx, |this, _| frame_delay(&this.image, this.frame)) else {
+                    break;
+                };
+                cx.background_executor().timer(delay).await;
+                let advanced = this.update(cx, |this, cx| {
+                    this.frame = (this.frame + 1) % this.frame_count();
+                    cx.notify();
+                });
+                if advanced.is_err() {
+                    break;
+                }
+            }
+        }));
+        cx.notify();
+    }
+
+    fn pause(&mut self, cx: &mut Context<Self>) {
+        self._playback = None;
+        cx.notify();
+    }
+
+    fn step(&mut self, forward: bool, cx: &mut Context<Self>) {
+        self._playback = None;
+        let count = self.frame_count();
+        self.frame = if forward {
+            (self.frame + 1) % count
+        } else {
+            (self.frame + count - 1) % count
+        };
+        cx.notify();
+    }
+
+    fn image_size(&self) -> (f32, f32) {
+        let size = self.image.size(0);
+        (size.width.0 as f32, size.height.0 as f32)
+    }
+
+    /// The scale the image is drawn at right now; fitting only ever shrinks,
+    /// so small images stay crisp at 1:1.
+    fn scale(&self) -> f32 {
+        self.zoom.unwrap_or_else(|| {
+            let viewport = self.viewport.get().size;
+            fit_scale(self.image_size(), (f32::from(viewport.width), f32::from(viewport.height)))
+        })
+    }
+
+    fn set_zoom(&mut self, zoom: Option<f32>, cx: &mut Context<Self>) {
+        self.zoom = zoom.map(|zoom| zoom.clamp(MIN_ZOOM, MAX_ZOOM));
+        self.pan = (0.0, 0.0);
+        cx.notify();
+    }
+
+    /// Zooms by `factor` keeping the image point under the cursor in place.
+    fn zoom_at(&mut self, factor: f32, position: (f32, f32), cx: &mut Context<Self>) {
+        let old = self.scale();
+        let new = (old * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let center = self.viewport.get().center();
+        let cursor = (position.0 - f32::from(center.x), position.1 - f32::from(center.y));
+        let k = new / old;
+        self.pan = (
+            cursor.0 - (cursor.0 - self.pan.0) * k,
+            cursor.1 - (cursor.1 - self.pan.1) * k,
+        );
+        self.zoom = Some(new);
+        cx.notify();
+    }
+
+    fn on_scroll(&mut self, event: &ScrollWheelEvent, _: &mut Window, cx: &mut Context<Self>) {
+        let delta = f32::from(event.delta.pixel_delta(px(20.0)).y);
+        if delta == 0.0 {
+            return;
+        }
+        let position = (f32::from(event.position.x), f32::from(event.position.y));
+        self.zoom_at((delta / 200.0).exp(), position, cx);
+    }
+
+    fn on_mouse_down(&mut self, event: &MouseDownEvent, _: &mut Window, _: &mut Context<Self>) {
+        self.drag_origin = Some((f32::from(event.position.x), f32::from(event.position.y)));
+    }
+
+    fn on_mouse_move(&mut self, event: &MouseMoveEvent, _: &mut Window, cx: &mut Context<Self>) {
+        let Some(origin) = self.drag_origin else {
+            return;
+        };
+        if !event.dragging() {
+            self.drag_origin = None;
+            return;
+        }
+        let position = (f32::from(event.position.x), f32::from(event.position.y));
+        self.pan = (self.pan.0 + position.0 - origin.0, self.pan.1 + position.1 - origin.1);
+        self.drag_origin = Some(position);
+        cx.notify();
+    }
+
+    fn render_toolbar(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let (width, height) = self.image_size();
+        h_flex()
+            .gap_1()
+            .px_3()
+            .py_1()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .text_sm()
+            .child(
+                Button::new("zoom-fit")
+                    .label("Fit")
+                    .small()
+                    .ghost()
+                    .selected(self.zoom.is_none())
+                    .on_click(cx.listener(|this, _, _, cx| this.set_zoom(None, cx))),
+            )
+            .child(
+                Button::new("zoom-actual")
+                    .label("1:1")
+                    .small()
+                    .ghost()
+                    .selected(self.zoom == Some(1.0))
+                    .on_click(cx.listener(|this, _, _, cx| this.set_zoom(Some(1.0), cx))),
+            )
+            .child(
+                Button::new("zoom-out")
+                    .label("−")
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        let scale = this.scale();
+                        this.set_zoom(Some(scale / 1.25), cx)
+                    })),
+            )
+            .child(
+                Button::new("zoom-in")
+                    .label("+")
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        let scale = this.scale();
+                        this.set_zoom(Some(scale * 1.25), cx)
+                    })),
+            )
+            .child(
+                div()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(format!("{:.0}% · {width:.0}×{height:.0}", self.scale() * 100.0)),
+            )
+            .when(self.frame_count() > 1, |this| {
+                this.child(div().flex_1())
+                    .child(
+                        Button::new("frame-prev")
+                            .label("⏮")
+                            .small()
+                            .ghost()
+                            .on_click(cx.listener(|this, _, _, cx| this.step(false, cx))),
+                    )
+                    .child(
+                        Button::new("frame-play")
+                            .label(if self.is_playing() { "⏸" } else { "▶" })
+                            .small()
+                            .ghost()
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                if this.is_playing() {
+                                    this.pause(cx);
+                                } else {
+                                    this.play(cx);
+                                }
+                            })),
+                    )
+                    .child(
+                        Button::new("frame-next")
+                            .label("⏭")
+                            .small()
+                            .ghost()
+                            .on_click(cx.listener(|this, _, _, cx| this.step(true, cx))),
+                    )
+                    .child(
+                        div()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(format!("Frame {}/{}", self.frame + 1, self.frame_count())),
+                    )
+                    .when(self.truncated, |this| {
+                        this.child(
+                            div()
+                                .text_color(cx.theme().muted_foreground)
+                                .child("· later frames not loaded, the animation is too large"),
+                        )
+                    })
+            })
+    }
+}
+
+impl Render for ImageView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let image = self.image.clone();
+        let frame = self.frame;
+        let image_size = self.image_size();
+        let zoom = self.zoom;
+        let pan = self.pan;
+        let viewport = self.viewport.clone();
+        let (light, dark) = (cx.theme().background, cx.theme().muted);
+
+        v_flex().size_full().child(self.render_toolbar(cx)).child(
+            div()
+                .id("image-viewport")
+                .flex_1()
+                .size_full()
+                .overflow_hidden()
+                .on_scroll_wheel(cx.listener(Self::on_scroll))
+                .on_mouse_down(MouseButton::Left, cx.listener(Self::on_mouse_down))
+                .on_mouse_move(cx.listener(Self::on_mouse_move))
+                .child(
+                    canvas(
+                        move |bounds, _, _| viewport.set(bounds),
+                        move |bounds, _, window, _: &mut App| {
+                            let scale = zoom.unwrap_or_else(|| {
+                                fit_scale(image_size, (f32::from(bounds.size.width), f32::from(bounds.size.height)))
+                            });
+                            let scaled: Size<Pixels> = size(px(image_size.0 * scale), px(image_size.1 * scale));
+                            let center = bounds.center();
+                            let origin = point(
+                                center.x + px(pan.0) - scaled.width / 2.0,
+                                center.y + px(pan.1) - scaled.height / 2.0,
+                            );
+                            let image_bounds = Bounds { origin, size: scaled };
+                            window.with_content_mask(Some(gpui::ContentMask { bounds }), |window| {
+                                paint_checkerboard(image_bounds.intersect(&bounds), light, dark, window);
+                                if let Err(err) = window.paint_image(image_bounds, Corners::default(), image, frame, false) {
+                                    eprintln!("Failed to paint image: {err}");
+                                }
+                            });
+                        },
+                    )
+                    .size_full(),
+                ),
+        )
+    }
+}
+
+/// Frees the textures `image` took in the sprite atlas; gpui keeps them
+/// until told otherwise. Deferred so that the window drawing the image is
+/// reachable, rather than borrowed by the update that dropped it.
+pub fn release_image(image: Arc<RenderImage>, cx: &mut App) {
+    cx.defer(move |cx| cx.drop_image(image, None));
+}
+
+fn fit_scale(image: (f32, f32), viewport: (f32, f32)) -> f32 {
+    if image.0 <= 0.0 || image.1 <= 0.0 || viewport.0 <= 0.0 || viewport.1 <= 0.0 {
+        return 1.0;
+    }
+    (viewport.0 / image.0).min(viewport.1 / image.1).min(1.0)
+}
+
+/// Paints the checkerboard in screen space, so the squares stay the same size
+/// at any zoom.
+fn paint_checkerboard(bounds: Bounds<Pixels>, light: gpui::Hsla, dark: gpui::Hsla, window: &mut Window) {
+    window.paint_quad(fill(bounds, light));
+    let (left, top) = (f32::from(bounds.origin.x), f32::from(bounds.origin.y));
+    let (width, height) = (f32::from(bounds.size.width), f32::from(bounds.size.height));
+    let cols = (width / CHECKER_SIZE).ceil() as usize;
+    let rows = (height / CHECKER_SIZE).ceil() as usize;
+    for row in 0..rows {
+        for col in (row % 2..cols).step_by(2) {
+            let x = left + col as f32 * CHECKER_SIZE;
+            let y = top + row as f32 * CHECKER_SIZE;
+            let square = Bounds {
+                origin: point(px(x), px(y)),
+                size: size(
+                    px(CHECKER_SIZE.min(left + width - x)),
+                    px(CHECKER_SIZE.min(top + height - y)),
+                ),
+            };
+            window.paint_quad(fill(square, dark));
+        }
+    }
+}
+
+/// Decodes the frames of an image into a texture gpui can paint, up to
+/// [`MAX_DECODED_BYTES`], checking `cancelled` between frames so a long
+/// animation stops decoding as soon as the user moves on. An animation's
+/// first frame is handed to `on_first` as soon as a second one follows, to
+/// show while the rest decode. gpui expects BGRA, so the red and blue
+/// channels are swapped here rather than on every paint.
+pub fn decode_image(
+    path: &Path,
+    cancelled: &AtomicBool,
+    mut on_first: impl FnMut(Arc<RenderImage>),
+) -> Result<DecodedImage, SharedString> {
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut bytes = 0;
+    let mut truncated = false;
+    for frame in decode_frames(path).map_err(|err| SharedString::from(err.to_string()))? {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err("Cancelled".into());
+        }
+        let mut frame = frame.map_err(|err| SharedString::from(err.to_string()))?;
+        bytes += frame.buffer().len();
+        if !frames.is_empty() && bytes > MAX_DECODED_BYTES {
+            truncated = true;
+            break;
+        }
+        for pixel in frame.buffer_mut().chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+        if let [first] = frames.as_slice() {
+            on_first(Arc::new(RenderImage::new(vec![first.clone()])));
+        }
+        frames.push(frame);
+    }
+    match frames.first() {
+        Some(frame) if frame.buffer().width() > 0 && frame.buffer().height() > 0 => Ok(DecodedImage {
+            image: Arc::new(RenderImage::new(frames)),
+            truncated,
+        }),
+        _ => Err("Image is empty".into()),
+    }
+}
+
+fn decode_frames(path: &Path) -> image::ImageResult<Frames<'static>> {
+    let reader = || -> image::ImageResult<BufReader<File>> { Ok(BufReader::new(File::open(path)?)) };
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "gif" => return Ok(GifDecoder::new(reader()?)?.into_frames()),
+        "png" | "apng" => {
+            let decoder = PngDecoder::new(reader()?)?;
+            if decoder.is_apng()? {
+                return Ok(decoder.apng()?.into_frames());
+            }
+        }
+        "webp" => {
+            let decoder = WebPDecoder::new(reader()?)?;
+            if decoder.has_animation() {
+                return Ok(decoder.into_frames());
+            }
+        }
+        _ => {}
+    }
+    let image = image::open(path)?.into_rgba8();
+    Ok(Frames::new(Box::new(std::iter::once(Ok(Frame::new(image))))))
+}
+
+/// How long a frame stays up. Like browsers, near-zero delays are treated as
+/// the common 100ms default instead of spinning.
+fn frame_delay(image: &RenderImage, frame: usize) -> Duration {
+    let (numer, denom) = image.delay(frame).numer_denom_ms();
+    let ms = numer / denom.max(1);
+    Duration::from_millis(if ms <= 10 { 100 } else { ms as u64 })
+}
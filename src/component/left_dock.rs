@@ -1,30 +1,43 @@
-use gpui::{div, App, AppContext, Context, Entity, FocusHandle, IntoElement, Render, Window, ParentElement};
-use gpui_component::{IconName, Side};
+use gpui::{div, App, AppContext, Context, Entity, EventEmitter, IntoElement, Render, Styled, Window, ParentElement};
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::sidebar::{Sidebar, SidebarGroup, SidebarMenu, SidebarMenuItem};
+use gpui_component::{v_flex, IconName, Side, Sizable};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-enum Item {
+use crate::session::SidebarState;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Item {
     Library,
     Workflow,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-enum SubItem {
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum SubItem {
     Explorer,
     Categories,
     Tags,
+    Trash,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LeftDockEvent {
+    Select(Item, Option<SubItem>),
+    /// Switched to the other side of the window.
+    Moved,
 }
 
-struct LeftDock {
+pub struct LeftDock {
     active_items: HashMap<Item, bool>,
     last_active_item: Item,
     active_subitems: Option<SubItem>,
     collapsed: bool,
     side: Side,
-    focus_handle: FocusHandle,
-    checked: bool,
 }
 
+impl EventEmitter<LeftDockEvent> for LeftDock {}
+
 impl Item {
     fn label(&self) -> &'static str {
         match self {
@@ -39,6 +52,18 @@ impl Item {
             Item::Workflow => IconName::SquareTerminal,
         }
     }
+
+    fn items(&self) -> Vec<SubItem> {
+        match self {
+            Item::Library => vec![
+                SubItem::Explorer,
+                SubItem::Categories,
+                SubItem::Tags,
+                SubItem::Trash,
+            ],
+            Item::Workflow => vec![],
+        }
+    }
 }
 
 impl SubItem {
@@ -47,6 +72,7 @@ impl SubItem {
             SubItem::Explorer => "Explorer",
             SubItem::Categories => "Categories",
             SubItem::Tags => "Tags",
+            SubItem::Trash => "Trash",
         }
     }
 
@@ -55,37 +81,113 @@ impl SubItem {
             SubItem::Explorer => IconName::Star,
             SubItem::Categories => IconName::Asterisk,
             SubItem::Tags => IconName::Bot,
+            SubItem::Trash => IconName::Delete,
         }
     }
 }
 
 impl LeftDock {
-    fn view(window: &mut Window, cx: &mut App) -> Entity<Self> {
-        cx.new(|cx| Self::new(window, cx))
+    pub fn view(state: SidebarState, window: &mut Window, cx: &mut App) -> Entity<Self> {
+        cx.new(|cx| Self::new(state, window, cx))
     }
 
-    fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+    fn new(state: SidebarState, _: &mut Window, _: &mut Context<Self>) -> Self {
         let mut active_items = HashMap::new();
-        active_items.insert(Item::Library, true);
+        active_items.insert(state.item, true);
 
         Self {
             active_items,
-            last_active_item: Item::Library,
-            active_subitems: None,
-            collapsed: false,
-            side: Side::Left,
-            focus_handle: cx.focus_handle(),
-            checked: false,
+            last_active_item: state.item,
+            active_subitems: state.subitem,
+            collapsed: state.collapsed,
+            side: if state.right { Side::Right } else { Side::Left },
+        }
+    }
+
+    pub fn state(&self) -> SidebarState {
+        SidebarState {
+            item: self.last_active_item,
+            subitem: self.active_subitems,
+            collapsed: self.collapsed,
+            right: self.side == Side::Right,
         }
     }
 
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn toggle_collapsed(&mut self, cx: &mut Context<Self>) {
+        self.collapsed = !self.collapsed;
+        cx.notify();
+    }
+
+    fn toggle_side(&mut self, cx: &mut Context<Self>) {
+        self.side = match self.side {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        };
+        cx.emit(LeftDockEvent::Moved);
+        cx.notify();
+    }
+
+    pub fn select(&mut self, item: Item, subitem: Option<SubItem>, cx: &mut Context<Self>) {
+        self.last_active_item = item;
+        self.active_subitems = subitem;
+        self.active_items.insert(item, true);
+        cx.emit(LeftDockEvent::Select(item, subitem));
+        cx.notify();
+    }
+
     fn render_content(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        div()
+        let menu = SidebarMenu::new().children([Item::Library, Item::Workflow].map(|item| {
+            let is_active = self.last_active_item == item && self.active_subitems.is_none();
+            SidebarMenuItem::new(item.label())
+                .icon(item.icons())
+                .active(is_active)
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    let subitem = item.items().first().copied();
+                    this.select(item, subitem, cx);
+                }))
+                .children(item.items().into_iter().map(|subitem| {
+                    SidebarMenuItem::new(subitem.label())
+                        .icon(subitem.icons())
+                        .active(self.active_subitems == Some(subitem))
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            this.select(item, Some(subitem), cx);
+                        }))
+                }))
+        }));
+
+        // Arrows point the way the sidebar will go.
+        let towards_edge = if self.side == Side::Left { "«" } else { "»" };
+        let away_from_edge = if self.side == Side::Left { "»" } else { "«" };
+        let footer = v_flex()
+            .w_full()
+            .child(
+                Button::new("toggle-sidebar")
+                    .label(if self.collapsed { away_from_edge } else { towards_edge })
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, _, cx| this.toggle_collapsed(cx))),
+            )
+            .children((!self.collapsed).then(|| {
+                Button::new("move-sidebar")
+                    .label(if self.side == Side::Left { "Move Right" } else { "Move Left" })
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, _, cx| this.toggle_side(cx)))
+            }));
+
+        Sidebar::new(self.side)
+            .collapsed(self.collapsed)
+            .child(SidebarGroup::new("Toshokan").child(menu))
+            .footer(footer)
     }
 }
 
 impl Render for LeftDock {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        div().child(self.render_content(window, cx))
+        div().h_full().child(self.render_content(window, cx))
     }
 }
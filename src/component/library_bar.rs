@@ -0,0 +1,134 @@
+use gpui::{
+    Context, Entity, EventEmitter, InteractiveElement, IntoElement, ParentElement, PathPromptOptions, Render,
+    SharedString, StatefulInteractiveElement, Styled, Subscription, Window, div, prelude::FluentBuilder, px,
+};
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::{h_flex, ActiveTheme, Sizable};
+use std::path::PathBuf;
+
+use crate::library::Library;
+
+#[derive(Clone)]
+pub enum LibraryBarEvent {
+    /// Browse this library folder.
+    Open(PathBuf),
+    /// Add the folder shown in the focused pane.
+    AddCurrentFolder,
+    Status(SharedString),
+}
+
+/// The library folders, shown above the explorer.
+pub struct LibraryBar {
+    library: Entity<Library>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl EventEmitter<LibraryBarEvent> for LibraryBar {}
+
+impl LibraryBar {
+    pub fn new(library: Entity<Library>, _: &mut Window, cx: &mut Context<Self>) -> Self {
+        let _subscriptions = vec![cx.observe(&library, |_, _, cx| cx.notify())];
+        Self { library, _subscriptions }
+    }
+
+    fn add_folders(&mut self, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: true,
+            prompt: Some("Add to Library".into()),
+        });
+        cx.spawn(async move |this, cx| {
+            let paths = match paths.await {
+                Ok(Ok(Some(paths))) => paths,
+                Ok(Ok(None)) | Err(_) => return,
+                Ok(Err(err)) => {
+                    eprintln!("Folder prompt failed: {err}");
+                    return;
+                }
+            };
+            if let Err(err) = this.update(cx, |this, cx| {
+                for path in paths {
+                    if let Err(err) = this.library.update(cx, |library, cx| library.add_root(&path, cx)) {
+                        cx.emit(LibraryBarEvent::Status(err));
+                    }
+                }
+            }) {
+                eprintln!("Add library folders failed: {err}");
+            }
+        })
+        .detach();
+    }
+}
+
+impl Render for LibraryBar {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let roots = self.library.read(cx).roots().iter().enumerate().map(|(ix, root)| {
+            let path = root.path().to_path_buf();
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string());
+            let count = match root.index() {
+                Some(index) => format!("{} files", index.files().len()),
+                None => "indexing…".to_string(),
+            };
+            h_flex()
+                .id(("library-root", ix))
+                .gap_1()
+                .pl_2()
+                .max_w(px(260.0))
+                .border_1()
+                .border_color(cx.theme().border)
+                .rounded(cx.theme().radius)
+                .cursor_pointer()
+                .hover(|style| style.bg(cx.theme().muted))
+                .on_click(cx.listener({
+                    let path = path.clone();
+                    move |_, _, _, cx| cx.emit(LibraryBarEvent::Open(path.clone()))
+                }))
+                .child(div().truncate().child(format!("📚 {name}")))
+                .child(div().text_xs().text_color(cx.theme().muted_foreground).child(count))
+                .when_some(root.error(), |this, err| {
+                    this.child(div().text_xs().text_color(cx.theme().danger).child(err.clone()))
+                })
+                .child(
+                    Button::new(("remove-library-root", ix))
+                        .label("×")
+                        .xsmall()
+                        .ghost()
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            cx.stop_propagation();
+                            this.library.update(cx, |library, cx| library.remove_root(&path, cx));
+                        })),
+                )
+        });
+
+        h_flex()
+            .id("library-bar")
+            .w_full()
+            .gap_2()
+            .px_2()
+            .py_1()
+            .overflow_x_scroll()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .child(div().text_sm().text_color(cx.theme().muted_foreground).child("Library"))
+            .children(roots)
+            .child(div().flex_1())
+            .child(
+                Button::new("add-library-folder")
+                    .label("Add Folder…")
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, _, cx| this.add_folders(cx))),
+            )
+            .child(
+                Button::new("add-current-folder")
+                    .label("Add Current Folder")
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|_, _, _, cx| cx.emit(LibraryBarEvent::AddCurrentFolder))),
+            )
+    }
+}
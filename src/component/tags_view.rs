@@ -0,0 +1,321 @@
+use gpui::{
+    App, AppContext, Context, Entity, EventEmitter, InteractiveElement, IntoElement, ParentElement, Render,
+    StatefulInteractiveElement, Styled, Subscription, Window, div, prelude::FluentBuilder, px, rgb,
+};
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::input::{Input, InputEvent, InputState};
+use gpui_component::{h_flex, v_flex, ActiveTheme, Selectable, Sizable};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::storage::{self, BaseDir, WriteOrder};
+use crate::tags::{self, Match, TagDb};
+
+#[derive(Clone)]
+pub enum TagsViewEvent {
+    /// Show this file or folder in the explorer.
+    Open(PathBuf),
+    /// Show only these files in the explorer's tree, or everything again.
+    Filter(Option<HashSet<PathBuf>>),
+}
+
+/// Every tag with how often it is used, and the files carrying the
+/// selected ones.
+pub struct TagsView {
+    tags: Entity<TagDb>,
+    selected: BTreeSet<String>,
+    mode: Match,
+    new_tag: Entity<InputState>,
+    /// Files matching the selection that still exist, sorted by path.
+    matches: Vec<PathBuf>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl EventEmitter<TagsViewEvent> for TagsView {}
+
+/// Applies `f` to the shared tags, then saves them and tells every view
+/// showing them.
+pub(crate) fn update_tags(tags: &Entity<TagDb>, cx: &mut App, f: impl FnOnce(&mut TagDb)) {
+    tags.update(cx, |db, cx| {
+        f(db);
+        save_tags(db, cx);
+        cx.notify();
+    });
+}
+
+/// Writes a copy of `db` in the background.
+pub(crate) fn save_tags(db: &TagDb, cx: &App) {
+    static ORDER: WriteOrder = WriteOrder::new();
+
+    let Some(dir) = storage::dir(BaseDir::Data) else {
+        return;
+    };
+    let db = db.clone();
+    let ticket = ORDER.ticket();
+    cx.background_spawn(async move {
+        if let Err(err) = ORDER.write(ticket, || db.save(&dir)) {
+            eprintln!("Failed to save tags to {}: {err}", dir.display());
+        }
+    })
+    .detach();
+}
+
+/// A tag's name on its own color, with a button to take it off.
+pub(crate) fn tag_chip(
+    ix: usize,
+    tag: &tags::Tag,
+    on_remove: impl Fn(&mut Window, &mut App) + 'static,
+) -> impl IntoElement {
+    h_flex()
+        .gap_1()
+        .pl_2()
+        .rounded(px(10.0))
+        .bg(rgb(tag.color))
+        .text_color(gpui::white())
+        .text_xs()
+        .child(tag.name.clone())
+        .child(
+            Button::new(("remove-tag", ix))
+                .label("×")
+                .xsmall()
+                .ghost()
+                .on_click(move |_, window, cx| on_remove(window, cx)),
+        )
+}
+
+impl TagsView {
+    pub fn new(tags: Entity<TagDb>, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let new_tag = cx.new(|cx| InputState::new(window, cx).placeholder("New tag"));
+        let _subscriptions = vec![
+            cx.observe(&tags, |this, _, cx| this.refresh(cx)),
+            cx.subscribe_in(&new_tag, window, |this, input, event: &InputEvent, window, cx| {
+                if let InputEvent::PressEnter { .. } = event {
+                    let name = input.read(cx).value().to_string();
+                    update_tags(&this.tags, cx, |db| {
+                        db.create_tag(&name);
+                    });
+                    input.update(cx, |input, cx| input.set_value("", window, cx));
+                }
+            }),
+        ];
+        Self {
+            tags,
+            selected: BTreeSet::new(),
+            mode: Match::All,
+            new_tag,
+            matches: Vec::new(),
+            _subscriptions,
+        }
+    }
+
+    fn refresh(&mut self, cx: &mut Context<Self>) {
+        let db = self.tags.read(cx);
+        self.selected.retain(|name| db.tag(name).is_some());
+        self.matches = db.matching(&self.selected, self.mode);
+        self.matches.retain(|path| path.exists());
+        let filter = (!self.selected.is_empty()).then(|| self.matches.iter().cloned().collect());
+        cx.emit(TagsViewEvent::Filter(filter));
+        cx.notify();
+    }
+
+    /// Deselects every tag, which shows the whole tree again.
+    pub fn clear_selection(&mut self, cx: &mut Context<Self>) {
+        self.selected.clear();
+        self.refresh(cx);
+    }
+
+    fn toggle_tag(&mut self, name: &str, cx: &mut Context<Self>) {
+        if !self.selected.remove(name) {
+            self.selected.insert(name.to_string());
+        }
+        self.refresh(cx);
+    }
+
+    fn set_mode(&mut self, mode: Match, cx: &mut Context<Self>) {
+        self.mode = mode;
+        self.refresh(cx);
+    }
+
+    fn render_matches(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let mut rows = Vec::new();
+        let mut folder: Option<&Path> = None;
+        for (ix, path) in self.matches.iter().enumerate() {
+            let parent = path.parent();
+            if parent != folder {
+                folder = parent;
+                rows.push(
+                    div()
+                        .px_3()
+                        .pt_3()
+                        .pb_1()
+                        .text_xs()
+                        .text_color(cx.theme().muted_foreground)
+                        .child(parent.map(|dir| dir.display().to_string()).unwrap_or_default())
+                        .into_any_element(),
+                );
+            }
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string());
+            let chips = self.tags.read(cx).tags_for(path).into_iter().map(|tag| {
+                div()
+                    .px_2()
+                    .rounded(px(10.0))
+                    .bg(rgb(tag.color))
+                    .text_color(gpui::white())
+                    .text_xs()
+                    .child(tag.name.clone())
+            });
+            rows.push(
+                h_flex()
+                    .id(("tagged-file", ix))
+                    .gap_2()
+                    .px_3()
+                    .py_1()
+                    .cursor_pointer()
+                    .hover(|style| style.bg(cx.theme().muted))
+                    .on_click(cx.listener({
+                        let path = path.clone();
+                        move |_, _, _, cx| cx.emit(TagsViewEvent::Open(path.clone()))
+                    }))
+                    .child(div().flex_1().truncate().child(if path.is_dir() { format!("📁 {name}") } else { name }))
+                    .children(chips)
+                    .into_any_element(),
+            );
+        }
+
+        div()
+            .id("tagged-files")
+            .flex_1()
+            .h_full()
+            .overflow_y_scroll()
+            .children(rows)
+            .when(self.matches.is_empty(), |this| {
+                this.p_4().text_color(cx.theme().muted_foreground).child(if self.selected.is_empty() {
+                    "Select tags to see the files carrying them"
+                } else {
+                    "No files match"
+                })
+            })
+    }
+}
+
+impl Render for TagsView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let db = self.tags.read(cx);
+        let rows = db
+            .tags()
+            .iter()
+            .enumerate()
+            .map(|(ix, tag)| {
+                let selected = self.selected.contains(&tag.name);
+                let name = tag.name.clone();
+                h_flex()
+                    .id(("tag", ix))
+                    .gap_2()
+                    .px_3()
+                    .py_1()
+                    .cursor_pointer()
+                    .when(selected, |this| this.bg(cx.theme().accent))
+                    .on_click(cx.listener({
+                        let name = name.clone();
+                        move |this, _, _, cx| this.toggle_tag(&name, cx)
+                    }))
+                    .child(
+                        div()
+                            .id(("tag-color", ix))
+                            .size_3()
+                            .rounded_full()
+                            .bg(rgb(tag.color))
+                            .on_click(cx.listener({
+                                let name = name.clone();
+                                move |this, _, _, cx| {
+                                    // Only recolor; don't select the row as well.
+                                    cx.stop_propagation();
+                                    update_tags(&this.tags, cx, |db| db.cycle_color(&name));
+                                }
+                            })),
+                    )
+                    .child(div().flex_1().truncate().child(name.clone()))
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(db.count(&tag.name).to_string()),
+                    )
+                    .child(
+                        Button::new(("delete-tag", ix))
+                            .label("×")
+                            .xsmall()
+                            .ghost()
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                cx.stop_propagation();
+                                update_tags(&this.tags, cx, |db| db.delete_tag(&name));
+                            })),
+                    )
+            })
+            .collect::<Vec<_>>();
+        let empty = rows.is_empty();
+
+        h_flex()
+            .size_full()
+            .child(
+                v_flex()
+                    .w(px(240.0))
+                    .h_full()
+                    .flex_shrink_0()
+                    .border_r_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        h_flex()
+                            .justify_between()
+                            .px_3()
+                            .py_2()
+                            .border_b_1()
+                            .border_color(cx.theme().border)
+                            .child("Tags")
+                            .child(
+                                h_flex()
+                                    .child(
+                                        Button::new("match-all")
+                                            .label("All")
+                                            .xsmall()
+                                            .ghost()
+                                            .selected(self.mode == Match::All)
+                                            .on_click(cx.listener(|this, _, _, cx| this.set_mode(Match::All, cx))),
+                                    )
+                                    .child(
+                                        Button::new("match-any")
+                                            .label("Any")
+                                            .xsmall()
+                                            .ghost()
+                                            .selected(self.mode == Match::Any)
+                                            .on_click(cx.listener(|this, _, _, cx| this.set_mode(Match::Any, cx))),
+                                    ),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .id("tags")
+                            .flex_1()
+                            .overflow_y_scroll()
+                            .children(rows)
+                            .when(empty, |this| {
+                                this.p_4()
+                                    .text_sm()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child("No tags yet. Add one below, or from a file's header.")
+                            }),
+                    )
+                    .child(
+                        div()
+                            .p_2()
+                            .border_t_1()
+                            .border_color(cx.theme().border)
+                            .child(Input::new(&self.new_tag).small()),
+                    ),
+            )
+            .child(self.render_matches(cx))
+    }
+}
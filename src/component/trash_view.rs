@@ -0,0 +1,255 @@
+use gpui::{
+    AppContext, Context, EventEmitter, InteractiveElement, IntoElement, ParentElement, Render,
+    SharedString, StatefulInteractiveElement, Styled, Window, div, prelude::FluentBuilder,
+};
+use gpui_component::button::{Button, ButtonVariant, ButtonVariants};
+use gpui_component::dialog::DialogButtonProps;
+use gpui_component::{h_flex, v_flex, ActiveTheme, Disableable, Sizable, WindowExt};
+use std::path::PathBuf;
+
+use crate::file_ops::Progress;
+use crate::trash::{Trash, TrashEntry};
+
+#[derive(Clone)]
+pub enum TrashViewEvent {
+    Restored(PathBuf),
+}
+
+pub struct TrashView {
+    trash: Option<Trash>,
+    entries: Vec<TrashEntry>,
+    /// Info files that couldn't be read, and why.
+    list_errors: Vec<SharedString>,
+    error: Option<SharedString>,
+}
+
+impl EventEmitter<TrashViewEvent> for TrashView {}
+
+impl TrashView {
+    pub fn new(_: &mut Window, cx: &mut Context<Self>) -> Self {
+        let (trash, error) = match Trash::home() {
+            Ok(trash) => (Some(trash), None),
+            Err(err) => (None, Some(format!("Trash unavailable: {err}").into())),
+        };
+        let mut this = Self {
+            trash,
+            entries: Vec::new(),
+            list_errors: Vec::new(),
+            error,
+        };
+        this.refresh(cx);
+        this
+    }
+
+    pub fn refresh(&mut self, cx: &mut Context<Self>) {
+        let Some(trash) = self.trash.clone() else {
+            return;
+        };
+        let task = cx.background_spawn(async move { trash.list() });
+        cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                match result {
+                    Ok((entries, errors)) => {
+                        this.entries = entries;
+                        this.list_errors = errors.into_iter().map(SharedString::from).collect();
+                    }
+                    Err(err) => this.error = Some(format!("Failed to read trash: {err}").into()),
+                }
+                cx.notify();
+            }) {
+                eprintln!("Update trash entries failed: {err}");
+            }
+        })
+        .detach();
+    }
+
+    fn restore(&mut self, entry: TrashEntry, cx: &mut Context<Self>) {
+        let task = cx.background_spawn(async move {
+            entry
+                .restore(&Progress::default())
+                .map(|_| entry.original_path)
+        });
+        cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                match result {
+                    Ok(path) => {
+                        this.error = None;
+                        cx.emit(TrashViewEvent::Restored(path));
+                    }
+                    Err(err) => this.error = Some(format!("Restore failed: {err}").into()),
+                }
+                this.refresh(cx);
+            }) {
+                eprintln!("Restore from trash failed: {err}");
+            }
+        })
+        .detach();
+    }
+
+    /// Asks before purging: the home trash is shared, so emptying it also
+    /// deletes what other applications put there.
+    fn confirm_purge(&mut self, entry: Option<TrashEntry>, window: &mut Window, cx: &mut Context<Self>) {
+        let (title, message) = match &entry {
+            Some(entry) => (
+                "Delete Permanently",
+                format!("Permanently delete \"{}\"? It can't be restored afterwards.", entry.name),
+            ),
+            None => (
+                "Empty Trash",
+                format!(
+                    "Permanently delete all {} items in the trash, including those other applications put there? \
+                     They can't be restored afterwards.",
+                    self.entries.len()
+                ),
+            ),
+        };
+        let view = cx.entity().downgrade();
+        window.open_dialog(cx, move |dialog, _, _| {
+            let view = view.clone();
+            let entry = entry.clone();
+            dialog
+                .confirm()
+                .title(title)
+                .child(message.clone())
+                .button_props(
+                    DialogButtonProps::default()
+                        .ok_text("Delete")
+                        .ok_variant(ButtonVariant::Danger),
+                )
+                .on_ok(move |_, _, cx| {
+                    if let Err(err) = view.update(cx, |view, cx| view.purge(entry.clone(), cx)) {
+                        eprintln!("Purge trash failed: {err}");
+                    }
+                    true
+                })
+        });
+    }
+
+    fn purge(&mut self, entry: Option<TrashEntry>, cx: &mut Context<Self>) {
+        let Some(trash) = self.trash.clone() else {
+            return;
+        };
+        let task = cx.background_spawn(async move {
+            match entry {
+                Some(entry) => entry.purge(),
+                None => trash.empty(),
+            }
+        });
+        cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.error = result
+                    .err()
+                    .map(|err| format!("Delete failed: {err}").into());
+                this.refresh(cx);
+            }) {
+                eprintln!("Purge trash failed: {err}");
+            }
+        })
+        .detach();
+    }
+}
+
+impl Render for TrashView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let rows = self.entries.iter().enumerate().map(|(ix, entry)| {
+            h_flex()
+                .gap_2()
+                .px_3()
+                .py_2()
+                .border_b_1()
+                .border_color(cx.theme().border)
+                .child(
+                    v_flex()
+                        .flex_1()
+                        .overflow_hidden()
+                        .child(entry.name.clone())
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .truncate()
+                                .child(entry.original_path.display().to_string()),
+                        )
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(entry.deletion_date.clone()),
+                        ),
+                )
+                .child(
+                    Button::new(("restore", ix))
+                        .label("Restore")
+                        .small()
+                        .ghost()
+                        .on_click(cx.listener({
+                            let entry = entry.clone();
+                            move |this, _, _, cx| this.restore(entry.clone(), cx)
+                        })),
+                )
+                .child(
+                    Button::new(("purge", ix))
+                        .label("Delete")
+                        .small()
+                        .danger()
+                        .on_click(cx.listener({
+                            let entry = entry.clone();
+                            move |this, _, window, cx| this.confirm_purge(Some(entry.clone()), window, cx)
+                        })),
+                )
+        });
+
+        v_flex()
+            .size_full()
+            .child(
+                h_flex()
+                    .justify_between()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(format!("Trash ({})", self.entries.len()))
+                    .child(
+                        Button::new("empty-trash")
+                            .label("Empty Trash")
+                            .small()
+                            .disabled(self.entries.is_empty())
+                            .on_click(cx.listener(|this, _, window, cx| this.confirm_purge(None, window, cx))),
+                    ),
+            )
+            .children(self.error.clone().map(|err| {
+                div()
+                    .px_3()
+                    .py_1()
+                    .text_sm()
+                    .text_color(cx.theme().danger)
+                    .child(err)
+            }))
+            .when(!self.list_errors.is_empty(), |this| {
+                this.child(
+                    v_flex()
+                        .px_3()
+                        .py_1()
+                        .text_xs()
+                        .text_color(cx.theme().warning)
+                        .child(format!("{} items in the trash couldn't be read:", self.list_errors.len()))
+                        .children(self.list_errors.iter().map(|err| div().truncate().child(err.clone()))),
+                )
+            })
+            .child(
+                div()
+                    .id("trash-entries")
+                    .flex_1()
+                    .overflow_y_scroll()
+                    .children(rows)
+                    .when(self.entries.is_empty(), |this| {
+                        this.p_4()
+                            .text_color(cx.theme().muted_foreground)
+                            .child("Trash is empty")
+                    }),
+            )
+    }
+}
@@ -0,0 +1,343 @@
+use gpui::{
+    App, AppContext, Context, EventEmitter, InteractiveElement, IntoElement, ParentElement, Render,
+    SharedString, StatefulInteractiveElement, Styled, Task, Window, div, prelude::FluentBuilder,
+};
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::{h_flex, v_flex, ActiveTheme, Disableable, Sizable};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::component::content_viewer::MONO_FONT;
+use crate::typography::{self, DiffLine, Issue, MAX_FILE_LEN};
+
+#[derive(Clone)]
+pub enum TypographyPanelEvent {
+    /// A 0-based line the user wants to see in the text view.
+    GoToLine(usize),
+    Saved,
+}
+
+/// A fix that has been worked out but not written yet.
+struct Pending {
+    summary: SharedString,
+    content: String,
+    diff: Vec<DiffLine>,
+}
+
+/// Lints the open text file with autocorrect and writes fixes back after
+/// showing their diff.
+pub struct TypographyPanel {
+    file_name: SharedString,
+    path: PathBuf,
+    /// The file as last read. Fixes are applied to it, and it must still be
+    /// what is on disk when they are saved.
+    text: Option<String>,
+    issues: Vec<Issue>,
+    pending: Option<Pending>,
+    busy: bool,
+    error: Option<SharedString>,
+    _task: Option<Task<()>>,
+}
+
+impl EventEmitter<TypographyPanelEvent> for TypographyPanel {}
+
+impl TypographyPanel {
+    pub fn new(file_name: SharedString, path: PathBuf, cx: &mut Context<Self>) -> Self {
+        let mut this = Self {
+            file_name,
+            path,
+            text: None,
+            issues: Vec::new(),
+            pending: None,
+            busy: false,
+            error: None,
+            _task: None,
+        };
+        this.check(cx);
+        this
+    }
+
+    pub fn check(&mut self, cx: &mut Context<Self>) {
+        let path = self.path.clone();
+        let file_name = self.file_name.clone();
+        let task = cx.background_spawn(async move {
+            let text = read_text(&path)?;
+            let issues = typography::lint(&text, &file_name)?;
+            Ok::<_, String>((text, issues))
+        });
+        self.busy = true;
+        self.pending = None;
+        self._task = Some(cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.busy = false;
+                match result {
+                    Ok((text, issues)) => {
+                        this.text = Some(text);
+                        this.issues = issues;
+                        this.error = None;
+                    }
+                    Err(err) => {
+                        this.text = None;
+                        this.issues.clear();
+                        this.error = Some(err.into());
+                    }
+                }
+                cx.notify();
+            }) {
+                eprintln!("Update typography issues failed: {err}");
+            }
+        }));
+        cx.notify();
+    }
+
+    /// Whether autocorrect found anything on this 0-based line.
+    pub fn has_issue_on(&self, line: usize) -> bool {
+        let ix = self.issues.partition_point(|issue| issue.line.saturating_sub(1) < line);
+        self.issues.get(ix).is_some_and(|issue| issue.line.saturating_sub(1) == line)
+    }
+
+    /// Works out the fixed text for one issue, or all of them, and shows it
+    /// as a diff to be saved.
+    fn preview(&mut self, issue: Option<Issue>, cx: &mut Context<Self>) {
+        let Some(text) = self.text.clone() else {
+            return;
+        };
+        let file_name = self.file_name.clone();
+        let summary: SharedString = match &issue {
+            Some(issue) => format!("Fix line {}", issue.line).into(),
+            None => format!("Fix all {} issues", self.issues.len()).into(),
+        };
+        let task = cx.background_spawn(async move {
+            let content = match issue {
+                Some(issue) => typography::fix_one(&text, &issue)
+                    .ok_or_else(|| "The issue is no longer in the text, check again".to_string())?,
+                None => typography::fix_all(&text, &file_name)?,
+            };
+            let diff = typography::diff(&text, &content);
+            Ok::<_, String>((content, diff))
+        });
+        self.busy = true;
+        self._task = Some(cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.busy = false;
+                match result {
+                    Ok((content, diff)) => {
+                        this.pending = Some(Pending { summary, content, diff });
+                        this.error = None;
+                    }
+                    Err(err) => this.error = Some(err.into()),
+                }
+                cx.notify();
+            }) {
+                eprintln!("Update typography preview failed: {err}");
+            }
+        }));
+        cx.notify();
+    }
+
+    fn save(&mut self, cx: &mut Context<Self>) {
+        let (Some(pending), Some(text)) = (self.pending.as_ref(), self.text.clone()) else {
+            return;
+        };
+        let content = pending.content.clone();
+        let path = self.path.clone();
+        let file_name = self.file_name.clone();
+        let task = cx.background_spawn(async move {
+            typography::write_fixed(&path, &text, &content)?;
+            let issues = typography::lint(&content, &file_name)?;
+            Ok::<_, String>((content, issues))
+        });
+        self.busy = true;
+        self._task = Some(cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.busy = false;
+                match result {
+                    Ok((content, issues)) => {
+                        this.text = Some(content);
+                        this.issues = issues;
+                        this.pending = None;
+                        this.error = None;
+                        cx.emit(TypographyPanelEvent::Saved);
+                    }
+                    Err(err) => this.error = Some(err.into()),
+                }
+                cx.notify();
+            }) {
+                eprintln!("Save typography fix failed: {err}");
+            }
+        }));
+        cx.notify();
+    }
+
+    fn render_issues(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let rows = self.issues.iter().enumerate().map(|(ix, issue)| {
+            let line = issue.line.saturating_sub(1);
+            h_flex()
+                .id(("typography-issue", ix))
+                .gap_2()
+                .px_3()
+                .py_2()
+                .border_b_1()
+                .border_color(cx.theme().border)
+                .cursor_pointer()
+                .on_click(cx.listener(move |_, _, _, cx| cx.emit(TypographyPanelEvent::GoToLine(line))))
+                .child(
+                    v_flex()
+                        .flex_1()
+                        .overflow_hidden()
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(format!("Ln {}, Col {}", issue.line, issue.col)),
+                        )
+                        .child(
+                            div()
+                                .truncate()
+                                .line_through()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(issue.old.clone()),
+                        )
+                        .child(div().truncate().child(issue.new.clone())),
+                )
+                .child(
+                    Button::new(("typography-fix", ix))
+                        .label("Fix")
+                        .small()
+                        .ghost()
+                        .disabled(self.busy)
+                        .on_click(cx.listener({
+                            let issue = issue.clone();
+                            move |this, _, _, cx| this.preview(Some(issue.clone()), cx)
+                        })),
+                )
+        });
+
+        div()
+            .id("typography-issues")
+            .flex_1()
+            .overflow_y_scroll()
+            .children(rows)
+            .when(self.issues.is_empty() && self.text.is_some(), |this| {
+                this.p_4()
+                    .text_color(cx.theme().muted_foreground)
+                    .child("No typography issues")
+            })
+    }
+
+    fn render_diff(&self, pending: &Pending, cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex()
+            .flex_1()
+            .overflow_hidden()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(div().flex_1().child(pending.summary.clone()))
+                    .child(
+                        Button::new("typography-save")
+                            .label("Save")
+                            .small()
+                            .disabled(self.busy)
+                            .on_click(cx.listener(|this, _, _, cx| this.save(cx))),
+                    )
+                    .child(
+                        Button::new("typography-cancel")
+                            .label("Cancel")
+                            .small()
+                            .ghost()
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.pending = None;
+                                cx.notify();
+                            })),
+                    ),
+            )
+            .child(render_diff_lines("typography-diff", &pending.diff, cx))
+    }
+}
+
+impl Render for TypographyPanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex()
+            .size_full()
+            .child(
+                h_flex()
+                    .gap_2()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(div().flex_1().child(format!("Typography ({})", self.issues.len())))
+                    .child(
+                        Button::new("typography-check")
+                            .label("Check Again")
+                            .small()
+                            .ghost()
+                            .disabled(self.busy)
+                            .on_click(cx.listener(|this, _, _, cx| this.check(cx))),
+                    )
+                    .child(
+                        Button::new("typography-fix-all")
+                            .label("Fix All")
+                            .small()
+                            .disabled(self.busy || self.issues.is_empty())
+                            .on_click(cx.listener(|this, _, _, cx| this.preview(None, cx))),
+                    ),
+            )
+            .children(self.error.clone().map(|err| {
+                div()
+                    .px_3()
+                    .py_1()
+                    .text_sm()
+                    .text_color(cx.theme().danger)
+                    .child(err)
+            }))
+            .map(|this| match &self.pending {
+                Some(pending) => this.child(self.render_diff(pending, cx)),
+                None => this.child(self.render_issues(cx)),
+            })
+    }
+}
+
+/// A scrollable, colored diff as shown before fixes are written.
+pub(crate) fn render_diff_lines(id: &'static str, diff: &[DiffLine], cx: &App) -> impl IntoElement {
+    let lines = diff.iter().map(|line| {
+        let row = div().px_3().whitespace_nowrap();
+        match line {
+            DiffLine::Context(text) => row.child(format!("  {text}")),
+            DiffLine::Removed(text) => row
+                .bg(cx.theme().red.opacity(0.15))
+                .child(format!("- {text}")),
+            DiffLine::Added(text) => row
+                .bg(cx.theme().green.opacity(0.15))
+                .child(format!("+ {text}")),
+            DiffLine::Skipped(count) => row
+                .text_color(cx.theme().muted_foreground)
+                .child(format!("⋯ {count} unchanged lines")),
+        }
+    });
+    div()
+        .id(id)
+        .flex_1()
+        .overflow_scroll()
+        .py_2()
+        .font_family(MONO_FONT)
+        .text_sm()
+        .children(lines)
+}
+
+/// Reads the file for autocorrect, which only handles UTF-8.
+fn read_text(path: &Path) -> Result<String, String> {
+    let len = fs::metadata(path).map_err(|err| format!("Failed to read file: {err}"))?.len();
+    if len > MAX_FILE_LEN {
+        return Err("The file is too large to check".to_string());
+    }
+    let bytes = fs::read(path).map_err(|err| format!("Failed to read file: {err}"))?;
+    String::from_utf8(bytes).map_err(|_| "Typography only checks UTF-8 text".to_string())
+}
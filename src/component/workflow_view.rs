@@ -0,0 +1,321 @@
+use futures::StreamExt;
+use gpui::{
+    Context, EventEmitter, Hsla, InteractiveElement, IntoElement, ParentElement, Render, SharedString,
+    StatefulInteractiveElement, Styled, Task, Window, div, prelude::FluentBuilder, px,
+};
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::{h_flex, v_flex, ActiveTheme, Disableable, Sizable};
+use std::path::PathBuf;
+use std::process::Child;
+use std::time::Duration;
+
+use crate::component::content_viewer::MONO_FONT;
+use crate::config::Config;
+use crate::workflow::{self, CommandDef, Line};
+
+/// Older lines are dropped past this, so a chatty command can't eat memory.
+const MAX_LINES: usize = 10_000;
+/// How often to check whether a command that closed its output has exited.
+const EXIT_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Clone)]
+pub enum WorkflowViewEvent {
+    /// Run the command at this index on the explorer's selection.
+    Run(usize),
+}
+
+enum Status {
+    Running,
+    Exited(Option<i32>),
+    Cancelled,
+    Failed(SharedString),
+}
+
+struct Run {
+    id: usize,
+    title: SharedString,
+    lines: Vec<Line>,
+    status: Status,
+    /// Until the process has exited and its output has closed.
+    child: Option<Child>,
+    cancelled: bool,
+    _task: Option<Task<()>>,
+}
+
+/// The commands from the config, and the output of each run of them.
+pub struct WorkflowView {
+    commands: Vec<CommandDef>,
+    /// Why config.json was ignored, if it was.
+    config_error: Option<SharedString>,
+    /// Newest first.
+    runs: Vec<Run>,
+    next_id: usize,
+}
+
+impl EventEmitter<WorkflowViewEvent> for WorkflowView {}
+
+impl WorkflowView {
+    pub fn new(config: &Config, config_error: Option<SharedString>, _: &mut Window, _: &mut Context<Self>) -> Self {
+        Self {
+            commands: config.commands.clone(),
+            config_error,
+            runs: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Starts the command at `ix` on `target`, streaming its output into a
+    /// new run.
+    pub fn run(&mut self, ix: usize, target: PathBuf, cx: &mut Context<Self>) {
+        let Some(def) = self.commands.get(ix) else {
+            return;
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut run = Run {
+            id,
+            title: format!("{} · {}", def.name, target.display()).into(),
+            lines: Vec::new(),
+            status: Status::Running,
+            child: None,
+            cancelled: false,
+            _task: None,
+        };
+        match workflow::spawn(def, &target) {
+            Ok((child, output)) => {
+                run.child = Some(child);
+                run._task = Some(cx.spawn(async move |this, cx| {
+                    let mut output = output.ready_chunks(256);
+                    while let Some(lines) = output.next().await {
+                        if let Err(err) = this.update(cx, |this, cx| {
+                            if let Some(run) = this.run_mut(id) {
+                                run.lines.extend(lines);
+                                let excess = run.lines.len().saturating_sub(MAX_LINES);
+                                run.lines.drain(..excess);
+                                cx.notify();
+                            }
+                        }) {
+                            eprintln!("Update command output failed: {err}");
+                            return;
+                        }
+                    }
+                    loop {
+                        match this.update(cx, |this, cx| this.check_exit(id, cx)) {
+                            Ok(true) => break,
+                            Ok(false) => cx.background_executor().timer(EXIT_POLL).await,
+                            Err(err) => {
+                                eprintln!("Update command status failed: {err}");
+                                break;
+                            }
+                        }
+                    }
+                }));
+            }
+            Err(err) => run.status = Status::Failed(format!("Failed to start: {err}").into()),
+        }
+        self.runs.insert(0, run);
+        cx.notify();
+    }
+
+    fn run_mut(&mut self, id: usize) -> Option<&mut Run> {
+        self.runs.iter_mut().find(|run| run.id == id)
+    }
+
+    /// Records the exit status once the run's process is done; returns
+    /// whether it is.
+    fn check_exit(&mut self, id: usize, cx: &mut Context<Self>) -> bool {
+        let Some(run) = self.run_mut(id) else {
+            return true;
+        };
+        let Some(child) = &mut run.child else {
+            return true;
+        };
+        run.status = match child.try_wait() {
+            Ok(Some(_)) if run.cancelled => Status::Cancelled,
+            Ok(Some(status)) => Status::Exited(status.code()),
+            Ok(None) => return false,
+            Err(err) => Status::Failed(format!("Failed to wait for the command: {err}").into()),
+        };
+        run.child = None;
+        cx.notify();
+        true
+    }
+
+    fn cancel(&mut self, id: usize, cx: &mut Context<Self>) {
+        let Some(run) = self.run_mut(id) else {
+            return;
+        };
+        let Some(child) = &mut run.child else {
+            return;
+        };
+        // The run stays running until its output closes and the exit is
+        // picked up as usual.
+        match workflow::kill(child) {
+            Ok(()) => run.cancelled = true,
+            Err(err) => run.status = Status::Failed(format!("Failed to cancel: {err}").into()),
+        }
+        cx.notify();
+    }
+
+    fn clear_finished(&mut self, cx: &mut Context<Self>) {
+        self.runs.retain(|run| run.child.is_some());
+        cx.notify();
+    }
+
+    fn render_commands(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let commands = self.commands.iter().enumerate().map(|(ix, def)| {
+            v_flex()
+                .id(("workflow-command", ix))
+                .px_3()
+                .py_1()
+                .cursor_pointer()
+                .hover(|style| style.bg(cx.theme().muted))
+                .on_click(cx.listener(move |_, _, _, cx| cx.emit(WorkflowViewEvent::Run(ix))))
+                .child(div().truncate().child(def.name.clone()))
+                .child(
+                    div()
+                        .text_xs()
+                        .truncate()
+                        .font_family(MONO_FONT)
+                        .text_color(cx.theme().muted_foreground)
+                        .child(def.command.clone()),
+                )
+        });
+        let empty = self.commands.is_empty();
+
+        v_flex()
+            .w(px(240.0))
+            .h_full()
+            .flex_shrink_0()
+            .border_r_1()
+            .border_color(cx.theme().border)
+            .child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child("Commands"),
+            )
+            .child(
+                div()
+                    .id("workflow-commands")
+                    .flex_1()
+                    .overflow_y_scroll()
+                    .children(commands)
+                    .when(empty, |this| {
+                        this.p_4()
+                            .text_sm()
+                            .text_color(cx.theme().muted_foreground)
+                            .child("No commands. Define them in config.json.")
+                    }),
+            )
+            .children(self.config_error.clone().map(|err| {
+                div()
+                    .px_3()
+                    .py_1()
+                    .text_xs()
+                    .text_color(cx.theme().danger)
+                    .child(err)
+            }))
+            .child(
+                div()
+                    .px_3()
+                    .py_2()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child("Click a command to run it on the item selected in the explorer."),
+            )
+    }
+
+    fn render_run(&self, run: &Run, cx: &mut Context<Self>) -> impl IntoElement + use<> {
+        let (status, color): (SharedString, Hsla) = match &run.status {
+            Status::Running if run.cancelled => ("cancelling…".into(), cx.theme().muted_foreground),
+            Status::Running => ("running…".into(), cx.theme().muted_foreground),
+            Status::Exited(Some(0)) => ("exited 0".into(), cx.theme().muted_foreground),
+            Status::Exited(Some(code)) => (format!("exited {code}").into(), cx.theme().danger),
+            Status::Exited(None) => ("killed by a signal".into(), cx.theme().danger),
+            Status::Cancelled => ("cancelled".into(), cx.theme().muted_foreground),
+            Status::Failed(err) => (err.clone(), cx.theme().danger),
+        };
+        let id = run.id;
+        v_flex()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .child(
+                h_flex()
+                    .gap_2()
+                    .px_3()
+                    .py_1()
+                    .bg(cx.theme().muted)
+                    .child(div().flex_1().truncate().child(run.title.clone()))
+                    .child(div().text_xs().text_color(color).child(status))
+                    .when(run.child.is_some() && !run.cancelled, |this| {
+                        this.child(
+                            Button::new(("cancel-run", id))
+                                .label("Cancel")
+                                .xsmall()
+                                .danger()
+                                .on_click(cx.listener(move |this, _, _, cx| this.cancel(id, cx))),
+                        )
+                    }),
+            )
+            .child(
+                v_flex()
+                    .px_3()
+                    .py_1()
+                    .font_family(MONO_FONT)
+                    .text_xs()
+                    .children(run.lines.iter().map(|line| {
+                        div()
+                            .when(line.stderr, |this| this.text_color(cx.theme().danger))
+                            .child(line.text.clone())
+                    })),
+            )
+    }
+}
+
+impl Render for WorkflowView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let finished = self.runs.iter().any(|run| run.child.is_none());
+        let runs = self.runs.iter().map(|run| self.render_run(run, cx)).collect::<Vec<_>>();
+        let empty = runs.is_empty();
+
+        h_flex()
+            .size_full()
+            .child(self.render_commands(cx))
+            .child(
+                v_flex()
+                    .flex_1()
+                    .h_full()
+                    .child(
+                        h_flex()
+                            .px_3()
+                            .py_1()
+                            .border_b_1()
+                            .border_color(cx.theme().border)
+                            .child(div().flex_1().child("Output"))
+                            .child(
+                                Button::new("clear-finished-runs")
+                                    .label("Clear Finished")
+                                    .small()
+                                    .ghost()
+                                    .disabled(!finished)
+                                    .on_click(cx.listener(|this, _, _, cx| this.clear_finished(cx))),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .id("workflow-runs")
+                            .flex_1()
+                            .overflow_y_scroll()
+                            .children(runs)
+                            .when(empty, |this| {
+                                this.p_4()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child("Nothing has run yet")
+                            }),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::categories::{CategoryDef, Rule};
+use crate::storage;
+use crate::workflow::CommandDef;
+
+const FILE_NAME: &str = "config.json";
+
+/// Settings meant to be edited by hand, unlike the session.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Folders making up the library.
+    pub roots: Vec<PathBuf>,
+    pub categories: Vec<CategoryDef>,
+    /// Commands offered in the Workflow view.
+    pub commands: Vec<CommandDef>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let extensions = |list: &[&str]| vec![Rule::Extensions(list.iter().map(|ext| ext.to_string()).collect())];
+        Self {
+            roots: Vec::new(),
+            categories: vec![
+                CategoryDef {
+                    name: "Books".to_string(),
+                    any: false,
+                    rules: extensions(&["epub", "pdf", "mobi", "azw3", "djvu"]),
+                },
+                CategoryDef {
+                    name: "Documents".to_string(),
+                    any: false,
+                    rules: extensions(&["md", "txt", "doc", "docx", "odt", "rtf"]),
+                },
+                CategoryDef {
+                    name: "Images".to_string(),
+                    any: false,
+                    rules: vec![Rule::Mime("image/*".to_string())],
+                },
+            ],
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// The config file, or the defaults if there is none. A file with
+    /// mistakes is an error to show the user, and is left alone for them to
+    /// fix; the defaults stand in until then.
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let path = dir.join(FILE_NAME);
+        storage::read_json(&path)
+            .map(Option::unwrap_or_default)
+            .map_err(|err| format!("Ignoring {}: {err}", path.display()))
+    }
+}
+
+/// Writes `roots` into the config file, leaving everything else in it as
+/// the user wrote it. A file that isn't JSON is refused rather than
+/// overwritten.
+pub fn save_roots(dir: &Path, roots: &[PathBuf]) -> io::Result<()> {
+    let path = dir.join(FILE_NAME);
+    let mut value = match storage::read_json(&path)? {
+        Some(value) => value,
+        None => serde_json::to_value(Config::default()).map_err(io::Error::other)?,
+    };
+    let Some(object) = value.as_object_mut() else {
+        return Err(io::Error::other(format!("{} is not a JSON object", path.display())));
+    };
+    object.insert("roots".to_string(), serde_json::to_value(roots).map_err(io::Error::other)?);
+    storage::write_json(&path, &value, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_partial_and_invalid_configs() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Config::load(dir.path()).unwrap(), Config::default());
+
+        fs::write(
+            dir.path().join(FILE_NAME),
+            r#"{
+                "categories": [{"name": "Books", "rules": [{"glob": "*.epub|*.pdf|*.mobi"}, {"size": {"min": 1024}}]}],
+                "commands": [{"name": "Format", "command": "cargo fmt", "cwd": "{dir}"}]
+            }"#,
+        )
+        .unwrap();
+        let config = Config::load(dir.path()).unwrap();
+        assert!(config.roots.is_empty());
+        assert_eq!(
+            config.categories[0].rules,
+            [
+                Rule::Glob("*.epub|*.pdf|*.mobi".to_string()),
+                Rule::Size { min: Some(1024), max: None },
+            ]
+        );
+
+        assert_eq!(config.commands[0].cwd.as_deref(), Some("{dir}"));
+
+        save_roots(dir.path(), &["/books".into()]).unwrap();
+        let saved = Config::load(dir.path()).unwrap();
+        assert_eq!(saved.roots, [PathBuf::from("/books")]);
+        assert_eq!(saved.categories, config.categories);
+        assert_eq!(saved.commands, config.commands);
+
+        fs::write(dir.path().join(FILE_NAME), "{\"roots\": 1}").unwrap();
+        assert!(Config::load(dir.path()).unwrap_err().contains("config.json"));
+        fs::write(dir.path().join(FILE_NAME), "{\"roots\": [").unwrap();
+        assert!(save_roots(dir.path(), &[]).is_err());
+    }
+}
@@ -0,0 +1,431 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::trash::{Trash, TrashEntry};
+
+/// A reversible change to the file system. Every operation the user performs
+/// through the shelf is recorded as one of these so it can be undone.
+#[derive(Clone, Debug)]
+pub enum Operation {
+    Create { path: PathBuf, is_dir: bool },
+    Copy { from: PathBuf, to: PathBuf },
+    Move { from: PathBuf, to: PathBuf },
+    Delete { path: PathBuf, entry: TrashEntry },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Apply,
+    Revert,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Clipboard {
+    Copy(PathBuf),
+    Cut(PathBuf),
+}
+
+/// Paths that appeared or disappeared after running an operation.
+#[derive(Default, Debug)]
+pub struct Changes {
+    pub removed: Vec<PathBuf>,
+    pub added: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+pub struct Progress {
+    done: AtomicU64,
+    total: AtomicU64,
+    finished: AtomicBool,
+}
+
+impl Progress {
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    fn finish(&self) {
+        self.finished.store(true, Ordering::Release);
+    }
+
+    fn set_total(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    fn advance(&self, bytes: u64) {
+        self.done.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return Ok(());
+        }
+        let done = self.done.load(Ordering::Relaxed).min(total);
+        write!(f, "{}%", done * 100 / total)
+    }
+}
+
+#[derive(Default)]
+pub struct Journal {
+    undo: Vec<Operation>,
+    redo: Vec<Operation>,
+}
+
+impl Journal {
+    pub fn record(&mut self, op: Operation) {
+        self.undo.push(op);
+        self.redo.clear();
+    }
+
+    pub fn take(&mut self, direction: Direction) -> Option<Operation> {
+        match direction {
+            Direction::Apply => self.redo.pop(),
+            Direction::Revert => self.undo.pop(),
+        }
+    }
+
+    /// Puts an operation taken with [`Journal::take`] back on the right stack
+    /// once it has run; a failed run goes back where it came from.
+    pub fn restore(&mut self, op: Operation, direction: Direction, succeeded: bool) {
+        match (direction, succeeded) {
+            (Direction::Apply, true) | (Direction::Revert, false) => self.undo.push(op),
+            (Direction::Revert, true) | (Direction::Apply, false) => self.redo.push(op),
+        }
+    }
+}
+
+impl Operation {
+    pub fn new_entry(dir: &Path, is_dir: bool) -> Self {
+        let name = if is_dir { "untitled folder" } else { "untitled" };
+        Operation::Create {
+            path: unique_path(dir, name),
+            is_dir,
+        }
+    }
+
+    pub fn duplicate(path: &Path) -> Option<Self> {
+        let dir = path.parent()?;
+        Some(Operation::Copy {
+            from: path.to_path_buf(),
+            to: unique_path(dir, &copy_name(path)),
+        })
+    }
+
+    /// `None` when a cut is pasted back into its own folder, which leaves
+    /// nothing to move.
+    pub fn paste(clipboard: &Clipboard, dir: &Path) -> Result<Option<Self>, String> {
+        let (Clipboard::Copy(from) | Clipboard::Cut(from)) = clipboard;
+        if dir.starts_with(from) {
+            return Err("Cannot paste a folder into itself".into());
+        }
+        if matches!(clipboard, Clipboard::Cut(_)) && from.parent() == Some(dir) {
+            return Ok(None);
+        }
+        let name = from
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| format!("Cannot paste {}", from.display()))?;
+        Ok(Some(match clipboard {
+            Clipboard::Copy(_) => {
+                let name = if from.parent() == Some(dir) {
+                    copy_name(from)
+                } else {
+                    name
+                };
+                Operation::Copy {
+                    from: from.clone(),
+                    to: unique_path(dir, &name),
+                }
+            }
+            Clipboard::Cut(_) => Operation::Move {
+                from: from.clone(),
+                to: unique_path(dir, &name),
+            },
+        }))
+    }
+
+    pub fn delete(path: &Path) -> io::Result<Self> {
+        Ok(Operation::Delete {
+            path: path.to_path_buf(),
+            entry: Trash::home()?.reserve(path)?,
+        })
+    }
+
+    pub fn describe(&self, direction: Direction) -> String {
+        let name = |path: &Path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string())
+        };
+        let (verb, path) = match self {
+            Operation::Create { path, is_dir: true } => ("Create folder", path),
+            Operation::Create { path, is_dir: false } => ("Create file", path),
+            Operation::Copy { from, .. } => ("Copy", from),
+            Operation::Move { from, .. } => ("Move", from),
+            Operation::Delete { path, .. } => ("Move to trash", path),
+        };
+        match direction {
+            Direction::Apply => format!("{verb} {}", name(path)),
+            Direction::Revert => format!("Undo {} {}", verb.to_lowercase(), name(path)),
+        }
+    }
+
+    pub fn run(&self, direction: Direction, progress: &Progress) -> io::Result<Changes> {
+        let result = match direction {
+            Direction::Apply => self.apply(progress),
+            Direction::Revert => self.revert(progress),
+        };
+        progress.finish();
+        result?;
+
+        let (removed, added) = match self {
+            Operation::Create { path, .. } => (vec![], vec![path.clone()]),
+            Operation::Copy { to, .. } => (vec![], vec![to.clone()]),
+            Operation::Move { from, to } => (vec![from.clone()], vec![to.clone()]),
+            Operation::Delete { path, .. } => (vec![path.clone()], vec![]),
+        };
+        Ok(match direction {
+            Direction::Apply => Changes { removed, added },
+            Direction::Revert => Changes {
+                removed: added,
+                added: removed,
+            },
+        })
+    }
+
+    fn apply(&self, progress: &Progress) -> io::Result<()> {
+        match self {
+            Operation::Create { path, is_dir } => {
+                if *is_dir {
+                    fs::create_dir(path)
+                } else {
+                    fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(path)
+                        .map(drop)
+                }
+            }
+            Operation::Copy { from, to } => copy_path(from, to, progress),
+            Operation::Move { from, to } => move_path(from, to, progress),
+            Operation::Delete { path, entry } => entry.put(path, progress),
+        }
+    }
+
+    fn revert(&self, progress: &Progress) -> io::Result<()> {
+        match self {
+            // Only an empty folder is removed so undo never throws away work
+            // done inside it after it was created.
+            Operation::Create { path, is_dir: true } => fs::remove_dir(path),
+            // Whatever was written to it since goes to the trash, not away.
+            Operation::Create { path, is_dir: false } | Operation::Copy { to: path, .. } => {
+                Trash::home()?.reserve(path)?.put(path, progress)
+            }
+            Operation::Move { from, to } => move_path(to, from, progress),
+            Operation::Delete { entry, .. } => entry.restore(progress),
+        }
+    }
+}
+
+/// Returns `dir/name`, or `dir/name 2`, `dir/name 3`, ... if it's taken. The
+/// number goes before the extension so "a.txt" becomes "a 2.txt".
+pub fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let candidate = dir.join(name);
+    if candidate.symlink_metadata().is_err() {
+        return candidate;
+    }
+    let (stem, ext) = split_name(name);
+    (2..)
+        .map(|n| dir.join(format!("{stem} {n}{ext}")))
+        .find(|path| path.symlink_metadata().is_err())
+        .unwrap_or(candidate)
+}
+
+fn copy_name(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if path.is_dir() {
+        return format!("{name} copy");
+    }
+    let (stem, ext) = split_name(&name);
+    format!("{stem} copy{ext}")
+}
+
+fn split_name(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(ix) if ix > 0 => name.split_at(ix),
+        _ => (name, ""),
+    }
+}
+
+pub(crate) fn remove_path(path: &Path) -> io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+pub(crate) fn move_path(from: &Path, to: &Path, progress: &Progress) -> io::Result<()> {
+    if to.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        ));
+    }
+    match fs::rename(from, to) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            copy_path(from, to, progress)?;
+            remove_path(from)
+        }
+        result => result,
+    }
+}
+
+/// Copies `from` to `to`, removing whatever part of `to` was written if the
+/// copy fails partway.
+fn copy_path(from: &Path, to: &Path, progress: &Progress) -> io::Result<()> {
+    progress.set_total(total_size(from));
+    // Something already there isn't ours to clean up.
+    let existed = to.symlink_metadata().is_ok();
+    let result = copy_recursive(from, to, progress);
+    if result.is_err()
+        && !existed
+        && to.symlink_metadata().is_ok()
+        && let Err(err) = remove_path(to)
+    {
+        eprintln!("Failed to remove the partial copy {}: {err}", to.display());
+    }
+    result
+}
+
+fn total_size(path: &Path) -> u64 {
+    let Ok(meta) = path.symlink_metadata() else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| total_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+fn copy_recursive(from: &Path, to: &Path, progress: &Progress) -> io::Result<()> {
+    let meta = from.symlink_metadata()?;
+    if meta.is_symlink() {
+        return copy_symlink(from, to);
+    }
+    if meta.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()), progress)?;
+        }
+        return Ok(());
+    }
+
+    let mut reader = fs::File::open(from)?;
+    let mut writer = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to)?;
+    let mut buf = vec![0; 1 << 20];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n])?;
+        progress.advance(n as u64);
+    }
+    writer.set_permissions(meta.permissions())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_names_keep_the_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(unique_path(dir.path(), "a.txt"), dir.path().join("a.txt"));
+        fs::write(dir.path().join("a.txt"), "").unwrap();
+        assert_eq!(unique_path(dir.path(), "a.txt"), dir.path().join("a 2.txt"));
+        assert_eq!(copy_name(&dir.path().join("a.txt")), "a copy.txt");
+        assert_eq!(copy_name(&dir.path().join(".bashrc")), ".bashrc copy");
+    }
+
+    #[test]
+    fn undo_and_redo_a_move() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("nested/file.txt"), "hello").unwrap();
+        fs::create_dir(dir.path().join("dst")).unwrap();
+
+        assert!(Operation::paste(&Clipboard::Cut(src.clone()), dir.path()).unwrap().is_none());
+        let op = Operation::paste(&Clipboard::Cut(src.clone()), &dir.path().join("dst"))
+            .unwrap()
+            .unwrap();
+        let progress = Progress::default();
+        let changes = op.run(Direction::Apply, &progress).unwrap();
+        let moved = dir.path().join("dst/src/nested/file.txt");
+        assert_eq!(changes.removed, vec![src.clone()]);
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "hello");
+
+        let mut journal = Journal::default();
+        journal.record(op);
+        let op = journal.take(Direction::Revert).unwrap();
+        op.run(Direction::Revert, &Progress::default()).unwrap();
+        journal.restore(op, Direction::Revert, true);
+        assert!(!moved.exists());
+        assert_eq!(fs::read_to_string(src.join("nested/file.txt")).unwrap(), "hello");
+
+        let op = journal.take(Direction::Apply).unwrap();
+        op.run(Direction::Apply, &Progress::default()).unwrap();
+        assert!(moved.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_copy_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+        fs::write(src.join("a.txt"), "hello").unwrap();
+        // A socket can't be opened like a file, so copying it fails.
+        let _socket = std::os::unix::net::UnixListener::bind(src.join("socket")).unwrap();
+
+        let to = dir.path().join("copy");
+        assert!(copy_path(&src, &to, &Progress::default()).is_err());
+        assert!(!to.exists());
+        assert!(copy_path(&src, &src, &Progress::default()).is_err());
+        assert!(src.join("a.txt").exists());
+    }
+
+    #[test]
+    fn paste_into_itself_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let clipboard = Clipboard::Copy(dir.path().to_path_buf());
+        assert!(Operation::paste(&clipboard, &dir.path().join("child")).is_err());
+    }
+}
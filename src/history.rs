@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+/// Locations kept in each direction; older ones fall off.
+const MAX_HISTORY: usize = 100;
+
+/// A folder that was shown, with the file that was selected in it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub dir: PathBuf,
+    pub selected: Option<PathBuf>,
+}
+
+/// Browser-style back and forward stacks. The current location isn't kept
+/// here; it is passed in whenever it is left, so it carries whatever was
+/// selected at that moment.
+#[derive(Default)]
+pub struct History {
+    back: Vec<Location>,
+    forward: Vec<Location>,
+}
+
+impl History {
+    /// Records leaving `from` for somewhere new, which drops the forward
+    /// history.
+    pub fn push(&mut self, from: Location) {
+        self.back.push(from);
+        if self.back.len() > MAX_HISTORY {
+            self.back.remove(0);
+        }
+        self.forward.clear();
+    }
+
+    /// Goes `steps` locations back from `current`, or nowhere if there
+    /// aren't that many.
+    pub fn go_back(&mut self, steps: usize, current: Location) -> Option<Location> {
+        step(&mut self.back, &mut self.forward, steps, current)
+    }
+
+    pub fn go_forward(&mut self, steps: usize, current: Location) -> Option<Location> {
+        step(&mut self.forward, &mut self.back, steps, current)
+    }
+
+    /// Locations behind the current one, most recent first.
+    pub fn back_entries(&self) -> impl Iterator<Item = &Location> {
+        self.back.iter().rev()
+    }
+
+    /// Locations ahead of the current one, nearest first.
+    pub fn forward_entries(&self) -> impl Iterator<Item = &Location> {
+        self.forward.iter().rev()
+    }
+}
+
+fn step(from: &mut Vec<Location>, to: &mut Vec<Location>, steps: usize, current: Location) -> Option<Location> {
+    if steps == 0 || steps > from.len() {
+        return None;
+    }
+    let mut current = current;
+    for _ in 0..steps {
+        let next = from.pop()?;
+        to.push(current);
+        current = next;
+    }
+    Some(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(dir: &str, selected: Option<&str>) -> Location {
+        Location {
+            dir: dir.into(),
+            selected: selected.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn steps_back_and_forward_keeping_selections() {
+        let mut history = History::default();
+        history.push(at("/a", Some("/a/1.txt")));
+        history.push(at("/b", None));
+        history.push(at("/c", None));
+
+        let current = history.go_back(2, at("/d", Some("/d/x.md"))).unwrap();
+        assert_eq!(current, at("/b", None));
+        let dirs = |entries: Vec<&Location>| entries.iter().map(|l| l.dir.clone()).collect::<Vec<_>>();
+        assert_eq!(dirs(history.back_entries().collect()), [PathBuf::from("/a")]);
+        assert_eq!(dirs(history.forward_entries().collect()), [PathBuf::from("/c"), PathBuf::from("/d")]);
+
+        assert_eq!(history.go_forward(2, current.clone()), Some(at("/d", Some("/d/x.md"))));
+        assert_eq!(history.go_back(3, at("/d", None)).unwrap(), at("/a", Some("/a/1.txt")));
+        assert_eq!(history.go_back(1, at("/a", None)), None);
+
+        history.push(at("/a", None));
+        assert_eq!(history.forward_entries().count(), 0);
+    }
+}
@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::storage::{self, BaseDir};
+
+/// Bigger files aren't hashed; reading them whole would take too long.
+const HASH_LIMIT: u64 = 16 * 1024 * 1024;
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FileInfo {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    /// FNV-1a of the contents, once it has been read.
+    pub hash: Option<u64>,
+}
+
+impl FileInfo {
+    /// Whether `other` describes the same contents, as far as can be told
+    /// without reading them.
+    fn unchanged(&self, other: &FileInfo) -> bool {
+        self.len == other.len && self.modified == other.modified
+    }
+}
+
+/// A changed path as it is now on disk.
+pub enum Refresh {
+    Gone(PathBuf),
+    /// The files at or under the path, not hashed yet.
+    Found(PathBuf, Vec<(PathBuf, FileInfo)>),
+}
+
+/// What applying a [`Refresh`] changed in an index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// The path and everything under it.
+    Removed(PathBuf),
+    Updated(PathBuf, FileInfo),
+}
+
+/// Every file under one library root, read once and then kept current, so
+/// features can look files up without going to the disk.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    root: PathBuf,
+    files: BTreeMap<PathBuf, FileInfo>,
+    /// Files that couldn't be read to hash them, as they were then. They
+    /// are only tried again once they change.
+    #[serde(skip)]
+    unreadable: BTreeMap<PathBuf, FileInfo>,
+}
+
+/// Where the indexes are cached, one file per root.
+pub fn cache_dir() -> Option<PathBuf> {
+    storage::dir(BaseDir::Cache).map(|dir| dir.join("index"))
+}
+
+impl Index {
+    /// The index saved for `root` by an earlier run, if any. It is only a
+    /// cache, so one that can't be read is simply rebuilt.
+    pub fn load(dir: &Path, root: &Path) -> Option<Self> {
+        storage::read_json::<Self>(&cache_file(dir, root))
+            .ok()
+            .flatten()
+            .filter(|index| index.root == root)
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        storage::write_json(&cache_file(dir, &self.root), self, false)
+    }
+
+    /// Reads `root` from disk, keeping the hashes `previous` has for files
+    /// that haven't changed since.
+    pub fn build(root: &Path, previous: Option<&Index>) -> Self {
+        let mut files = BTreeMap::new();
+        for (path, mut info) in scan(root) {
+            if let Some(known) = previous.and_then(|previous| previous.files.get(&path))
+                && known.unchanged(&info)
+            {
+                info.hash = known.hash;
+            }
+            files.insert(path, info);
+        }
+        Self {
+            root: root.to_path_buf(),
+            files,
+            unreadable: BTreeMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn files(&self) -> &BTreeMap<PathBuf, FileInfo> {
+        &self.files
+    }
+
+    pub fn apply(&mut self, refreshes: Vec<Refresh>) -> Vec<Change> {
+        let mut changes = Vec::new();
+        for refresh in refreshes {
+            match refresh {
+                Refresh::Gone(path) => {
+                    self.files.retain(|known, _| !known.starts_with(&path));
+                    self.unreadable.retain(|known, _| !known.starts_with(&path));
+                    changes.push(Change::Removed(path));
+                }
+                Refresh::Found(path, found) => {
+                    // Whatever was under a replaced folder and isn't anymore goes.
+                    let present = found.iter().map(|(path, _)| path).collect::<HashSet<_>>();
+                    let stale = self
+                        .files
+                        .range(path.clone()..)
+                        .take_while(|(known, _)| known.starts_with(&path))
+                        .filter(|(known, _)| !present.contains(known))
+                        .map(|(known, _)| known.clone())
+                        .collect::<Vec<_>>();
+                    for stale in stale {
+                        self.files.remove(&stale);
+                        self.unreadable.remove(&stale);
+                        changes.push(Change::Removed(stale));
+                    }
+                    for (path, info) in found {
+                        if self.files.get(&path).is_some_and(|known| known.unchanged(&info)) {
+                            continue;
+                        }
+                        self.files.insert(path.clone(), info.clone());
+                        changes.push(Change::Updated(path, info));
+                    }
+                }
+            }
+        }
+        changes
+    }
+
+    /// Files still waiting for their contents to be hashed.
+    pub fn unhashed(&self) -> Vec<(PathBuf, FileInfo)> {
+        self.files
+            .iter()
+            .filter(|(_, info)| info.hash.is_none() && info.len <= HASH_LIMIT)
+            .filter(|(path, info)| !self.unreadable.get(*path).is_some_and(|failed| failed.unchanged(info)))
+            .map(|(path, info)| (path.clone(), info.clone()))
+            .collect()
+    }
+
+    /// Takes in hashes computed off the main thread, for files that didn't
+    /// change again in the meantime. Files without one couldn't be read.
+    pub fn set_hashes(&mut self, hashed: Vec<(PathBuf, FileInfo)>) {
+        for (path, info) in hashed {
+            let Some(known) = self.files.get_mut(&path).filter(|known| known.unchanged(&info)) else {
+                continue;
+            };
+            match info.hash {
+                Some(hash) => known.hash = Some(hash),
+                None => {
+                    self.unreadable.insert(path, info);
+                }
+            }
+        }
+    }
+}
+
+fn cache_file(dir: &Path, root: &Path) -> PathBuf {
+    let hash = fnv1a(FNV_OFFSET, root.to_string_lossy().as_bytes());
+    dir.join(format!("{hash:016x}.json"))
+}
+
+/// Hashes each of `files`; the ones that can't be read are left without.
+pub fn hash_files(files: Vec<(PathBuf, FileInfo)>) -> Vec<(PathBuf, FileInfo)> {
+    files
+        .into_iter()
+        .map(|(path, mut info)| {
+            info.hash = content_hash(&path, info.len);
+            (path, info)
+        })
+        .collect()
+}
+
+/// FNV-1a of the file at `path`, if it is `len` bytes or fewer.
+pub fn content_hash(path: &Path, len: u64) -> Option<u64> {
+    if len > HASH_LIMIT {
+        return None;
+    }
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut buf = [0; 8192];
+    let mut hash = FNV_OFFSET;
+    loop {
+        let read = reader.read(&mut buf).ok()?;
+        if read == 0 {
+            return Some(hash);
+        }
+        hash = fnv1a(hash, &buf[..read]);
+    }
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash = (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Every file under `root`, leaving out hidden files and folders.
+pub fn scan(root: &Path) -> Vec<(PathBuf, FileInfo)> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(read_dir) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read_dir.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            // Links aren't followed, so a link to a parent can't loop.
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+            } else if file_type.is_file()
+                && let Ok(meta) = entry.metadata()
+            {
+                files.push((entry.path(), file_info(&meta)));
+            }
+        }
+    }
+    files
+}
+
+/// Whether `path` is, or is inside, a hidden entry of `root`; [`scan`]
+/// leaves those out.
+pub fn is_hidden(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root).is_ok_and(|relative| {
+        relative
+            .components()
+            .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+    })
+}
+
+/// Reads what changed at each of `paths`, off the main thread.
+pub fn refresh(paths: Vec<PathBuf>) -> Vec<Refresh> {
+    paths
+        .into_iter()
+        .map(|path| match fs::symlink_metadata(&path) {
+            Ok(meta) if meta.is_dir() => {
+                let files = scan(&path);
+                Refresh::Found(path, files)
+            }
+            Ok(meta) if meta.is_file() => Refresh::Found(path.clone(), vec![(path, file_info(&meta))]),
+            _ => Refresh::Gone(path),
+        })
+        .collect()
+}
+
+fn file_info(meta: &fs::Metadata) -> FileInfo {
+    FileInfo {
+        len: meta.len(),
+        modified: meta.modified().ok(),
+        hash: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexes_and_follows_changes() {
+        let root = tempfile::tempdir().unwrap();
+        let cache = tempfile::tempdir().unwrap();
+        let path = |name: &str| root.path().join(name);
+        fs::create_dir(path("docs")).unwrap();
+        fs::write(path("a.txt"), "hello").unwrap();
+        fs::write(path("docs/b.txt"), "world").unwrap();
+        fs::write(path(".hidden"), "").unwrap();
+
+        let mut index = Index::build(root.path(), None);
+        assert_eq!(index.files().len(), 2);
+        index.set_hashes(hash_files(index.unhashed()));
+        assert!(index.unhashed().is_empty());
+        assert_eq!(index.files()[&path("a.txt")].hash, content_hash(&path("a.txt"), 5));
+        assert_ne!(index.files()[&path("a.txt")].hash, index.files()[&path("docs/b.txt")].hash);
+
+        index.save(cache.path()).unwrap();
+        let cached = Index::load(cache.path(), root.path()).unwrap();
+        assert_eq!(cached, index);
+        // Unchanged files keep their hashes when the root is read again.
+        assert!(Index::build(root.path(), Some(&cached)).unhashed().is_empty());
+
+        fs::rename(path("docs"), path("notes")).unwrap();
+        fs::write(path("a.txt"), "hello, world").unwrap();
+        let changes = index.apply(refresh(vec![path("docs"), path("notes"), path("a.txt")]));
+        assert_eq!(changes[0], Change::Removed(path("docs")));
+        assert!(matches!(&changes[1], Change::Updated(updated, _) if *updated == path("notes/b.txt")));
+        assert!(matches!(&changes[2], Change::Updated(updated, info) if *updated == path("a.txt") && info.len == 12));
+        assert_eq!(
+            index.files().keys().cloned().collect::<Vec<_>>(),
+            [path("a.txt"), path("notes/b.txt")]
+        );
+        assert_eq!(index.unhashed().len(), 2);
+
+        // Gone before it could be read: not tried again until it changes.
+        fs::write(path("c.txt"), "later").unwrap();
+        index.apply(refresh(vec![path("c.txt")]));
+        fs::remove_file(path("c.txt")).unwrap();
+        index.set_hashes(hash_files(index.unhashed()));
+        assert!(index.unhashed().is_empty());
+        assert_eq!(index.files()[&path("c.txt")].hash, None);
+    }
+}
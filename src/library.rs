@@ -0,0 +1,260 @@
+use gpui::{AppContext, Context, EventEmitter, SharedString, Task};
+use notify::RecommendedWatcher;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config;
+use crate::index::{self, Change, Index};
+use crate::storage::{self, BaseDir, WriteOrder};
+use crate::watcher::{self, FsChange};
+
+/// How long hashing has to settle before the index is cached, so a busy
+/// folder doesn't have its whole index written out on every change.
+const SAVE_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+pub enum LibraryEvent {
+    /// Roots came or went, or one was read for the first time.
+    Rebuilt,
+    /// Files under the root `root` changed on disk.
+    Changed { root: PathBuf, changes: Vec<Change> },
+}
+
+/// The folders making up the collection, each indexed in the background
+/// and kept current by a watcher.
+pub struct Library {
+    roots: Vec<Root>,
+}
+
+pub struct Root {
+    path: PathBuf,
+    /// Missing until the first read finishes. Shared with background tasks
+    /// reading it, so it is only copied if it changes while one does.
+    index: Option<Arc<Index>>,
+    error: Option<SharedString>,
+    writes: Arc<WriteOrder>,
+    _watcher: Option<RecommendedWatcher>,
+    _tasks: Vec<Task<()>>,
+    _hash_task: Option<Task<()>>,
+    _save_task: Option<Task<()>>,
+}
+
+impl Root {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn index(&self) -> Option<&Index> {
+        self.index.as_deref()
+    }
+
+    pub fn error(&self) -> Option<&SharedString> {
+        self.error.as_ref()
+    }
+}
+
+impl EventEmitter<LibraryEvent> for Library {}
+
+impl Library {
+    pub fn new(roots: Vec<PathBuf>, cx: &mut Context<Self>) -> Self {
+        let mut this = Self { roots: Vec::new() };
+        for root in roots {
+            this.open(root, cx);
+        }
+        this
+    }
+
+    pub fn roots(&self) -> &[Root] {
+        &self.roots
+    }
+
+    /// The indexes read so far, shared so they can be gone through off the
+    /// UI thread.
+    pub fn snapshot(&self) -> Vec<Arc<Index>> {
+        self.roots.iter().filter_map(|root| root.index.clone()).collect()
+    }
+
+    pub fn is_indexing(&self) -> bool {
+        self.roots.iter().any(|root| root.index.is_none())
+    }
+
+    pub fn add_root(&mut self, path: &Path, cx: &mut Context<Self>) -> Result<(), SharedString> {
+        let path = fs::canonicalize(path).map_err(|err| format!("Can't add {}: {err}", path.display()))?;
+        if !path.is_dir() {
+            return Err(format!("{} is not a folder", path.display()).into());
+        }
+        // A file in two roots would be counted twice everywhere.
+        if let Some(root) = self
+            .roots
+            .iter()
+            .find(|root| path.starts_with(&root.path) || root.path.starts_with(&path))
+        {
+            return Err(format!("{} overlaps the library folder {}", path.display(), root.path.display()).into());
+        }
+        self.open(path, cx);
+        self.save_roots();
+        cx.emit(LibraryEvent::Rebuilt);
+        cx.notify();
+        Ok(())
+    }
+
+    pub fn remove_root(&mut self, path: &Path, cx: &mut Context<Self>) {
+        self.roots.retain(|root| root.path != path);
+        self.save_roots();
+        cx.emit(LibraryEvent::Rebuilt);
+        cx.notify();
+    }
+
+    fn save_roots(&self) {
+        let Some(dir) = storage::dir(BaseDir::Config) else {
+            return;
+        };
+        let roots = self.roots.iter().map(|root| root.path.clone()).collect::<Vec<_>>();
+        if let Err(err) = config::save_roots(&dir, &roots) {
+            eprintln!("Failed to save library folders to {}: {err}", dir.display());
+        }
+    }
+
+    fn root_mut(&mut self, path: &Path) -> Option<&mut Root> {
+        self.roots.iter_mut().find(|root| root.path == path)
+    }
+
+    /// Reads `path` in the background, starting from the index cached by an
+    /// earlier run, and watches it from then on.
+    fn open(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        let scan = cx.background_spawn({
+            let path = path.clone();
+            async move {
+                let cached = index::cache_dir().and_then(|dir| Index::load(&dir, &path));
+                Index::build(&path, cached.as_ref())
+            }
+        });
+        let mut tasks = vec![cx.spawn(async move |this, cx| {
+            let index = scan.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                let path = index.root().to_path_buf();
+                if let Some(root) = this.root_mut(&path) {
+                    root.index = Some(Arc::new(index));
+                }
+                this.hash(&path, cx);
+                cx.emit(LibraryEvent::Rebuilt);
+                cx.notify();
+            }) {
+                eprintln!("Update library index failed: {err}");
+            }
+        })];
+
+        let (watcher, error) = match watcher::watch_tree(&path) {
+            Ok((watcher, mut changes)) => {
+                let root = path.clone();
+                tasks.push(cx.spawn(async move |this, cx| {
+                    while let Some(batch) = watcher::next_burst(&mut changes, cx.background_executor()).await {
+                        let paths = batch
+                            .into_iter()
+                            .flat_map(|change| match change {
+                                FsChange::Added(path) | FsChange::Removed(path) => vec![path],
+                                FsChange::Renamed(from, to) => vec![from, to],
+                            })
+                            .filter(|path| !index::is_hidden(&root, path))
+                            .collect();
+                        let refreshes = cx.background_spawn(async move { index::refresh(paths) }).await;
+                        if let Err(err) = this.update(cx, |this, cx| {
+                            let Some(index) = this.root_mut(&root).and_then(|root| root.index.as_mut()) else {
+                                return;
+                            };
+                            let changes = Arc::make_mut(index).apply(refreshes);
+                            if !changes.is_empty() {
+                                this.hash(&root, cx);
+                                cx.emit(LibraryEvent::Changed {
+                                    root: root.clone(),
+                                    changes,
+                                });
+                                cx.notify();
+                            }
+                        }) {
+                            eprintln!("Update library index failed: {err}");
+                            break;
+                        }
+                    }
+                }));
+                (Some(watcher), None)
+            }
+            Err(err) => (None, Some(format!("Not watching: {err}").into())),
+        };
+
+        self.roots.push(Root {
+            path,
+            index: None,
+            error,
+            writes: Arc::default(),
+            _watcher: watcher,
+            _tasks: tasks,
+            _hash_task: None,
+            _save_task: None,
+        });
+    }
+
+    /// Hashes the files of `path` that aren't yet, then caches the index so
+    /// the next start doesn't have to.
+    fn hash(&mut self, path: &Path, cx: &mut Context<Self>) {
+        let Some(root) = self.root_mut(path) else {
+            return;
+        };
+        let Some(index) = &root.index else {
+            return;
+        };
+        let unhashed = index.unhashed();
+        if unhashed.is_empty() {
+            return;
+        }
+        let task = cx.background_spawn(async move { index::hash_files(unhashed) });
+        let path = path.to_path_buf();
+        root._hash_task = Some(cx.spawn(async move |this, cx| {
+            let hashed = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                let Some(index) = this.root_mut(&path).and_then(|root| root.index.as_mut()) else {
+                    return;
+                };
+                Arc::make_mut(index).set_hashes(hashed);
+                this.save_index(&path, cx);
+            }) {
+                eprintln!("Update library hashes failed: {err}");
+            }
+        }));
+    }
+
+    /// Caches the index of `path` once it has been left alone for
+    /// [`SAVE_DELAY`]; each call starts the wait over.
+    fn save_index(&mut self, path: &Path, cx: &mut Context<Self>) {
+        let Some(root) = self.root_mut(path) else {
+            return;
+        };
+        let path = path.to_path_buf();
+        root._save_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(SAVE_DELAY).await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                let Some(dir) = index::cache_dir() else {
+                    return;
+                };
+                let Some(root) = this.root_mut(&path) else {
+                    return;
+                };
+                let Some(index) = root.index.clone() else {
+                    return;
+                };
+                let writes = root.writes.clone();
+                let ticket = writes.ticket();
+                cx.background_spawn(async move {
+                    if let Err(err) = writes.write(ticket, || index.save(&dir)) {
+                        eprintln!("Failed to cache the index of {}: {err}", index.root().display());
+                    }
+                })
+                .detach();
+            }) {
+                eprintln!("Cache library index failed: {err}");
+            }
+        }));
+    }
+}
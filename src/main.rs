@@ -1,20 +1,45 @@
 use gpui::{
-    actions, prelude::*, px, size, App, Application, Bounds, KeyBinding,
-    Menu, MenuItem, SharedString, WindowBounds, WindowOptions,
+    actions, prelude::*, App, Application, KeyBinding, Menu, MenuItem, SharedString, WindowOptions,
 };
 use gpui_component::Root;
 
+mod binary;
+mod categories;
+mod charset;
+mod compare;
 mod component;
+mod config;
+mod file_ops;
+mod history;
+mod index;
+mod library;
+mod markdown;
+mod path_complete;
+mod session;
 mod shelf;
+mod storage;
+mod syntax;
+mod tags;
+mod text_file;
+mod trash;
+mod typography;
 mod ui;
+mod watcher;
+mod workflow;
 
+use component::{file_tree, header};
+use session::Session;
 use shelf::Shelf;
+use storage::BaseDir;
 
 actions!(toshokan, [Quit]);
 
 fn main() {
-    Application::new().run(move |cx: &mut App| {
+    Application::new().with_assets(markdown::ImageFiles).run(move |cx: &mut App| {
         gpui_component::init(cx);
+        file_tree::init(cx);
+        header::init(cx);
+        shelf::init(cx);
 
         cx.on_action(|_: &Quit, cx| cx.quit());
         cx.bind_keys([KeyBinding::new("cmd-q", Quit, Some("App"))]);
@@ -24,18 +49,20 @@ fn main() {
         }]);
         cx.activate(true);
 
-        let bounds = Bounds::centered(None, size(px(1200.0), px(800.0)), cx);
+        let session = storage::dir(BaseDir::State)
+            .map(|dir| Session::load(&dir))
+            .unwrap_or_default();
         let window_options = WindowOptions {
             titlebar: Some(gpui::TitlebarOptions {
                 title: Some(SharedString::from("图书馆")),
                 appears_transparent: true,
                 ..Default::default()
             }),
-            window_bounds: Some(WindowBounds::Windowed(bounds)),
+            window_bounds: Some(shelf::window_bounds(session.window, cx)),
             ..Default::default()
         };
         if let Err(err) = cx.open_window(window_options, |window, cx| {
-            let shelf = cx.new(|cx| Shelf::new(window, cx));
+            let shelf = cx.new(|cx| Shelf::new(session, window, cx));
             cx.new(|cx| Root::new(shelf, window, cx))
         }) {
             eprintln!("Failed to open window: {err}");
@@ -0,0 +1,399 @@
+use gpui::{AssetSource, Result, SharedString};
+use markdown::mdast::Node;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// A Markdown document made ready for display. gpui-component's `TextView`
+/// renders most of it, but can't read images from disk and hands every link
+/// to the system opener, so the blocks with local links are kept apart for
+/// the viewer to render and open itself.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Preview {
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Block {
+    /// Markdown for `TextView`, with local images pointing at `file://`
+    /// URLs, which [`ImageFiles`] serves.
+    Markdown(SharedString),
+    Heading(u8, Inline),
+    Paragraph(Inline),
+    /// Items of an ordered list are numbered from `start`.
+    List { start: Option<u32>, items: Vec<Vec<Block>> },
+    Quote(Vec<Block>),
+}
+
+/// The text of a paragraph or heading with local links in it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Inline {
+    pub text: SharedString,
+    pub marks: Vec<(Range<usize>, Mark)>,
+    pub links: Vec<(Range<usize>, Target)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mark {
+    Strong,
+    Emphasis,
+    Delete,
+    Code,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Local(PathBuf),
+    Remote(String),
+}
+
+/// Splits `source` into blocks, resolving local images and links against
+/// `base_dir`, the folder of the document.
+pub fn prepare(source: &str, base_dir: &Path) -> Preview {
+    let root = match markdown::to_mdast(source, &markdown::ParseOptions::gfm()) {
+        Ok(root) => root,
+        Err(err) => {
+            eprintln!("Failed to parse markdown: {err}");
+            return Preview {
+                blocks: vec![Block::Markdown(source.to_string().into())],
+            };
+        }
+    };
+    let mut preparer = Preparer {
+        source,
+        base_dir,
+        definitions: HashMap::new(),
+        definition_spans: Vec::new(),
+        images: Vec::new(),
+    };
+    preparer.collect_definitions(&root);
+    preparer.collect_images(&root);
+
+    let mut blocks = Vec::new();
+    // Neighbouring blocks without local links share one `TextView`.
+    let mut pending: Option<Range<usize>> = None;
+    for child in root.children().into_iter().flatten() {
+        let Some(range) = span(child).filter(|_| !matches!(child, Node::Definition(_))) else {
+            continue;
+        };
+        if preparer.has_local_link(child) {
+            if let Some(pending) = pending.take() {
+                blocks.push(preparer.markdown(pending));
+            }
+            preparer.push_block(child, &mut blocks);
+        } else {
+            pending = Some(pending.map_or(range.clone(), |pending| pending.start..range.end));
+        }
+    }
+    if let Some(pending) = pending {
+        blocks.push(preparer.markdown(pending));
+    }
+    Preview { blocks }
+}
+
+/// Resolves an image or link target against the folder of the document.
+/// Remote URLs, and anything else with a scheme, have no local path.
+pub fn local_path(base_dir: &Path, url: &str) -> Option<PathBuf> {
+    let url = url.split(['#', '?']).next().unwrap_or("");
+    let url = url.strip_prefix("file://").unwrap_or(url);
+    let has_scheme = url.split_once(':').is_some_and(|(scheme, _)| {
+        !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    if url.is_empty() || has_scheme {
+        return None;
+    }
+    Some(base_dir.join(url))
+}
+
+/// Serves the `file://` URLs [`prepare`] gives local images. gpui fetches
+/// other URLs itself and asks the asset source for anything else.
+pub struct ImageFiles;
+
+impl AssetSource for ImageFiles {
+    fn load(&self, path: &str) -> Result<Option<Cow<'static, [u8]>>> {
+        let Some(path) = path.strip_prefix("file://") else {
+            return Ok(None);
+        };
+        Ok(Some(Cow::Owned(fs::read(percent_decode(path))?)))
+    }
+
+    fn list(&self, _: &str) -> Result<Vec<SharedString>> {
+        Ok(Vec::new())
+    }
+}
+
+struct Preparer<'a> {
+    source: &'a str,
+    base_dir: &'a Path,
+    /// Reference definitions by identifier, for `[text][ref]` links.
+    definitions: HashMap<String, String>,
+    /// Appended to every Markdown block, so its reference links resolve.
+    definition_spans: Vec<Range<usize>>,
+    /// Local images and what they're rewritten to, in document order.
+    images: Vec<(Range<usize>, String)>,
+}
+
+impl Preparer<'_> {
+    fn collect_definitions(&mut self, node: &Node) {
+        if let Node::Definition(definition) = node {
+            self.definitions
+                .entry(definition.identifier.clone())
+                .or_insert_with(|| definition.url.clone());
+            self.definition_spans.extend(span(node));
+        }
+        for child in node.children().into_iter().flatten() {
+            self.collect_definitions(child);
+        }
+    }
+
+    fn collect_images(&mut self, node: &Node) {
+        if let (Some(image), Some(range)) = (self.image(node), span(node)) {
+            return self.images.push((range, image));
+        }
+        for child in node.children().into_iter().flatten() {
+            self.collect_images(child);
+        }
+    }
+
+    /// `node` as Markdown, if it's a local image.
+    fn image(&self, node: &Node) -> Option<String> {
+        let (alt, url) = match node {
+            Node::Image(image) => (&image.alt, image.url.as_str()),
+            Node::ImageReference(image) => (&image.alt, self.definitions.get(&image.identifier)?.as_str()),
+            _ => return None,
+        };
+        Some(image_markdown(alt, &local_path(self.base_dir, url)?))
+    }
+
+    fn link_target(&self, node: &Node) -> Option<Target> {
+        let url = match node {
+            Node::Link(link) => link.url.as_str(),
+            Node::LinkReference(link) => self.definitions.get(&link.identifier)?.as_str(),
+            _ => return None,
+        };
+        Some(match local_path(self.base_dir, url) {
+            Some(path) => Target::Local(path),
+            None => Target::Remote(url.to_string()),
+        })
+    }
+
+    fn has_local_link(&self, node: &Node) -> bool {
+        matches!(self.link_target(node), Some(Target::Local(_)))
+            || node.children().into_iter().flatten().any(|child| self.has_local_link(child))
+    }
+
+    /// The source in `range` with its local images rewritten.
+    fn markdown(&self, range: Range<usize>) -> Block {
+        // Images never nest, so the edits are sorted and don't overlap.
+        let mut markdown = String::with_capacity(range.len());
+        let mut end = range.start;
+        for (image, replacement) in &self.images {
+            if image.start >= range.start && image.end <= range.end {
+                markdown.push_str(&self.source[end..image.start]);
+                markdown.push_str(replacement);
+                end = image.end;
+            }
+        }
+        markdown.push_str(&self.source[end..range.end]);
+        for definition in &self.definition_spans {
+            markdown.push('\n');
+            markdown.push_str(&self.source[definition.clone()]);
+        }
+        Block::Markdown(markdown.into())
+    }
+
+    fn push_block(&self, node: &Node, blocks: &mut Vec<Block>) {
+        match node {
+            Node::Definition(_) => {}
+            Node::Paragraph(paragraph) => self.push_inline(&paragraph.children, Block::Paragraph, blocks),
+            Node::Heading(heading) => {
+                self.push_inline(&heading.children, |inline| Block::Heading(heading.depth, inline), blocks)
+            }
+            Node::List(list) => {
+                let items = list
+                    .children
+                    .iter()
+                    .map(|item| {
+                        let mut blocks = Vec::new();
+                        for child in item.children().into_iter().flatten() {
+                            self.push_block(child, &mut blocks);
+                        }
+                        blocks
+                    })
+                    .collect();
+                blocks.push(Block::List {
+                    start: list.ordered.then_some(list.start.unwrap_or(1)),
+                    items,
+                });
+            }
+            Node::Blockquote(quote) => {
+                let mut quoted = Vec::new();
+                for child in &quote.children {
+                    self.push_block(child, &mut quoted);
+                }
+                blocks.push(Block::Quote(quoted));
+            }
+            _ => blocks.extend(span(node).map(|range| self.markdown(range))),
+        }
+    }
+
+    /// Pushes the block made of `nodes`, followed by its images, which
+    /// can't be laid out inside its text.
+    fn push_inline(&self, nodes: &[Node], block: impl FnOnce(Inline) -> Block, blocks: &mut Vec<Block>) {
+        let mut text = String::new();
+        let mut inline = Inline::default();
+        let mut images = Vec::new();
+        for node in nodes {
+            self.visit_inline(node, &mut text, &mut inline, &mut images);
+        }
+        inline.text = text.into();
+        blocks.push(block(inline));
+        if !images.is_empty() {
+            blocks.push(Block::Markdown(images.join(" ").into()));
+        }
+    }
+
+    fn visit_inline(&self, node: &Node, text: &mut String, inline: &mut Inline, images: &mut Vec<String>) {
+        let start = text.len();
+        let mark = match node {
+            Node::Text(node) => return text.push_str(&node.value),
+            Node::InlineCode(code) => {
+                text.push_str(&code.value);
+                return inline.marks.push((start..text.len(), Mark::Code));
+            }
+            Node::Break(_) => return text.push('\n'),
+            Node::Image(_) | Node::ImageReference(_) => {
+                let source = span(node).map(|range| self.source[range].to_string());
+                return images.extend(self.image(node).or(source));
+            }
+            Node::Strong(_) => Some(Mark::Strong),
+            Node::Emphasis(_) => Some(Mark::Emphasis),
+            Node::Delete(_) => Some(Mark::Delete),
+            Node::Link(_) | Node::LinkReference(_) => None,
+            _ => {
+                if let Some(range) = span(node) {
+                    text.push_str(&self.source[range]);
+                }
+                return;
+            }
+        };
+        for child in node.children().into_iter().flatten() {
+            self.visit_inline(child, text, inline, images);
+        }
+        if let Some(mark) = mark {
+            inline.marks.push((start..text.len(), mark));
+        } else if let Some(target) = self.link_target(node) {
+            inline.links.push((start..text.len(), target));
+        }
+    }
+}
+
+fn span(node: &Node) -> Option<Range<usize>> {
+    let position = node.position()?;
+    Some(position.start.offset..position.end.offset)
+}
+
+fn image_markdown(alt: &str, path: &Path) -> String {
+    let alt = alt.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]");
+    // Angle brackets allow spaces in the path, but not `<` or `>`.
+    let path = path
+        .display()
+        .to_string()
+        .replace('%', "%25")
+        .replace('<', "%3C")
+        .replace('>', "%3E");
+    format!("![{alt}](<file://{path}>)")
+}
+
+/// Undoes the escapes [`image_markdown`] puts in a path.
+fn percent_decode(path: &str) -> String {
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let [byte, tail @ ..] = rest {
+        let escaped = match tail {
+            [high, low, ..] if *byte == b'%' => std::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(escaped) => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(*byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::{ImageSource, Resource};
+
+    #[test]
+    fn keeps_local_links_inline() {
+        let preview = prepare(
+            "# Title\n\n![logo](<img/my logo.png>) ![remote](https://example.com/a.png)\n\n\
+             See [the **notes**](notes.md), [docs][d] and [the web](https://example.com).\n\n\
+             - [Guide](guide.md)\n- plain\n\n[d]: ../docs/\n",
+            Path::new("/books"),
+        );
+        let [Block::Markdown(head), Block::Paragraph(paragraph), Block::List { start: None, items }] =
+            preview.blocks.as_slice()
+        else {
+            panic!("unexpected blocks: {:?}", preview.blocks);
+        };
+        assert_eq!(
+            head,
+            "# Title\n\n![logo](<file:///books/img/my logo.png>) ![remote](https://example.com/a.png)\n[d]: ../docs/"
+        );
+        assert_eq!(paragraph.text, "See the notes, docs and the web.");
+        assert_eq!(paragraph.marks, [(8..13, Mark::Strong)]);
+        assert_eq!(
+            paragraph.links,
+            [
+                (4..13, Target::Local(PathBuf::from("/books/notes.md"))),
+                (15..19, Target::Local(PathBuf::from("/books/../docs/"))),
+                (24..31, Target::Remote("https://example.com".to_string())),
+            ]
+        );
+        let [guide, plain] = items.as_slice() else {
+            panic!("unexpected items: {items:?}");
+        };
+        assert!(matches!(guide.as_slice(), [Block::Paragraph(item)] if item.text == "Guide"));
+        assert!(matches!(plain.as_slice(), [Block::Paragraph(item)] if item.links.is_empty()));
+
+        // gpui would try to fetch a plain path over HTTP.
+        let source = ImageSource::from("file:///books/logo.png");
+        assert!(matches!(source, ImageSource::Resource(Resource::Embedded(_))));
+    }
+
+    #[test]
+    fn resolves_only_local_paths() {
+        let base = Path::new("/docs");
+        assert_eq!(local_path(base, "img/a.png#x"), Some(PathBuf::from("/docs/img/a.png")));
+        assert_eq!(local_path(base, "/abs/b.png"), Some(PathBuf::from("/abs/b.png")));
+        assert_eq!(local_path(base, "file:///abs/c.md"), Some(PathBuf::from("/abs/c.md")));
+        assert_eq!(local_path(base, "https://example.com/c.png"), None);
+        assert_eq!(local_path(base, "mailto:me@example.com"), None);
+        assert_eq!(local_path(base, "#section"), None);
+    }
+
+    #[test]
+    fn serves_images_with_escaped_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a <b> 100%.png");
+        fs::write(&path, "png").unwrap();
+        let markdown = image_markdown("logo", &path);
+        let url = markdown.split_once("](<").unwrap().1.strip_suffix(">)").unwrap();
+        assert!(!url.contains(['<', '>']));
+        assert_eq!(ImageFiles.load(url).unwrap().as_deref(), Some(&b"png"[..]));
+    }
+}
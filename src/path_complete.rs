@@ -0,0 +1,141 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+
+const MAX_COMPLETIONS: usize = 50;
+
+/// Expands a leading `~` and `$VAR` or `${VAR}` references the way a shell
+/// would, looking variables up with `var` (`env::var_os` outside of tests).
+/// Unknown variables are left as typed.
+pub fn expand(input: &str, var: impl Fn(&str) -> Option<OsString>) -> String {
+    let input = input.trim();
+    let home = match input.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', MAIN_SEPARATOR]) => {
+            var("HOME").map(|home| format!("{}{rest}", home.to_string_lossy()))
+        }
+        _ => None,
+    };
+    expand_vars(home.as_deref().unwrap_or(input), var)
+}
+
+fn expand_vars(input: &str, var: impl Fn(&str) -> Option<OsString>) -> String {
+    let mut expanded = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(ix) = rest.find('$') {
+        expanded.push_str(&rest[..ix]);
+        let after = &rest[ix + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        let reference = &rest[ix..ix + 1 + len];
+        match (!name.is_empty()).then(|| var(name)).flatten() {
+            Some(value) => expanded.push_str(&value.to_string_lossy()),
+            None => expanded.push_str(reference),
+        }
+        rest = &rest[ix + 1 + len..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Directories `input` could be completed to: the ones inside it when it
+/// ends with a separator, otherwise the ones next to it whose names start
+/// with what was typed. Hidden folders only show up once a `.` is typed.
+pub fn completions(input: &str, var: impl Fn(&str) -> Option<OsString>) -> Vec<PathBuf> {
+    let expanded = expand(input, var);
+    let (dir, prefix) = if expanded.ends_with(['/', MAIN_SEPARATOR]) {
+        (PathBuf::from(&expanded), String::new())
+    } else {
+        let path = Path::new(&expanded);
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => (parent.to_path_buf(), name.to_string_lossy().to_lowercase()),
+            _ => return Vec::new(),
+        }
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut dirs = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            name.starts_with(&prefix) && (!name.starts_with('.') || prefix.starts_with('.'))
+        })
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs.truncate(MAX_COMPLETIONS);
+    dirs
+}
+
+/// What tab turns `input` into: the only match, ready for its children to be
+/// typed, or as much as all matches have in common.
+pub fn complete(input: &str, completions: &[PathBuf], var: impl Fn(&str) -> Option<OsString>) -> Option<String> {
+    let names = completions
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    match names.as_slice() {
+        [] => None,
+        [only] => Some(format!("{only}{MAIN_SEPARATOR}")),
+        [first, rest @ ..] => {
+            let len = rest
+                .iter()
+                .fold(first.len(), |len, name| common_prefix_len(&first[..len], name));
+            // Matching ignores case, so the shared part may be shorter than
+            // what was typed.
+            (len >= expand(input, var).len()).then(|| first[..len].to_string())
+        }
+    }
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .take_while(|((_, a), b)| a == b)
+        .last()
+        .map_or(0, |((ix, c), _)| ix + c.len_utf8())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn expands_and_completes_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["Documents", "Downloads", "music", ".config"] {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        std::fs::write(dir.path().join("documents.txt"), "").unwrap();
+        let root = dir.path().display().to_string();
+        let vars = HashMap::from([("HOME", "/home/reader"), ("BOOKS", root.as_str())]);
+        let var = |name: &str| vars.get(name).map(OsString::from);
+
+        assert_eq!(expand("~/notes", var), "/home/reader/notes");
+        assert_eq!(expand("~reader", var), "~reader");
+        assert_eq!(expand("$BOOKS/music", var), format!("{root}/music"));
+        assert_eq!(expand("${BOOKS}/x", var), format!("{root}/x"));
+        assert_eq!(expand("$UNSET/x", var), "$UNSET/x");
+
+        let matches = completions("$BOOKS/do", var);
+        assert_eq!(matches, [dir.path().join("Documents"), dir.path().join("Downloads")]);
+        assert_eq!(complete("$BOOKS/do", &matches, var), Some(format!("{root}/Do")));
+        assert_eq!(completions(&format!("{root}/"), var).len(), 3);
+        assert_eq!(completions(&format!("{root}/.c"), var), [dir.path().join(".config")]);
+
+        let matches = completions(&format!("{root}/mu"), var);
+        assert_eq!(complete(&format!("{root}/mu"), &matches, var), Some(format!("{root}/music/")));
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::component::left_dock::{Item, SubItem};
+use crate::storage;
+
+const FILE_NAME: &str = "session.json";
+
+/// What the window looked like when it was last closed, restored on the
+/// next launch.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub window: Option<WindowState>,
+    pub tabs: Vec<TabState>,
+    pub active_tab: usize,
+    pub dual_pane: Option<DualPaneState>,
+    pub sidebar: SidebarState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindowState {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub maximized: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TabState {
+    pub dir: PathBuf,
+    pub selected: Option<PathBuf>,
+    pub expanded: Vec<PathBuf>,
+    pub tree_width: f32,
+}
+
+impl Default for TabState {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::new(),
+            selected: None,
+            expanded: Vec::new(),
+            tree_width: 300.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DualPaneState {
+    pub other: TabState,
+    pub other_focused: bool,
+    pub synced: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SidebarState {
+    pub item: Item,
+    pub subitem: Option<SubItem>,
+    pub collapsed: bool,
+    /// Docked on the right edge of the window instead of the left.
+    pub right: bool,
+}
+
+impl Default for SidebarState {
+    fn default() -> Self {
+        Self {
+            item: Item::Library,
+            subitem: Some(SubItem::Explorer),
+            collapsed: false,
+            right: false,
+        }
+    }
+}
+
+impl Session {
+    /// The saved session, or an empty one if there is none or it is
+    /// corrupt.
+    pub fn load(dir: &Path) -> Self {
+        storage::load_json(&dir.join(FILE_NAME))
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        storage::write_json(&dir.join(FILE_NAME), self, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn round_trips_and_survives_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Session::load(dir.path()), Session::default());
+
+        let session = Session {
+            window: Some(WindowState {
+                x: 10.0,
+                y: 20.0,
+                width: 1200.0,
+                height: 800.0,
+                maximized: false,
+            }),
+            tabs: vec![TabState {
+                dir: "/tmp".into(),
+                selected: Some("/tmp/a.txt".into()),
+                expanded: vec!["/tmp/src".into()],
+                tree_width: 280.0,
+            }],
+            active_tab: 0,
+            dual_pane: None,
+            sidebar: SidebarState {
+                item: Item::Library,
+                subitem: Some(SubItem::Tags),
+                collapsed: true,
+                right: false,
+            },
+        };
+        session.save(dir.path()).unwrap();
+        assert_eq!(Session::load(dir.path()), session);
+
+        // Fields added later fall back to their defaults.
+        fs::write(dir.path().join(FILE_NAME), r#"{"tabs": [{"dir": "/home"}]}"#).unwrap();
+        assert_eq!(Session::load(dir.path()).tabs[0].tree_width, 300.0);
+
+        fs::write(dir.path().join(FILE_NAME), "{\"tabs\": [").unwrap();
+        assert_eq!(Session::load(dir.path()), Session::default());
+        assert!(dir.path().join("session.json.corrupt").exists());
+    }
+}
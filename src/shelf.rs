@@ -1,213 +1,1050 @@
 use gpui::{
-    AppContext, Context, Entity, IntoElement, ParentElement, Render, SharedString, Styled, Window,
-    div, px, rgb, white,
+    actions, AnyElement, App, AppContext, Bounds, Context, Entity, InteractiveElement, IntoElement, KeyBinding, MouseButton,
+    NavigationDirection, ParentElement, Render, SharedString, StatefulInteractiveElement, Styled, Task, Window,
+    WindowBounds, div, point, prelude::FluentBuilder, px, size,
 };
-use std::fs;
-use std::path::PathBuf;
+use gpui_component::button::{Button, ButtonVariants};
+use gpui_component::{h_flex, ActiveTheme, Root, Disableable, Selectable, Side, Sizable};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::component::header::HeaderEvent;
-use crate::component::content_viewer::ContentViewer;
-use crate::component::header::Header;
-use crate::component::DirEntry;
-use crate::component::file_tree::FileTree;
+use crate::compare;
+use crate::component::categories_view::{CategoriesView, CategoriesViewEvent};
+use crate::component::library_bar::{LibraryBar, LibraryBarEvent};
+use crate::component::left_dock::{Item, LeftDock, LeftDockEvent, SubItem};
+use crate::component::tags_view::{TagsView, TagsViewEvent};
+use crate::component::trash_view::{TrashView, TrashViewEvent};
+use crate::component::workflow_view::{WorkflowView, WorkflowViewEvent};
+use crate::config::Config;
+use crate::file_ops::{Clipboard, Direction, Journal, Operation, Progress};
+use crate::library::Library;
+use crate::session::{DualPaneState, Session, TabState, WindowState};
+use crate::storage::{self, BaseDir};
+use crate::tags::TagDb;
+use crate::watcher::FsChange;
+
+mod tab;
+
+use tab::{Tab, TabEvent};
+
+const CONTEXT: &str = "Shelf";
+
+actions!(
+    toshokan,
+    [
+        NewFile, NewFolder, Duplicate, CopyItem, CutItem, Paste, Delete, Undo, Redo, GoBack, GoForward, NewTab,
+        CloseTab, NextTab, PreviousTab, ToggleDualPane, SwitchPane, CopyToOtherPane, MoveToOtherPane,
+        ToggleSyncNavigation, ComparePanes, ToggleSidebar
+    ]
+);
+
+pub(crate) fn init(cx: &mut App) {
+    cx.bind_keys([
+        KeyBinding::new("cmd-n", NewFile, Some(CONTEXT)),
+        KeyBinding::new("cmd-shift-n", NewFolder, Some(CONTEXT)),
+        KeyBinding::new("cmd-d", Duplicate, Some(CONTEXT)),
+        KeyBinding::new("cmd-c", CopyItem, Some(CONTEXT)),
+        KeyBinding::new("cmd-x", CutItem, Some(CONTEXT)),
+        KeyBinding::new("cmd-v", Paste, Some(CONTEXT)),
+        KeyBinding::new("cmd-backspace", Delete, Some(CONTEXT)),
+        KeyBinding::new("delete", Delete, Some(CONTEXT)),
+        KeyBinding::new("cmd-z", Undo, Some(CONTEXT)),
+        KeyBinding::new("cmd-shift-z", Redo, Some(CONTEXT)),
+        KeyBinding::new("alt-left", GoBack, Some(CONTEXT)),
+        KeyBinding::new("alt-right", GoForward, Some(CONTEXT)),
+        KeyBinding::new("cmd-t", NewTab, Some(CONTEXT)),
+        KeyBinding::new("cmd-w", CloseTab, Some(CONTEXT)),
+        KeyBinding::new("ctrl-tab", NextTab, Some(CONTEXT)),
+        KeyBinding::new("ctrl-shift-tab", PreviousTab, Some(CONTEXT)),
+        KeyBinding::new("cmd-shift-]", NextTab, Some(CONTEXT)),
+        KeyBinding::new("cmd-shift-[", PreviousTab, Some(CONTEXT)),
+        KeyBinding::new("cmd-\\", ToggleDualPane, Some(CONTEXT)),
+        KeyBinding::new("ctrl-\\", SwitchPane, Some(CONTEXT)),
+        KeyBinding::new("f5", CopyToOtherPane, Some(CONTEXT)),
+        KeyBinding::new("f6", MoveToOtherPane, Some(CONTEXT)),
+        KeyBinding::new("ctrl-x s", ToggleSyncNavigation, Some(CONTEXT)),
+        KeyBinding::new("ctrl-x d", ComparePanes, Some(CONTEXT)),
+        KeyBinding::new("cmd-b", ToggleSidebar, Some(CONTEXT)),
+    ])
+}
 
 pub struct Shelf {
-    current_path: PathBuf,
-    entries: Vec<DirEntry>,
-    header: Entity<Header>,
-    file_tree: Entity<FileTree>,
-    selected_item: Option<DirEntry>,
-    content_viewer: Entity<ContentViewer>,
+    tabs: Vec<Entity<Tab>>,
+    active_tab: usize,
+    dual: Option<DualPane>,
+    left_dock: Entity<LeftDock>,
+    active_item: Item,
+    active_subitem: Option<SubItem>,
+    trash_view: Entity<TrashView>,
+    tags: Entity<TagDb>,
+    tags_view: Entity<TagsView>,
+    categories_view: Entity<CategoriesView>,
+    library: Entity<Library>,
+    library_bar: Entity<LibraryBar>,
+    workflow_view: Entity<WorkflowView>,
+    journal: Journal,
+    clipboard: Option<Clipboard>,
+    file_op: Option<(String, Arc<Progress>)>,
+    status: Option<SharedString>,
+    /// The files of the tags selected in the Tags view, which the trees
+    /// are narrowed to.
+    tag_filter: Option<HashSet<PathBuf>>,
+    /// Last known window bounds, saved with the session.
+    window_state: Option<WindowState>,
+}
+
+/// The right-hand pane of the side-by-side mode; the left one is the
+/// active tab.
+struct DualPane {
+    other: Entity<Tab>,
+    /// Whether `other`, rather than the active tab, has focus.
+    other_focused: bool,
+    /// Navigating the focused pane takes the other one along.
+    synced: bool,
+    /// The panes show a comparison, which is redone as their folders
+    /// change.
+    compared: bool,
+    _compare_task: Option<Task<()>>,
+}
+
+/// A tab being dragged to a new place in the tab bar.
+#[derive(Clone)]
+struct DraggedTab {
+    ix: usize,
+    title: SharedString,
+}
+
+impl Render for DraggedTab {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .px_3()
+            .py_1()
+            .bg(cx.theme().background)
+            .border_1()
+            .border_color(cx.theme().border)
+            .rounded(cx.theme().radius)
+            .shadow_md()
+            .child(self.title.clone())
+    }
 }
 
 impl Shelf {
-    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
-        let current_path = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
-        let entries = Vec::new();
-
-        let header = cx.new(|cx| Header::view(window, cx, None));
-        let file_tree = FileTree::view(window, cx);
-        let content_viewer = cx.new(|cx| ContentViewer::new(window, cx));
-
-        // Set up observer for file selection changes
-        let file_tree_weak = file_tree.downgrade();
-        cx.observe(&file_tree, move |this: &mut Shelf, _file_tree, cx| {
-            if let Some(file_tree) = file_tree_weak.upgrade() {
-                if let Some(path) = file_tree.read(cx).selected_path() {
-                    let is_dir = path.is_dir();
-                    let name = path
-                        .file_name()
-                        .map(|n| n.to_string_lossy().to_string())
-                        .unwrap_or_default();
-                    let entry = DirEntry {
-                        name: SharedString::from(name),
-                        is_dir,
-                        path,
-                    };
-                    this.handle_item_click(entry, cx);
+    pub fn new(session: Session, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let left_dock = LeftDock::view(session.sidebar, window, cx);
+        let trash_view = cx.new(|cx| TrashView::new(window, cx));
+        let tags = cx.new(|_| storage::dir(BaseDir::Data).map(|dir| TagDb::load(&dir)).unwrap_or_default());
+        let tags_view = cx.new(|cx| TagsView::new(tags.clone(), window, cx));
+        let (config, config_error) = match storage::dir(BaseDir::Config).map(|dir| Config::load(&dir)) {
+            Some(Ok(config)) => (config, None),
+            Some(Err(err)) => (Config::default(), Some(SharedString::from(err))),
+            None => (Config::default(), None),
+        };
+        let library = cx.new(|cx| Library::new(config.roots.clone(), cx));
+        let library_bar = cx.new(|cx| LibraryBar::new(library.clone(), window, cx));
+        let categories_view = cx.new(|cx| CategoriesView::new(&config, config_error.clone(), library.clone(), tags.clone(), window, cx));
+        let workflow_view = cx.new(|cx| WorkflowView::new(&config, config_error.clone(), window, cx));
+
+        cx.subscribe(
+            &left_dock,
+            move |this: &mut Shelf, _dock, event: &LeftDockEvent, cx| match event {
+                LeftDockEvent::Select(item, subitem) => {
+                    this.active_item = *item;
+                    this.active_subitem = *subitem;
+                    if *subitem == Some(SubItem::Trash) {
+                        this.trash_view.update(cx, |view, cx| view.refresh(cx));
+                    }
+                    cx.notify();
                 }
-            }
-        })
+                LeftDockEvent::Moved => cx.notify(),
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &trash_view,
+            move |this: &mut Shelf, _view, event: &TrashViewEvent, cx| match event {
+                TrashViewEvent::Restored(path) => {
+                    for tab in this.all_tabs() {
+                        tab.update(cx, |tab, cx| tab.apply_fs_changes(vec![FsChange::Added(path.clone())], cx));
+                    }
+                }
+            },
+        )
         .detach();
 
-        // Set up observer for header navigation events
         cx.subscribe(
-            &header,
-            move |this: &mut Shelf, _header, event: &HeaderEvent, cx| match event {
-                HeaderEvent::NavigateTo(path) => {
-                    this.navigate_to_directory(path.clone(), cx);
+            &tags_view,
+            move |this: &mut Shelf, _view, event: &TagsViewEvent, cx| match event {
+                TagsViewEvent::Open(path) => this.reveal(path.clone(), cx),
+                TagsViewEvent::Filter(filter) => {
+                    this.tag_filter = filter.clone();
+                    for tab in this.all_tabs() {
+                        tab.update(cx, |tab, cx| tab.set_tag_filter(filter.clone(), cx));
+                    }
+                }
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &categories_view,
+            move |this: &mut Shelf, _view, event: &CategoriesViewEvent, cx| match event {
+                CategoriesViewEvent::Open(path) => this.reveal(path.clone(), cx),
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &library_bar,
+            move |this: &mut Shelf, _bar, event: &LibraryBarEvent, cx| match event {
+                LibraryBarEvent::Open(path) => {
+                    this.focused().update(cx, |tab, cx| tab.navigate_to_directory(path.clone(), cx));
+                }
+                LibraryBarEvent::AddCurrentFolder => {
+                    let path = this.focused().read(cx).current_path().to_path_buf();
+                    if let Err(err) = this.library.update(cx, |library, cx| library.add_root(&path, cx)) {
+                        this.set_status(err, cx);
+                    }
+                }
+                LibraryBarEvent::Status(status) => this.set_status(status.clone(), cx),
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &workflow_view,
+            move |this: &mut Shelf, view, event: &WorkflowViewEvent, cx| match event {
+                WorkflowViewEvent::Run(ix) => {
+                    let tab = this.focused().read(cx);
+                    let target = tab.target_path(cx).unwrap_or_else(|| tab.current_path().to_path_buf());
+                    view.update(cx, |view, cx| view.run(*ix, target, cx));
                 }
             },
         )
         .detach();
 
         let mut this = Self {
-            current_path,
-            entries,
-            header,
-            file_tree,
-            selected_item: None,
-            content_viewer,
+            tabs: Vec::new(),
+            active_tab: 0,
+            dual: None,
+            left_dock,
+            active_item: session.sidebar.item,
+            active_subitem: session.sidebar.subitem,
+            trash_view,
+            tags,
+            tags_view,
+            categories_view,
+            library,
+            library_bar,
+            workflow_view,
+            journal: Journal::default(),
+            clipboard: None,
+            file_op: None,
+            status: config_error,
+            tag_filter: None,
+            window_state: session.window,
         };
+        this.restore(session, window, cx);
 
-        this.load_directory_async(this.current_path.clone(), cx);
+        cx.observe_window_bounds(window, |this, window, _| {
+            this.window_state = Some(window_state(window.window_bounds()));
+        })
+        .detach();
+        cx.on_app_quit(|this, cx| {
+            this.save_session(cx);
+            async {}
+        })
+        .detach();
+        let shelf = cx.entity().downgrade();
+        window.on_window_should_close(cx, move |_, cx| {
+            if let Some(shelf) = shelf.upgrade() {
+                shelf.read(cx).save_session(cx);
+            }
+            true
+        });
         this
     }
-    fn read_directory(path: &PathBuf) -> Vec<DirEntry> {
-        let mut entries = Vec::new();
 
-        if let Ok(read_dir) = fs::read_dir(path) {
-            for entry in read_dir.flatten() {
-                let path = entry.path();
-                let name = entry.file_name().to_string_lossy().to_string();
-                let is_dir = path.is_dir();
+    /// Reopens the session's tabs, leaving out folders that are gone.
+    fn restore(&mut self, session: Session, window: &mut Window, cx: &mut Context<Self>) {
+        let active = session.tabs.get(session.active_tab).map(|tab| tab.dir.clone());
+        for state in session.tabs.into_iter().filter(|tab| tab.dir.is_dir()) {
+            let tab = self.new_tab(state, window, cx);
+            self.tabs.push(tab);
+        }
+        if self.tabs.is_empty() {
+            let dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+            let tab = self.new_tab(TabState { dir, ..TabState::default() }, window, cx);
+            self.tabs.push(tab);
+        }
+        self.active_tab = active
+            .and_then(|dir| self.tabs.iter().position(|tab| tab.read(cx).current_path() == dir))
+            .unwrap_or(0);
 
-                entries.push(DirEntry {
-                    name: SharedString::from(name),
-                    is_dir,
-                    path,
-                });
+        if let Some(dual) = session.dual_pane.filter(|dual| dual.other.dir.is_dir()) {
+            let other = self.new_tab(dual.other, window, cx);
+            self.dual = Some(DualPane {
+                other,
+                other_focused: dual.other_focused,
+                synced: dual.synced,
+                compared: false,
+                _compare_task: None,
+            });
+            for tab in self.all_tabs() {
+                tab.update(cx, |tab, cx| tab.set_compact(true, cx));
             }
         }
+    }
 
-        // Sort: directories first, then files, both alphabetically
-        entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.cmp(&b.name),
-        });
+    fn session(&self, cx: &App) -> Session {
+        Session {
+            window: self.window_state,
+            tabs: self.tabs.iter().map(|tab| tab.read(cx).state(cx)).collect(),
+            active_tab: self.active_tab,
+            sidebar: self.left_dock.read(cx).state(),
+            dual_pane: self.dual.as_ref().map(|dual| DualPaneState {
+                other: dual.other.read(cx).state(cx),
+                other_focused: dual.other_focused,
+                synced: dual.synced,
+            }),
+        }
+    }
 
-        entries
-    }
-
-    pub fn handle_item_click(&mut self, entry: DirEntry, cx: &mut Context<Self>) {
-        if entry.is_dir {
-            // Change directory
-            self.navigate_to_directory(entry.path.clone(), cx);
-        } else {
-            let path = entry.path.clone();
-            let name = entry.name.clone();
-            self.selected_item = Some(entry);
-            if ContentViewer::is_image_name(&name) {
-                let _ = self.content_viewer.update(cx, |cv, cx| {
-                    cv.set_image_path(Some(name.clone()), path.clone(), cx);
-                });
-            } else {
-                let view = self.content_viewer.downgrade();
-                let task = cx.background_spawn(async move {
-                    fs::read_to_string(&path)
-                });
-                cx.spawn(async move |_, cx| {
-                    let content = task.await.ok();
-                    if let Some(cv) = view.upgrade() {
-                        let _ = cv.update(cx, |cv, cx| {
-                            cv.set_text(Some(name.clone()), content, cx);
-                        });
-                    }
-                }).detach();
+    fn save_session(&self, cx: &App) {
+        let Some(dir) = storage::dir(BaseDir::State) else {
+            return;
+        };
+        if let Err(err) = self.session(cx).save(&dir) {
+            eprintln!("Failed to save session to {}: {err}", dir.display());
+        }
+    }
+
+    fn active(&self) -> &Entity<Tab> {
+        &self.tabs[self.active_tab]
+    }
+
+    /// The pane file operations and navigation apply to.
+    fn focused(&self) -> &Entity<Tab> {
+        match &self.dual {
+            Some(dual) if dual.other_focused => &dual.other,
+            _ => self.active(),
+        }
+    }
+
+    /// The pane facing the focused one, in side-by-side mode.
+    fn opposite(&self) -> Option<&Entity<Tab>> {
+        let dual = self.dual.as_ref()?;
+        Some(if dual.other_focused { self.active() } else { &dual.other })
+    }
+
+    fn all_tabs(&self) -> impl Iterator<Item = &Entity<Tab>> {
+        self.tabs.iter().chain(self.dual.as_ref().map(|dual| &dual.other))
+    }
+
+    fn new_tab(&self, state: TabState, window: &mut Window, cx: &mut Context<Self>) -> Entity<Tab> {
+        let compact = self.dual.is_some();
+        let tab = cx.new(|cx| {
+            let mut tab = Tab::new(state, self.tags.clone(), window, cx);
+            tab.set_compact(compact, cx);
+            tab.set_tag_filter(self.tag_filter.clone(), cx);
+            tab
+        });
+        cx.subscribe_in(&tab, window, |this, tab, event: &TabEvent, window, cx| match event {
+            TabEvent::Status(status) => this.set_status(status.clone(), cx),
+            TabEvent::OpenInNewTab(path) => this.open_tab(path.clone(), window, cx),
+            TabEvent::Navigated { from, to } => {
+                // The tree drops its marks when it moves to another folder.
+                if this.is_pane(tab)
+                    && let Some(dual) = &mut this.dual
+                {
+                    dual.compared = false;
+                }
+                this.follow_navigation(tab, from, to, cx);
             }
+            TabEvent::Changed => {
+                if this.is_pane(tab) && this.dual.as_ref().is_some_and(|dual| dual.compared) {
+                    this.compare_panes(false, cx);
+                }
+            }
+            TabEvent::ClearTagFilter => this.tags_view.update(cx, |view, cx| view.clear_selection(cx)),
+            TabEvent::Reveal(path) => this.reveal(path.clone(), cx),
+        })
+        .detach();
+        tab
+    }
+
+    /// Opens `path` in a new tab right after the active one.
+    fn open_tab(&mut self, path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        let tab = self.new_tab(TabState { dir: path, ..TabState::default() }, window, cx);
+        let ix = if self.tabs.is_empty() { 0 } else { self.active_tab + 1 };
+        self.tabs.insert(ix, tab);
+        self.activate_tab(ix, cx);
+    }
+
+    fn activate_tab(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if ix < self.tabs.len() {
+            self.active_tab = ix;
+            cx.notify();
+        }
+    }
+
+    /// The last tab stays open; there is always a folder to show.
+    fn close_tab(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if self.tabs.len() == 1 || ix >= self.tabs.len() {
+            return;
+        }
+        self.tabs.remove(ix);
+        if ix < self.active_tab || self.active_tab == self.tabs.len() {
+            self.active_tab -= 1;
+        }
+        cx.notify();
+    }
+
+    fn move_tab(&mut self, from: usize, to: usize, cx: &mut Context<Self>) {
+        if from == to || from >= self.tabs.len() || to >= self.tabs.len() {
+            return;
+        }
+        let active = self.active().clone();
+        let tab = self.tabs.remove(from);
+        self.tabs.insert(to, tab);
+        self.active_tab = self.tabs.iter().position(|tab| *tab == active).unwrap_or(to);
+        cx.notify();
+    }
+
+    fn on_action_new_tab(&mut self, _: &NewTab, window: &mut Window, cx: &mut Context<Self>) {
+        let path = self.active().read(cx).current_path().to_path_buf();
+        self.open_tab(path, window, cx);
+    }
+
+    fn on_action_close_tab(&mut self, _: &CloseTab, _: &mut Window, cx: &mut Context<Self>) {
+        self.close_tab(self.active_tab, cx);
+    }
+
+    fn on_action_next_tab(&mut self, _: &NextTab, _: &mut Window, cx: &mut Context<Self>) {
+        self.activate_tab((self.active_tab + 1) % self.tabs.len(), cx);
+    }
+
+    fn on_action_previous_tab(&mut self, _: &PreviousTab, _: &mut Window, cx: &mut Context<Self>) {
+        self.activate_tab((self.active_tab + self.tabs.len() - 1) % self.tabs.len(), cx);
+    }
+
+    fn toggle_dual_pane(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.dual.take().is_none() {
+            let dir = self.active().read(cx).current_path().to_path_buf();
+            let other = self.new_tab(TabState { dir, ..TabState::default() }, window, cx);
+            self.dual = Some(DualPane {
+                other,
+                other_focused: false,
+                synced: false,
+                compared: false,
+                _compare_task: None,
+            });
         }
+        let compact = self.dual.is_some();
+        for tab in self.all_tabs() {
+            tab.update(cx, |tab, cx| tab.set_compact(compact, cx));
+        }
+        cx.notify();
     }
 
-    pub fn navigate_to_directory(&mut self, path: PathBuf, cx: &mut Context<Self>) {
-        self.current_path = path.clone();
-        self.load_directory_async(self.current_path.clone(), cx);
-        self.header.update(cx, |header, cx| {
-            header.set_path(self.current_path.clone());
+    fn focus_pane(&mut self, other: bool, cx: &mut Context<Self>) {
+        if let Some(dual) = &mut self.dual
+            && dual.other_focused != other
+        {
+            dual.other_focused = other;
             cx.notify();
+        }
+    }
+
+    /// Copies or moves the focused pane's selection into the folder shown
+    /// by the other pane.
+    fn transfer_to_opposite(&mut self, cut: bool, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(opposite) = self.opposite() else {
+            self.set_status("Open a second pane first", cx);
+            return;
+        };
+        let dir = opposite.read(cx).current_path().to_path_buf();
+        let Some(path) = self.focused().read(cx).target_path(cx) else {
+            return;
+        };
+        let clipboard = if cut { Clipboard::Cut(path) } else { Clipboard::Copy(path) };
+        match Operation::paste(&clipboard, &dir) {
+            Ok(Some(op)) => self.run_file_op(op, Direction::Apply, false, window, cx),
+            Ok(None) => {}
+            Err(err) => self.set_status(err, cx),
+        }
+    }
+
+    fn toggle_sync_navigation(&mut self, cx: &mut Context<Self>) {
+        if let Some(dual) = &mut self.dual {
+            dual.synced = !dual.synced;
+            cx.notify();
+        }
+    }
+
+    /// With synced navigation, takes the other pane along when the focused
+    /// one moves.
+    fn follow_navigation(&mut self, tab: &Entity<Tab>, from: &Path, to: &Path, cx: &mut Context<Self>) {
+        if !self.dual.as_ref().is_some_and(|dual| dual.synced) || tab != self.focused() {
+            return;
+        }
+        let Some(opposite) = self.opposite().cloned() else {
+            return;
+        };
+        let current = opposite.read(cx).current_path().to_path_buf();
+        match synced_dir(from, to, &current) {
+            Some(dir) => opposite.update(cx, |tab, cx| tab.navigate_to_directory(dir, cx)),
+            None => self.set_status(format!("No matching folder in {}", current.display()), cx),
+        }
+    }
+
+    fn is_pane(&self, tab: &Entity<Tab>) -> bool {
+        self.dual.as_ref().is_some_and(|dual| tab == self.active() || *tab == dual.other)
+    }
+
+    /// Marks the entries that differ between the two panes' folders, saying
+    /// how many there are if `announce`.
+    fn compare_panes(&mut self, announce: bool, cx: &mut Context<Self>) {
+        let Some(dual) = &self.dual else {
+            return;
+        };
+        let left = self.active().clone();
+        let right = dual.other.clone();
+        let task = cx.background_spawn({
+            let left = left.read(cx).current_path().to_path_buf();
+            let right = right.read(cx).current_path().to_path_buf();
+            async move { compare::differing(&left, &right) }
         });
-        self.file_tree.update(cx, |tree, cx| {
-            tree.set_root_path(self.current_path.clone(), cx);
-        });
-        self.selected_item = None;
-        let _ = self.content_viewer.update(cx, |cv, cx| {
-            cv.set_text(None, None, cx);
+        let compare_task = cx.spawn(async move |this, cx| {
+            let result = task.await;
+            if let Err(err) = this.update(cx, |this, cx| match result {
+                Ok((left_differing, right_differing)) => {
+                    if announce {
+                        this.set_status(
+                            format!(
+                                "{} differing entries on the left, {} on the right",
+                                left_differing.len(),
+                                right_differing.len()
+                            ),
+                            cx,
+                        );
+                    }
+                    left.update(cx, |tab, cx| tab.set_highlighted(left_differing, cx));
+                    right.update(cx, |tab, cx| tab.set_highlighted(right_differing, cx));
+                }
+                Err(err) => this.set_status(format!("Compare failed: {err}"), cx),
+            }) {
+                eprintln!("Compare directories failed: {err}");
+            }
         });
-        cx.notify();
+        if let Some(dual) = &mut self.dual {
+            dual.compared = true;
+            dual._compare_task = Some(compare_task);
+        }
+    }
+
+    fn on_action_toggle_dual_pane(&mut self, _: &ToggleDualPane, window: &mut Window, cx: &mut Context<Self>) {
+        self.toggle_dual_pane(window, cx);
+    }
+
+    fn on_action_switch_pane(&mut self, _: &SwitchPane, _: &mut Window, cx: &mut Context<Self>) {
+        let other = self.dual.as_ref().is_some_and(|dual| !dual.other_focused);
+        self.focus_pane(other, cx);
+    }
+
+    fn on_action_copy_to_other_pane(&mut self, _: &CopyToOtherPane, window: &mut Window, cx: &mut Context<Self>) {
+        self.transfer_to_opposite(false, window, cx);
+    }
+
+    fn on_action_move_to_other_pane(&mut self, _: &MoveToOtherPane, window: &mut Window, cx: &mut Context<Self>) {
+        self.transfer_to_opposite(true, window, cx);
+    }
+
+    fn on_action_toggle_sync_navigation(&mut self, _: &ToggleSyncNavigation, _: &mut Window, cx: &mut Context<Self>) {
+        self.toggle_sync_navigation(cx);
+    }
+
+    fn on_action_compare_panes(&mut self, _: &ComparePanes, _: &mut Window, cx: &mut Context<Self>) {
+        self.compare_panes(true, cx);
+    }
+
+    fn on_action_toggle_sidebar(&mut self, _: &ToggleSidebar, _: &mut Window, cx: &mut Context<Self>) {
+        self.left_dock.update(cx, |dock, cx| dock.toggle_collapsed(cx));
+    }
+
+    /// Switches to the explorer with `path` selected in the focused pane.
+    fn reveal(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        self.focused().update(cx, |tab, cx| tab.reveal(path, cx));
+        self.left_dock
+            .update(cx, |dock, cx| dock.select(Item::Library, Some(SubItem::Explorer), cx));
+    }
+
+    fn go_back(&mut self, steps: usize, cx: &mut Context<Self>) {
+        self.focused().update(cx, |tab, cx| tab.go_back(steps, cx));
+    }
+
+    fn go_forward(&mut self, steps: usize, cx: &mut Context<Self>) {
+        self.focused().update(cx, |tab, cx| tab.go_forward(steps, cx));
     }
 
-    fn load_directory_async(&mut self, path: PathBuf, cx: &mut Context<Self>) {
-        let view = cx.entity();
-        let task = cx.background_spawn(async move {
-            let mut entries = Vec::new();
-            if let Ok(read_dir) = fs::read_dir(&path) {
-                for entry in read_dir.flatten() {
-                    let path = entry.path();
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    let is_dir = path.is_dir();
-                    entries.push(DirEntry {
-                        name: SharedString::from(name),
-                        is_dir,
-                        path,
-                    });
+    fn on_action_go_back(&mut self, _: &GoBack, _: &mut Window, cx: &mut Context<Self>) {
+        self.go_back(1, cx);
+    }
+
+    fn on_action_go_forward(&mut self, _: &GoForward, _: &mut Window, cx: &mut Context<Self>) {
+        self.go_forward(1, cx);
+    }
+
+    fn on_action_new_file(&mut self, _: &NewFile, window: &mut Window, cx: &mut Context<Self>) {
+        let op = Operation::new_entry(&self.focused().read(cx).target_dir(cx), false);
+        self.run_file_op(op, Direction::Apply, false, window, cx);
+    }
+
+    fn on_action_new_folder(&mut self, _: &NewFolder, window: &mut Window, cx: &mut Context<Self>) {
+        let op = Operation::new_entry(&self.focused().read(cx).target_dir(cx), true);
+        self.run_file_op(op, Direction::Apply, false, window, cx);
+    }
+
+    fn on_action_duplicate(&mut self, _: &Duplicate, window: &mut Window, cx: &mut Context<Self>) {
+        let target = self.focused().read(cx).target_path(cx);
+        if let Some(op) = target.as_deref().and_then(Operation::duplicate) {
+            self.run_file_op(op, Direction::Apply, false, window, cx);
+        }
+    }
+
+    fn on_action_copy(&mut self, _: &CopyItem, _: &mut Window, cx: &mut Context<Self>) {
+        if let Some(path) = self.focused().read(cx).target_path(cx) {
+            self.set_status(format!("Copied {}", path.display()), cx);
+            self.clipboard = Some(Clipboard::Copy(path));
+        }
+    }
+
+    fn on_action_cut(&mut self, _: &CutItem, _: &mut Window, cx: &mut Context<Self>) {
+        if let Some(path) = self.focused().read(cx).target_path(cx) {
+            self.set_status(format!("Cut {}", path.display()), cx);
+            self.clipboard = Some(Clipboard::Cut(path));
+        }
+    }
+
+    fn on_action_paste(&mut self, _: &Paste, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(clipboard) = self.clipboard.clone() else {
+            return;
+        };
+        match Operation::paste(&clipboard, &self.focused().read(cx).target_dir(cx)) {
+            Ok(Some(op)) => self.run_file_op(op, Direction::Apply, false, window, cx),
+            Ok(None) => {}
+            Err(err) => self.set_status(err, cx),
+        }
+    }
+
+    fn on_action_delete(&mut self, _: &Delete, window: &mut Window, cx: &mut Context<Self>) {
+        // Reserving a trash entry writes its info file, so don't start one
+        // that would be refused as busy.
+        if self.file_op.is_some() {
+            return;
+        }
+        let Some(path) = self.focused().read(cx).target_path(cx) else {
+            return;
+        };
+        match Operation::delete(&path) {
+            Ok(op) => self.run_file_op(op, Direction::Apply, false, window, cx),
+            Err(err) => self.set_status(format!("Move to trash failed: {err}"), cx),
+        }
+    }
+
+    fn on_action_undo(&mut self, _: &Undo, window: &mut Window, cx: &mut Context<Self>) {
+        self.replay_file_op(Direction::Revert, window, cx);
+    }
+
+    fn on_action_redo(&mut self, _: &Redo, window: &mut Window, cx: &mut Context<Self>) {
+        self.replay_file_op(Direction::Apply, window, cx);
+    }
+
+    fn replay_file_op(&mut self, direction: Direction, window: &mut Window, cx: &mut Context<Self>) {
+        if self.file_op.is_some() {
+            return;
+        }
+        match self.journal.take(direction) {
+            Some(op) => self.run_file_op(op, direction, true, window, cx),
+            None => self.set_status(
+                match direction {
+                    Direction::Apply => "Nothing to redo",
+                    Direction::Revert => "Nothing to undo",
+                },
+                cx,
+            ),
+        }
+    }
+
+    /// Runs `op` on the background executor. Only one operation runs at a
+    /// time so the journal order always matches what happened on disk.
+    /// `replayed` operations come from the journal (undo/redo) rather than
+    /// being new user actions.
+    fn run_file_op(
+        &mut self,
+        op: Operation,
+        direction: Direction,
+        replayed: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some((description, _)) = &self.file_op {
+            let status = format!("Busy: {description}");
+            self.set_status(status, cx);
+            return;
+        }
+
+        let progress = Arc::new(Progress::default());
+        let description = op.describe(direction);
+        let origin = self.focused().clone();
+        self.file_op = Some((description.clone(), progress.clone()));
+        self.status = None;
+        cx.notify();
+
+        let task = cx.background_spawn({
+            let op = op.clone();
+            let progress = progress.clone();
+            async move { op.run(direction, &progress) }
+        });
+        cx.spawn_in(window, async move |this, cx| {
+            while !progress.is_finished() {
+                cx.background_executor()
+                    .timer(Duration::from_millis(100))
+                    .await;
+                if let Err(err) = this.update(cx, |_, cx| cx.notify()) {
+                    eprintln!("File operation progress update failed: {err}");
                 }
             }
-            entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.name.cmp(&b.name),
-            });
-            entries
-        });
-        cx.spawn(async move |_, cx| {
-            let entries = task.await;
-            if let Err(err) = view.update(cx, |this, cx| {
-                this.entries = entries;
-                cx.notify();
+            let result = task.await;
+            if let Err(err) = this.update_in(cx, |this, window, cx| {
+                this.file_op = None;
+                let changes = match result {
+                    Ok(changes) => changes,
+                    Err(err) => {
+                        if replayed {
+                            this.journal.restore(op, direction, false);
+                        }
+                        this.set_status(format!("{description} failed: {err}"), cx);
+                        return;
+                    }
+                };
+
+                let fs_changes = match (changes.removed.as_slice(), changes.added.as_slice()) {
+                    ([from], [to]) => vec![FsChange::Renamed(from.clone(), to.clone())],
+                    (removed, added) => removed
+                        .iter()
+                        .map(|path| FsChange::Removed(path.clone()))
+                        .chain(added.iter().map(|path| FsChange::Added(path.clone())))
+                        .collect(),
+                };
+                for tab in this.all_tabs() {
+                    tab.update(cx, |tab, cx| tab.apply_fs_changes(fs_changes.clone(), cx));
+                }
+                // Freshly created entries go straight into rename so they can be named.
+                if direction == Direction::Apply
+                    && let Operation::Create { path, .. } = &op
+                {
+                    origin.update(cx, |tab, cx| tab.start_rename(path, window, cx));
+                }
+                if matches!(op, Operation::Delete { .. }) {
+                    this.trash_view.update(cx, |view, cx| view.refresh(cx));
+                }
+                // A cut can only be pasted once; the source is gone now that
+                // the move went through.
+                if let Operation::Move { from, .. } = &op
+                    && direction == Direction::Apply
+                    && this.clipboard.as_ref() == Some(&Clipboard::Cut(from.clone()))
+                {
+                    this.clipboard = None;
+                }
+                if replayed {
+                    this.journal.restore(op, direction, true);
+                } else {
+                    this.journal.record(op);
+                }
+                this.set_status(description, cx);
             }) {
-                eprintln!("Update entries failed: {err}");
+                eprintln!("File operation update failed: {err}");
             }
         })
         .detach();
     }
+
+    /// The view picked in the sidebar.
+    fn render_main(&self, cx: &mut Context<Self>) -> AnyElement {
+        match (self.active_item, self.active_subitem) {
+            (_, Some(SubItem::Trash)) => self.trash_view.clone().into_any_element(),
+            (_, Some(SubItem::Categories)) => self.categories_view.clone().into_any_element(),
+            (_, Some(SubItem::Tags)) => self.tags_view.clone().into_any_element(),
+            (Item::Workflow, _) => self.workflow_view.clone().into_any_element(),
+            _ => div()
+                .flex()
+                .flex_col()
+                .size_full()
+                .child(self.library_bar.clone())
+                .child(self.render_tab_bar(cx))
+                .map(|this| match &self.dual {
+                    Some(dual) => this.child(self.render_dual_toolbar(dual, cx)).child(
+                        h_flex()
+                            .flex_1()
+                            .overflow_hidden()
+                            .child(self.render_pane(self.active().clone(), false, !dual.other_focused, cx))
+                            .child(self.render_pane(dual.other.clone(), true, dual.other_focused, cx)),
+                    ),
+                    None => this.child(div().flex_1().overflow_hidden().child(self.active().clone())),
+                })
+                .into_any_element(),
+        }
+    }
+
+    fn render_tab_bar(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let tabs = self.tabs.iter().enumerate().map(|(ix, tab)| {
+            let title = tab.read(cx).title();
+            let active = ix == self.active_tab;
+            h_flex()
+                .id(("tab", ix))
+                .gap_1()
+                .pl_3()
+                .pr_1()
+                .py_1()
+                .max_w(px(200.0))
+                .border_r_1()
+                .border_color(cx.theme().border)
+                .when(active, |this| this.bg(cx.theme().muted))
+                .when(!active, |this| this.text_color(cx.theme().muted_foreground))
+                .cursor_pointer()
+                .on_mouse_down(
+                    MouseButton::Left,
+                    cx.listener(move |this, _, _, cx| this.activate_tab(ix, cx)),
+                )
+                .on_mouse_down(
+                    MouseButton::Middle,
+                    cx.listener(move |this, _, _, cx| this.close_tab(ix, cx)),
+                )
+                .on_drag(DraggedTab { ix, title: title.clone() }, |tab, _, _, cx| {
+                    cx.new(|_| tab.clone())
+                })
+                .drag_over::<DraggedTab>(|style, _, _, cx| style.bg(cx.theme().accent))
+                .on_drop(cx.listener(move |this, dragged: &DraggedTab, _, cx| {
+                    this.move_tab(dragged.ix, ix, cx);
+                }))
+                .child(div().flex_1().truncate().child(title))
+                .child(
+                    Button::new(("close-tab", ix))
+                        .label("×")
+                        .xsmall()
+                        .ghost()
+                        .disabled(self.tabs.len() == 1)
+                        .on_click(cx.listener(move |this, _, _, cx| this.close_tab(ix, cx))),
+                )
+        });
+        h_flex()
+            .id("tab-bar")
+            .w_full()
+            .overflow_x_scroll()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .children(tabs)
+            .child(
+                Button::new("new-tab")
+                    .label("+")
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, window, cx| {
+                        let path = this.active().read(cx).current_path().to_path_buf();
+                        this.open_tab(path, window, cx);
+                    })),
+            )
+            .child(div().flex_1())
+            .child(
+                Button::new("toggle-dual-pane")
+                    .label(if self.dual.is_some() { "Single Pane" } else { "Split" })
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, window, cx| this.toggle_dual_pane(window, cx))),
+            )
+    }
+
+    fn render_dual_toolbar(&self, dual: &DualPane, cx: &mut Context<Self>) -> impl IntoElement {
+        let arrow = if dual.other_focused { "←" } else { "→" };
+        h_flex()
+            .gap_1()
+            .px_2()
+            .py_1()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .child(
+                Button::new("copy-to-pane")
+                    .label(format!("Copy {arrow}"))
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, window, cx| this.transfer_to_opposite(false, window, cx))),
+            )
+            .child(
+                Button::new("move-to-pane")
+                    .label(format!("Move {arrow}"))
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, window, cx| this.transfer_to_opposite(true, window, cx))),
+            )
+            .child(
+                Button::new("sync-panes")
+                    .label("Sync Navigation")
+                    .small()
+                    .ghost()
+                    .selected(dual.synced)
+                    .on_click(cx.listener(|this, _, _, cx| this.toggle_sync_navigation(cx))),
+            )
+            .child(
+                Button::new("compare-panes")
+                    .label("Compare")
+                    .small()
+                    .ghost()
+                    .on_click(cx.listener(|this, _, _, cx| this.compare_panes(true, cx))),
+            )
+    }
+
+    /// One side of the side-by-side mode; pressing anywhere in it focuses it.
+    fn render_pane(&self, tab: Entity<Tab>, other: bool, focused: bool, cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .flex_1()
+            .h_full()
+            .overflow_hidden()
+            .border_t_2()
+            .border_color(if focused { cx.theme().primary } else { cx.theme().border })
+            .when(other, |this| this.border_l_1())
+            .capture_any_mouse_down(cx.listener(move |this, _, _, cx| this.focus_pane(other, cx)))
+            .child(tab)
+    }
+
+    fn set_status(&mut self, status: impl Into<SharedString>, cx: &mut Context<Self>) {
+        self.status = Some(status.into());
+        cx.notify();
+    }
+}
+
+/// Where the window opens: as it was left, or centered at the default size.
+pub(crate) fn window_bounds(state: Option<WindowState>, cx: &App) -> WindowBounds {
+    let Some(state) = state else {
+        return WindowBounds::Windowed(Bounds::centered(None, size(px(1200.0), px(800.0)), cx));
+    };
+    let bounds = Bounds::new(point(px(state.x), px(state.y)), size(px(state.width), px(state.height)));
+    if state.maximized {
+        WindowBounds::Maximized(bounds)
+    } else {
+        WindowBounds::Windowed(bounds)
+    }
+}
+
+fn window_state(bounds: WindowBounds) -> WindowState {
+    let (bounds, maximized) = match bounds {
+        WindowBounds::Windowed(bounds) | WindowBounds::Fullscreen(bounds) => (bounds, false),
+        WindowBounds::Maximized(bounds) => (bounds, true),
+    };
+    WindowState {
+        x: bounds.origin.x.into(),
+        y: bounds.origin.y.into(),
+        width: bounds.size.width.into(),
+        height: bounds.size.height.into(),
+        maximized,
+    }
+}
+
+/// Where the other pane goes when one moves from `from` to `to`: into the
+/// same subfolder, or up as many levels.
+fn synced_dir(from: &Path, to: &Path, other: &Path) -> Option<PathBuf> {
+    if let Ok(relative) = to.strip_prefix(from) {
+        return Some(other.join(relative)).filter(|dir| dir.is_dir());
+    }
+    let up = from.strip_prefix(to).ok()?.components().count();
+    other.ancestors().nth(up).map(Path::to_path_buf)
 }
 
 impl Render for Shelf {
-    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
-        let _file_name = self.selected_item.as_ref().map(|e| e.name.clone());
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let status = match &self.file_op {
+            Some((description, progress)) => Some(format!("{description}… {progress}").into()),
+            None => self.status.clone(),
+        };
 
         div()
+            .flex()
             .size_full()
+            .key_context(CONTEXT)
+            .on_action(cx.listener(Self::on_action_new_file))
+            .on_action(cx.listener(Self::on_action_new_folder))
+            .on_action(cx.listener(Self::on_action_duplicate))
+            .on_action(cx.listener(Self::on_action_copy))
+            .on_action(cx.listener(Self::on_action_cut))
+            .on_action(cx.listener(Self::on_action_paste))
+            .on_action(cx.listener(Self::on_action_delete))
+            .on_action(cx.listener(Self::on_action_undo))
+            .on_action(cx.listener(Self::on_action_redo))
+            .on_action(cx.listener(Self::on_action_go_back))
+            .on_action(cx.listener(Self::on_action_go_forward))
+            .on_action(cx.listener(Self::on_action_new_tab))
+            .on_action(cx.listener(Self::on_action_close_tab))
+            .on_action(cx.listener(Self::on_action_next_tab))
+            .on_action(cx.listener(Self::on_action_previous_tab))
+            .on_action(cx.listener(Self::on_action_toggle_dual_pane))
+            .on_action(cx.listener(Self::on_action_switch_pane))
+            .on_action(cx.listener(Self::on_action_copy_to_other_pane))
+            .on_action(cx.listener(Self::on_action_move_to_other_pane))
+            .on_action(cx.listener(Self::on_action_toggle_sync_navigation))
+            .on_action(cx.listener(Self::on_action_compare_panes))
+            .on_action(cx.listener(Self::on_action_toggle_sidebar))
+            .on_mouse_down(
+                MouseButton::Navigate(NavigationDirection::Back),
+                cx.listener(|this, _, _, cx| this.go_back(1, cx)),
+            )
+            .on_mouse_down(
+                MouseButton::Navigate(NavigationDirection::Forward),
+                cx.listener(|this, _, _, cx| this.go_forward(1, cx)),
+            )
+            .when(self.left_dock.read(cx).side() == Side::Right, |this| this.flex_row_reverse())
+            .child(self.left_dock.clone())
             .child(
                 div()
+                    .flex()
                     .flex_col()
-                    .size_full()
-                    .child(self.header.clone())
-                    .child(
+                    .flex_1()
+                    .h_full()
+                    .child(div().flex_1().overflow_hidden().child(self.render_main(cx)))
+                    .children(status.map(|status: SharedString| {
                         div()
-                            .flex()
-                            .pt(px(8.0))
-                            .size_full()
-                            .child(
-                                div()
-                                    .flex_shrink()
-                                    .flex_basis(px(300.0))
-                                    .min_w(px(200.0))
-                                    .max_w(px(500.0))
-                                    .child(self.file_tree.clone()),
-                            )
-                            .child(self.content_viewer.clone()),
-                    ),
+                            .px_4()
+                            .py_1()
+                            .border_t_1()
+                            .border_color(cx.theme().border)
+                            .text_sm()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(status)
+                    })),
             )
+            .children(Root::render_dialog_layer(window, cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synced_navigation_follows_relative_moves() {
+        let left = tempfile::tempdir().unwrap();
+        let right = tempfile::tempdir().unwrap();
+        std::fs::create_dir(right.path().join("src")).unwrap();
+
+        let from = left.path();
+        assert_eq!(synced_dir(from, &from.join("src"), right.path()), Some(right.path().join("src")));
+        assert_eq!(synced_dir(from, &from.join("docs"), right.path()), None);
+        let inner = right.path().join("src");
+        assert_eq!(
+            synced_dir(&from.join("a/b"), from, &inner),
+            inner.parent().and_then(Path::parent).map(Path::to_path_buf)
+        );
+        assert_eq!(synced_dir(from, Path::new("/elsewhere"), right.path()), None);
     }
 }
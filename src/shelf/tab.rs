@@ -0,0 +1,467 @@
+use gpui::{
+    App, AppContext, Context, DragMoveEvent, Empty, Entity, EntityId, EventEmitter, InteractiveElement,
+    IntoElement, ParentElement, Pixels, Render, SharedString, StatefulInteractiveElement, Styled, Subscription,
+    Task, Window, div, prelude::FluentBuilder, px,
+};
+use notify::RecommendedWatcher;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::component::DirEntry;
+use crate::component::batch_fix_view::{BatchFixEvent, BatchFixView};
+use crate::component::content_viewer::{ContentViewer, ContentViewerEvent};
+use crate::component::file_tree::{FileTree, FileTreeEvent};
+use crate::component::header::{Header, HeaderEvent};
+use crate::component::tags_view::save_tags;
+use crate::history::{History, Location};
+use crate::session::TabState;
+use crate::tags::TagDb;
+use crate::watcher::{self, FsChange};
+
+const MIN_TREE_WIDTH: Pixels = px(200.0);
+const MAX_TREE_WIDTH: Pixels = px(600.0);
+
+#[derive(Clone)]
+pub enum TabEvent {
+    Status(SharedString),
+    OpenInNewTab(PathBuf),
+    /// Moved from one folder to another, by any means.
+    Navigated { from: PathBuf, to: PathBuf },
+    /// Entries were added, removed or renamed on disk.
+    Changed,
+    /// The tree asked to show more than the tagged files.
+    ClearTagFilter,
+    /// Show this file or folder in the explorer.
+    Reveal(PathBuf),
+}
+
+/// One folder being browsed: its header, tree, viewer and history. File
+/// operations, the clipboard and the trash are shared by all tabs and stay
+/// in the [`Shelf`](super::Shelf).
+pub struct Tab {
+    current_path: PathBuf,
+    entries: Vec<DirEntry>,
+    header: Entity<Header>,
+    file_tree: Entity<FileTree>,
+    selected_item: Option<DirEntry>,
+    content_viewer: Entity<ContentViewer>,
+    /// Shown in place of the content viewer while a folder is autocorrected.
+    batch_fix: Option<(Entity<BatchFixView>, Subscription)>,
+    history: History,
+    watch: Option<(RecommendedWatcher, Task<()>)>,
+    /// Only the header and tree are shown, as one of two side-by-side panes.
+    compact: bool,
+    tree_width: Pixels,
+    tags: Entity<TagDb>,
+    _subscriptions: Vec<Subscription>,
+}
+
+/// The handle between the tree and the viewer, tagged with the tab it
+/// belongs to since every tab sees every drag.
+#[derive(Clone)]
+struct TreeDivider(EntityId);
+
+impl Render for TreeDivider {
+    fn render(&mut self, _: &mut Window, _: &mut Context<Self>) -> impl IntoElement {
+        Empty
+    }
+}
+
+impl EventEmitter<TabEvent> for Tab {}
+
+impl Tab {
+    pub fn new(state: TabState, tags: Entity<TagDb>, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let path = state.dir;
+        let header = cx.new(|cx| Header::view(window, cx, Some(path.clone())));
+        let file_tree = FileTree::view(window, cx);
+        let content_viewer = cx.new(|cx| ContentViewer::new(tags.clone(), window, cx));
+
+        let _subscriptions = vec![
+            cx.subscribe(&file_tree, |this, _, event: &FileTreeEvent, cx| match event {
+                FileTreeEvent::Select(path) => this.handle_item_click(entry_for(path.clone()), cx),
+                FileTreeEvent::Open(path) => this.navigate_to_directory(path.clone(), cx),
+                FileTreeEvent::Autocorrect(dir) => this.start_batch_fix(dir.clone(), cx),
+                FileTreeEvent::OpenInNewTab(dir) => cx.emit(TabEvent::OpenInNewTab(dir.clone())),
+                FileTreeEvent::ClearFilter => cx.emit(TabEvent::ClearTagFilter),
+                FileTreeEvent::Loaded(path) => {
+                    if let Some((watcher, _)) = &mut this.watch
+                        && let Err(err) = watcher::watch_dir(watcher, path)
+                    {
+                        eprintln!("Failed to watch {}: {err}", path.display());
+                    }
+                }
+            }),
+            cx.subscribe(&content_viewer, |_, _, event: &ContentViewerEvent, cx| match event {
+                ContentViewerEvent::Open(path) => cx.emit(TabEvent::Reveal(path.clone())),
+            }),
+            cx.subscribe(&header, |this, _, event: &HeaderEvent, cx| match event {
+                HeaderEvent::NavigateTo(path) => this.navigate_to_directory(path.clone(), cx),
+                HeaderEvent::GoBack(steps) => this.go_back(*steps, cx),
+                HeaderEvent::GoForward(steps) => this.go_forward(*steps, cx),
+            }),
+        ];
+
+        let mut this = Self {
+            current_path: path.clone(),
+            entries: Vec::new(),
+            header,
+            file_tree,
+            selected_item: None,
+            content_viewer,
+            batch_fix: None,
+            history: History::default(),
+            watch: None,
+            compact: false,
+            tree_width: px(state.tree_width).clamp(MIN_TREE_WIDTH, MAX_TREE_WIDTH),
+            tags,
+            _subscriptions,
+        };
+        this.open_location(Location { dir: path, selected: state.selected }, cx);
+        this.file_tree.update(cx, |tree, _| tree.expand_on_load(state.expanded));
+        this
+    }
+
+    /// What is saved of this tab in the session.
+    pub fn state(&self, cx: &App) -> TabState {
+        TabState {
+            dir: self.current_path.clone(),
+            selected: self.selected_item.as_ref().map(|item| item.path.clone()),
+            expanded: self.file_tree.read(cx).expanded_paths(),
+            tree_width: self.tree_width.into(),
+        }
+    }
+
+    pub fn current_path(&self) -> &Path {
+        &self.current_path
+    }
+
+    /// Name shown on the tab.
+    pub fn title(&self) -> SharedString {
+        self.current_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.current_path.display().to_string())
+            .into()
+    }
+
+    pub fn handle_item_click(&mut self, entry: DirEntry, cx: &mut Context<Self>) {
+        if entry.is_dir {
+            // Only shown, to tag it; double-clicking it in the tree opens it.
+            let path = entry.path.clone();
+            let name = entry.name.clone();
+            self.selected_item = Some(entry);
+            self.content_viewer.update(cx, |cv, cx| cv.open_folder(name, path, cx));
+        } else {
+            let path = entry.path.clone();
+            let name = entry.name.clone();
+            self.selected_item = Some(entry);
+            self.content_viewer.update(cx, |cv, cx| cv.open_file(name, path, cx));
+        }
+    }
+
+    fn start_batch_fix(&mut self, dir: PathBuf, cx: &mut Context<Self>) {
+        let root = self.file_tree.read(cx).root().to_path_buf();
+        let view = cx.new(|cx| BatchFixView::new(dir, root, cx));
+        let subscription = cx.subscribe(&view, |this, _, event: &BatchFixEvent, cx| match event {
+            BatchFixEvent::Written(paths) => {
+                // The viewer would otherwise keep showing the old text.
+                if let Some(entry) = this.selected_item.clone()
+                    && paths.contains(&entry.path)
+                {
+                    this.handle_item_click(entry, cx);
+                }
+                cx.emit(TabEvent::Status(format!("Autocorrect wrote {} files", paths.len()).into()));
+            }
+            BatchFixEvent::Closed => {
+                this.batch_fix = None;
+                cx.notify();
+            }
+        });
+        self.batch_fix = Some((view, subscription));
+        cx.notify();
+    }
+
+    pub fn navigate_to_directory(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        if path != self.current_path {
+            self.history.push(self.location());
+        }
+        self.open_location(Location { dir: path, selected: None }, cx);
+    }
+
+    /// Opens the folder containing `path` with `path` selected.
+    pub fn reveal(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        let Some(dir) = path.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        self.history.push(self.location());
+        self.open_location(Location { dir, selected: Some(path) }, cx);
+    }
+
+    pub fn go_back(&mut self, steps: usize, cx: &mut Context<Self>) {
+        if let Some(location) = self.history.go_back(steps, self.location()) {
+            self.open_location(location, cx);
+        }
+    }
+
+    pub fn go_forward(&mut self, steps: usize, cx: &mut Context<Self>) {
+        if let Some(location) = self.history.go_forward(steps, self.location()) {
+            self.open_location(location, cx);
+        }
+    }
+
+    fn location(&self) -> Location {
+        Location {
+            dir: self.current_path.clone(),
+            selected: self.selected_item.as_ref().map(|item| item.path.clone()),
+        }
+    }
+
+    /// Shows `location` without touching the history, selecting its file
+    /// again if it is still there.
+    fn open_location(&mut self, location: Location, cx: &mut Context<Self>) {
+        let selected = location.selected.filter(|path| path.exists());
+        let from = std::mem::replace(&mut self.current_path, location.dir);
+        self.load_directory_async(self.current_path.clone(), cx);
+        self.watch_directory(self.current_path.clone(), cx);
+        let back = self.history.back_entries().map(|location| location.dir.clone()).collect();
+        let forward = self.history.forward_entries().map(|location| location.dir.clone()).collect();
+        self.header.update(cx, |header, cx| {
+            header.set_path(self.current_path.clone());
+            header.set_history(back, forward, cx);
+        });
+        self.file_tree.update(cx, |tree, cx| {
+            tree.set_root_path_selecting(self.current_path.clone(), selected.clone(), cx);
+        });
+        self.selected_item = None;
+        self.content_viewer.update(cx, |cv, cx| cv.clear(cx));
+        if let Some(path) = selected {
+            self.handle_item_click(entry_for(path), cx);
+        }
+        if from != self.current_path {
+            cx.emit(TabEvent::Navigated {
+                from,
+                to: self.current_path.clone(),
+            });
+        }
+        cx.notify();
+    }
+
+    pub fn target_path(&self, cx: &App) -> Option<PathBuf> {
+        self.file_tree.read(cx).target_path(cx)
+    }
+
+    /// Directory that new and pasted entries go into: the selected folder, or
+    /// the folder containing the selected file.
+    pub fn target_dir(&self, cx: &App) -> PathBuf {
+        match self.target_path(cx) {
+            Some(path) if path.is_dir() => path,
+            Some(path) => path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| self.current_path.clone()),
+            None => self.current_path.clone(),
+        }
+    }
+
+    pub fn set_compact(&mut self, compact: bool, cx: &mut Context<Self>) {
+        self.compact = compact;
+        cx.notify();
+    }
+
+    pub fn set_highlighted(&mut self, paths: HashSet<PathBuf>, cx: &mut Context<Self>) {
+        self.file_tree.update(cx, |tree, cx| tree.set_highlighted(paths, cx));
+    }
+
+    pub fn set_tag_filter(&mut self, filter: Option<HashSet<PathBuf>>, cx: &mut Context<Self>) {
+        self.file_tree.update(cx, |tree, cx| tree.set_filter(filter, cx));
+    }
+
+    pub fn start_rename(&mut self, path: &Path, window: &mut Window, cx: &mut Context<Self>) {
+        self.file_tree.update(cx, |tree, cx| tree.start_rename(path, window, cx));
+    }
+
+    fn watch_directory(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        self.watch = None;
+        let (watcher, mut changes) = match watcher::watch(&path) {
+            Ok(watch) => watch,
+            Err(err) => {
+                cx.emit(TabEvent::Status(format!("Not watching {}: {err}", path.display()).into()));
+                return;
+            }
+        };
+        let task = cx.spawn(async move |this, cx| {
+            while let Some(batch) = watcher::next_burst(&mut changes, cx.background_executor()).await {
+                if let Err(err) = this.update(cx, |this, cx| this.apply_fs_changes(batch, cx)) {
+                    eprintln!("Apply file system changes failed: {err}");
+                    break;
+                }
+            }
+        });
+        self.watch = Some((watcher, task));
+    }
+
+    /// Patches the tree and entries for changes made on disk, whether seen
+    /// by the watcher or made by a file operation in any tab.
+    pub fn apply_fs_changes(&mut self, changes: Vec<FsChange>, cx: &mut Context<Self>) {
+        let mut selection_removed = false;
+        let mut tags_moved = false;
+        for change in &changes {
+            if let FsChange::Renamed(from, to) = change {
+                tags_moved |= self.tags.update(cx, |db, _| db.rename_path(from, to));
+            }
+            let removed = match change {
+                FsChange::Added(_) => None,
+                FsChange::Removed(path) | FsChange::Renamed(path, _) => Some(path),
+            };
+            selection_removed |= removed.is_some_and(|removed| {
+                self.selected_item
+                    .as_ref()
+                    .is_some_and(|item| item.path.starts_with(removed))
+            });
+        }
+
+        self.file_tree.update(cx, |tree, cx| {
+            for change in &changes {
+                match change {
+                    FsChange::Added(path) => tree.add_path(path, cx),
+                    FsChange::Removed(path) => tree.remove_path(path, cx),
+                    FsChange::Renamed(from, to) => tree.apply_rename(from, to, cx),
+                }
+            }
+        });
+
+        for change in changes {
+            let (removed, added) = match change {
+                FsChange::Added(path) => (None, Some(path)),
+                FsChange::Removed(path) => (Some(path), None),
+                FsChange::Renamed(from, to) => (Some(from), Some(to)),
+            };
+            if let Some(path) = removed {
+                self.entries.retain(|entry| entry.path != path);
+            }
+            if let Some(path) = added.filter(|path| path.parent() == Some(self.current_path.as_path()))
+                && !self.entries.iter().any(|entry| entry.path == path)
+            {
+                self.entries.push(entry_for(path));
+            }
+        }
+        sort_entries(&mut self.entries);
+
+        if selection_removed {
+            self.selected_item = None;
+            self.content_viewer.update(cx, |cv, cx| cv.clear(cx));
+        }
+        if tags_moved {
+            self.tags.update(cx, |db, cx| {
+                save_tags(db, cx);
+                cx.notify();
+            });
+        }
+        cx.emit(TabEvent::Changed);
+        cx.notify();
+    }
+
+    fn load_directory_async(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        let task = cx.background_spawn(async move {
+            let mut entries = Vec::new();
+            if let Ok(read_dir) = fs::read_dir(&path) {
+                for entry in read_dir.flatten() {
+                    let path = entry.path();
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    let is_dir = path.is_dir();
+                    entries.push(DirEntry {
+                        name: SharedString::from(name),
+                        is_dir,
+                        path,
+                    });
+                }
+            }
+            sort_entries(&mut entries);
+            entries
+        });
+        cx.spawn(async move |this, cx| {
+            let entries = task.await;
+            if let Err(err) = this.update(cx, |this, cx| {
+                this.entries = entries;
+                cx.notify();
+            }) {
+                eprintln!("Update entries failed: {err}");
+            }
+        })
+        .detach();
+    }
+}
+
+fn entry_for(path: PathBuf) -> DirEntry {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    DirEntry {
+        name: SharedString::from(name),
+        is_dir: path.is_dir(),
+        path,
+    }
+}
+
+// Sort: directories first, then files, both alphabetically
+fn sort_entries(entries: &mut [DirEntry]) {
+    entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
+        (true, false) => std::cmp::Ordering::Less,
+        (false, true) => std::cmp::Ordering::Greater,
+        _ => a.name.cmp(&b.name),
+    });
+}
+
+impl Render for Tab {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .flex_col()
+            .size_full()
+            .child(self.header.clone())
+            .child(
+                div()
+                    .flex()
+                    .pt(px(8.0))
+                    .size_full()
+                    .on_drag_move(cx.listener(|this, event: &DragMoveEvent<TreeDivider>, _, cx| {
+                        if event.drag(cx).0 != cx.entity_id() {
+                            return;
+                        }
+                        let width = event.event.position.x - event.bounds.left();
+                        this.tree_width = width.clamp(MIN_TREE_WIDTH, MAX_TREE_WIDTH);
+                        cx.notify();
+                    }))
+                    .map(|this| {
+                        if self.compact {
+                            return this.child(match &self.batch_fix {
+                                Some((view, _)) => div().flex_1().h_full().child(view.clone()),
+                                None => div().flex_1().child(self.file_tree.clone()),
+                            });
+                        }
+                        this.child(
+                            div()
+                                .flex_shrink_0()
+                                .w(self.tree_width)
+                                .child(self.file_tree.clone()),
+                        )
+                        .child(
+                            div()
+                                .id("tree-divider")
+                                .flex_shrink_0()
+                                .w(px(6.0))
+                                .h_full()
+                                .cursor_col_resize()
+                                .on_drag(TreeDivider(cx.entity_id()), |divider, _, _, cx| {
+                                    cx.new(|_| divider.clone())
+                                }),
+                        )
+                        .child(match &self.batch_fix {
+                            Some((view, _)) => div().flex_1().h_full().child(view.clone()),
+                            None => div().flex_1().flex().child(self.content_viewer.clone()),
+                        })
+                    }),
+            )
+    }
+}
@@ -0,0 +1,157 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The XDG base directories the app keeps its files in.
+#[derive(Clone, Copy, Debug)]
+pub enum BaseDir {
+    /// Settings edited by hand.
+    Config,
+    /// Data the user made, like tags.
+    Data,
+    /// What the app remembers between runs, like the session.
+    State,
+    /// Anything that can be rebuilt.
+    Cache,
+}
+
+/// `$XDG_<KIND>_HOME/toshokan`, or the spec's default under the home
+/// folder when that isn't set to an absolute path.
+pub fn dir(base: BaseDir) -> Option<PathBuf> {
+    base_home(base).map(|dir| dir.join("toshokan"))
+}
+
+/// `$XDG_<KIND>_HOME` itself, shared with other applications, like the
+/// trash under the data home.
+pub fn base_home(base: BaseDir) -> Option<PathBuf> {
+    let (var, default) = match base {
+        BaseDir::Config => ("XDG_CONFIG_HOME", ".config"),
+        BaseDir::Data => ("XDG_DATA_HOME", ".local/share"),
+        BaseDir::State => ("XDG_STATE_HOME", ".local/state"),
+        BaseDir::Cache => ("XDG_CACHE_HOME", ".cache"),
+    };
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(default)))
+}
+
+/// Reads `path` as JSON; `None` if there is no such file. Text that doesn't
+/// parse is an `InvalidData` error.
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> io::Result<Option<T>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Reads `path` as JSON, or the default if there is none. A file that
+/// can't be parsed is moved aside rather than left to be overwritten, in
+/// case it is worth looking at.
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> T {
+    match read_json(path) {
+        Ok(value) => value.unwrap_or_default(),
+        Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+            eprintln!("Ignoring corrupt {}: {err}", path.display());
+            if let Err(err) = fs::rename(path, path.with_extension("json.corrupt")) {
+                eprintln!("Failed to move {} aside: {err}", path.display());
+            }
+            T::default()
+        }
+        Err(err) => {
+            eprintln!("Failed to read {}: {err}", path.display());
+            T::default()
+        }
+    }
+}
+
+/// Writes `value` as JSON to a temporary file first, so a crash mid-write
+/// never leaves a half-written file behind. `pretty` is for files people
+/// may read.
+pub fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T, pretty: bool) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+    .map_err(io::Error::other)?;
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(tmp, path)
+}
+
+/// Keeps background writes of one file in order: each snapshot takes a
+/// ticket on the UI thread, and a write is skipped once a newer ticket has
+/// been written.
+#[derive(Default)]
+pub struct WriteOrder {
+    issued: AtomicU64,
+    written: Mutex<u64>,
+}
+
+impl WriteOrder {
+    pub const fn new() -> Self {
+        Self {
+            issued: AtomicU64::new(0),
+            written: Mutex::new(0),
+        }
+    }
+
+    pub fn ticket(&self) -> u64 {
+        self.issued.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Runs `write` for the snapshot holding `ticket`, unless it is stale.
+    pub fn write(&self, ticket: u64, write: impl FnOnce() -> io::Result<()>) -> io::Result<()> {
+        // A panic mid-write leaves nothing worth protecting in the count.
+        let mut written = self.written.lock().unwrap_or_else(|err| err.into_inner());
+        if *written > ticket {
+            return Ok(());
+        }
+        write()?;
+        *written = ticket;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_reads_and_moves_corrupt_files_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/list.json");
+        assert_eq!(read_json::<Vec<u32>>(&path).unwrap(), None);
+        assert_eq!(load_json::<Vec<u32>>(&path), Vec::<u32>::new());
+
+        write_json(&path, &[1, 2, 3], false).unwrap();
+        assert_eq!(load_json::<Vec<u32>>(&path), [1, 2, 3]);
+        assert!(!dir.path().join("nested/list.json.tmp").exists());
+
+        fs::write(&path, "[1, 2").unwrap();
+        let err = read_json::<Vec<u32>>(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(load_json::<Vec<u32>>(&path), Vec::<u32>::new());
+        assert!(dir.path().join("nested/list.json.corrupt").exists());
+        assert!(!path.exists());
+
+        let order = WriteOrder::new();
+        let (old, new) = (order.ticket(), order.ticket());
+        order.write(new, || write_json(&path, &[2], false)).unwrap();
+        order.write(old, || write_json(&path, &[1], false)).unwrap();
+        assert_eq!(load_json::<Vec<u32>>(&path), [2]);
+    }
+}
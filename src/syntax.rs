@@ -0,0 +1,64 @@
+use gpui_component::highlighter::Language;
+
+/// Picks the language to highlight a file as from its name, falling back to
+/// the shebang on the first line for extensionless scripts.
+pub fn detect(file_name: &str, first_line: &str) -> Language {
+    let name = file_name.to_lowercase();
+    let ext = name.rsplit_once('.').map(|(_, ext)| ext).unwrap_or("");
+    // What gpui-component doesn't recognize by extension alone.
+    match (name.as_str(), ext) {
+        ("cargo.lock", _) => return Language::Toml,
+        ("makefile" | "gnumakefile", _) | (_, "mk") => return Language::Make,
+        ("cmakelists.txt", _) => return Language::CMake,
+        (".bashrc" | ".bash_profile" | ".zshrc" | ".profile" | "pkgbuild", _) | (_, "zsh") => {
+            return Language::Bash;
+        }
+        (_, "pyi" | "pyw") => return Language::Python,
+        (_, "json5" | "ipynb") => return Language::Json,
+        (_, "h") => return Language::C,
+        (_, "hpp" | "cc" | "cxx") => return Language::Cpp,
+        (_, "jsx" | "mjs" | "cjs") => return Language::JavaScript,
+        (_, "htm") => return Language::Html,
+        _ => {}
+    }
+    let language = Language::from_str(ext);
+    if language != Language::Plain {
+        return language;
+    }
+
+    let Some(shebang) = first_line.strip_prefix("#!") else {
+        return Language::Plain;
+    };
+    let interpreter = shebang
+        .split_whitespace()
+        .flat_map(|part| part.rsplit('/').next())
+        .find(|part| *part != "env")
+        .unwrap_or("");
+    if interpreter.starts_with("python") {
+        Language::Python
+    } else if interpreter.starts_with("ruby") {
+        Language::Ruby
+    } else if interpreter == "node" {
+        Language::JavaScript
+    } else if matches!(interpreter, "sh" | "bash" | "zsh" | "dash" | "ksh") {
+        Language::Bash
+    } else {
+        Language::Plain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_language_from_name_and_shebang() {
+        assert_eq!(detect("main.rs", ""), Language::Rust);
+        assert_eq!(detect("Cargo.lock", ""), Language::Toml);
+        assert_eq!(detect("README.MD", ""), Language::Markdown);
+        assert_eq!(detect("Makefile", ""), Language::Make);
+        assert_eq!(detect("build", "#!/usr/bin/env python3"), Language::Python);
+        assert_eq!(detect("run", "#!/bin/bash -e"), Language::Bash);
+        assert_eq!(detect("notes.txt", "hello"), Language::Plain);
+    }
+}
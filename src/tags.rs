@@ -0,0 +1,359 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::index;
+use crate::storage;
+
+const FILE_NAME: &str = "tags.json";
+/// Colors handed to new tags in turn, and cycled through when a swatch is
+/// clicked.
+pub const PALETTE: [u32; 8] = [
+    0xef4444, 0xf97316, 0xeab308, 0x22c55e, 0x3b82f6, 0x8b5cf6, 0xec4899, 0x6b7280,
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tag {
+    pub name: String,
+    /// `0xRRGGBB`.
+    pub color: u32,
+}
+
+/// Whether a file must carry every selected tag or just one of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Match {
+    All,
+    Any,
+}
+
+/// Each tagged path with the names of its tags, for looking many files up
+/// at once.
+pub type PathTags = HashMap<PathBuf, BTreeSet<String>>;
+
+/// What a file is recognized by once its path no longer leads to it, see
+/// [`identify`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    dev: u64,
+    inode: u64,
+    is_dir: bool,
+    len: u64,
+    /// Inodes get reused, so a file only counts as the same one by inode if
+    /// this and `len` match too.
+    #[serde(default)]
+    modified: Option<SystemTime>,
+    /// FNV-1a of the contents, for files small enough to read.
+    hash: Option<u64>,
+}
+
+impl Identity {
+    fn same_inode(&self, other: &Identity) -> bool {
+        self.dev == other.dev
+            && self.inode == other.inode
+            && self.is_dir == other.is_dir
+            && (self.is_dir || (self.len == other.len && self.modified == other.modified))
+    }
+}
+
+/// The tagged files that may have gone missing, copied out of the db so
+/// they can be checked against the disk off the UI thread.
+pub struct Orphans(Vec<(PathBuf, Identity)>);
+
+/// A missing tagged file found again, to be applied with
+/// [`TagDb::apply_relink`].
+pub struct Relink {
+    from: PathBuf,
+    to: PathBuf,
+    identity: Option<Identity>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct TaggedFile {
+    path: PathBuf,
+    identity: Option<Identity>,
+    tags: BTreeSet<String>,
+}
+
+/// User-defined tags and the files and folders carrying them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagDb {
+    tags: Vec<Tag>,
+    files: Vec<TaggedFile>,
+}
+
+impl TagDb {
+    /// The saved tags, or none if there are none or the file is corrupt.
+    pub fn load(dir: &Path) -> Self {
+        storage::load_json(&dir.join(FILE_NAME))
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        storage::write_json(&dir.join(FILE_NAME), self, true)
+    }
+
+    pub fn tags(&self) -> &[Tag] {
+        &self.tags
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&Tag> {
+        self.tags.iter().find(|tag| tag.name == name)
+    }
+
+    /// Adds a tag nobody carries yet. Returns false for blank or taken names.
+    pub fn create_tag(&mut self, name: &str) -> bool {
+        let name = name.trim();
+        if name.is_empty() || self.tag(name).is_some() {
+            return false;
+        }
+        let color = PALETTE[self.tags.len() % PALETTE.len()];
+        self.tags.push(Tag { name: name.to_string(), color });
+        true
+    }
+
+    pub fn cycle_color(&mut self, name: &str) {
+        if let Some(tag) = self.tags.iter_mut().find(|tag| tag.name == name) {
+            let next = PALETTE.iter().position(|color| *color == tag.color).map_or(0, |ix| ix + 1);
+            tag.color = PALETTE[next % PALETTE.len()];
+        }
+    }
+
+    /// Removes the tag and takes it off every file.
+    pub fn delete_tag(&mut self, name: &str) {
+        self.tags.retain(|tag| tag.name != name);
+        for file in &mut self.files {
+            file.tags.remove(name);
+        }
+        self.files.retain(|file| !file.tags.is_empty());
+    }
+
+    /// How many files and folders carry `name`.
+    pub fn count(&self, name: &str) -> usize {
+        self.files.iter().filter(|file| file.tags.contains(name)).count()
+    }
+
+    pub fn tags_for(&self, path: &Path) -> Vec<&Tag> {
+        let Some(file) = self.files.iter().find(|file| file.path == path) else {
+            return Vec::new();
+        };
+        self.tags.iter().filter(|tag| file.tags.contains(&tag.name)).collect()
+    }
+
+    pub fn path_tags(&self) -> PathTags {
+        self.files.iter().map(|file| (file.path.clone(), file.tags.clone())).collect()
+    }
+
+    /// Tags `path`, creating the tag first if it is new. `identity` is what
+    /// [`identify`] made of it.
+    pub fn add(&mut self, path: &Path, name: &str, identity: Option<Identity>) {
+        let name = name.trim();
+        if name.is_empty() {
+            return;
+        }
+        self.create_tag(name);
+        match self.files.iter_mut().find(|file| file.path == path) {
+            Some(file) => {
+                file.tags.insert(name.to_string());
+                file.identity = identity;
+            }
+            None => self.files.push(TaggedFile {
+                path: path.to_path_buf(),
+                identity,
+                tags: BTreeSet::from([name.to_string()]),
+            }),
+        }
+    }
+
+    pub fn remove(&mut self, path: &Path, name: &str) {
+        if let Some(file) = self.files.iter_mut().find(|file| file.path == path) {
+            file.tags.remove(name);
+        }
+        self.files.retain(|file| !file.tags.is_empty());
+    }
+
+    /// The files `path` could be, if it was renamed or moved while we
+    /// weren't looking; `None` if it carries tags already or there is none.
+    pub fn relink_candidates(&self, path: &Path) -> Option<Orphans> {
+        if self.files.iter().any(|file| file.path == path) {
+            return None;
+        }
+        let orphans = self
+            .files
+            .iter()
+            .filter_map(|file| Some((file.path.clone(), file.identity.clone()?)))
+            .collect::<Vec<_>>();
+        (!orphans.is_empty()).then_some(Orphans(orphans))
+    }
+
+    /// Moves the tags found by [`Orphans::find`] over, unless the db changed
+    /// in the meantime. Returns whether anything changed.
+    pub fn apply_relink(&mut self, relink: Relink) -> bool {
+        if self.files.iter().any(|file| file.path == relink.to) {
+            return false;
+        }
+        let Some(file) = self.files.iter_mut().find(|file| file.path == relink.from) else {
+            return false;
+        };
+        file.path = relink.to;
+        file.identity = relink.identity;
+        true
+    }
+
+    /// Follows a rename or move of `from`, including everything inside it.
+    /// Returns whether any tagged path moved.
+    pub fn rename_path(&mut self, from: &Path, to: &Path) -> bool {
+        let mut moved = false;
+        for file in &mut self.files {
+            if let Ok(rest) = file.path.strip_prefix(from) {
+                file.path = if rest.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rest) };
+                moved = true;
+            }
+        }
+        moved
+    }
+
+    /// Paths carrying all, or any, of `selected`, in path order.
+    pub fn matching(&self, selected: &BTreeSet<String>, mode: Match) -> Vec<PathBuf> {
+        if selected.is_empty() {
+            return Vec::new();
+        }
+        let mut paths = self
+            .files
+            .iter()
+            .filter(|file| match mode {
+                Match::All => selected.is_subset(&file.tags),
+                Match::Any => !selected.is_disjoint(&file.tags),
+            })
+            .map(|file| file.path.clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+}
+
+impl Orphans {
+    /// The missing file `path` turns out to be: same inode, length and
+    /// modification time, or failing that the same contents. Reads the disk.
+    pub fn find(&self, path: &Path) -> Option<Relink> {
+        let orphans = self.0.iter().filter(|(path, _)| !path.exists()).collect::<Vec<_>>();
+        if orphans.is_empty() {
+            return None;
+        }
+        let mut current = identity(path, false)?;
+        let mut found = orphans.iter().find(|(_, known)| known.same_inode(&current));
+        // Only read the file when some missing one could have the same contents.
+        if found.is_none()
+            && !current.is_dir
+            && orphans.iter().any(|(_, known)| known.hash.is_some() && known.len == current.len)
+        {
+            current.hash = index::content_hash(path, current.len);
+            found = orphans
+                .iter()
+                .find(|(_, known)| !known.is_dir && known.hash.is_some() && known.hash == current.hash);
+        }
+        let (from, _) = found?;
+        Some(Relink {
+            from: from.clone(),
+            to: path.to_path_buf(),
+            identity: identity(path, true),
+        })
+    }
+}
+
+/// What `path` is recognized by after a rename, hashing its contents if it
+/// is small enough. Reads the disk.
+pub fn identify(path: &Path) -> Option<Identity> {
+    identity(path, true)
+}
+
+#[cfg(unix)]
+fn identity(path: &Path, with_hash: bool) -> Option<Identity> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = fs::metadata(path).ok()?;
+    let hash = if with_hash && meta.is_file() { index::content_hash(path, meta.len()) } else { None };
+    Some(Identity {
+        dev: meta.dev(),
+        inode: meta.ino(),
+        is_dir: meta.is_dir(),
+        len: meta.len(),
+        modified: meta.modified().ok(),
+        hash,
+    })
+}
+
+#[cfg(not(unix))]
+fn identity(_: &Path, _: bool) -> Option<Identity> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(db: &mut TagDb, path: &Path, name: &str) {
+        db.add(path, name, identify(path));
+    }
+
+    fn relink(db: &mut TagDb, path: &Path) -> bool {
+        let relink = db.relink_candidates(path).and_then(|orphans| orphans.find(path));
+        relink.is_some_and(|relink| db.apply_relink(relink))
+    }
+
+    #[test]
+    fn tags_follow_files_and_match_selections() {
+        let dir = tempfile::tempdir().unwrap();
+        let book = dir.path().join("book.epub");
+        let notes = dir.path().join("notes.md");
+        fs::write(&book, "chapter one").unwrap();
+        fs::write(&notes, "todo").unwrap();
+
+        let mut db = TagDb::default();
+        add(&mut db, &book, "reading");
+        add(&mut db, &book, "fiction");
+        add(&mut db, &notes, "reading");
+        assert_eq!(db.count("reading"), 2);
+        assert_ne!(db.tag("reading").unwrap().color, db.tag("fiction").unwrap().color);
+
+        let selected = BTreeSet::from(["reading".to_string(), "fiction".to_string()]);
+        assert_eq!(db.matching(&selected, Match::All), std::slice::from_ref(&book));
+        assert_eq!(db.matching(&selected, Match::Any), [book.clone(), notes.clone()]);
+
+        // Renamed behind our back: found again by inode.
+        let moved = dir.path().join("novel.epub");
+        fs::rename(&book, &moved).unwrap();
+        assert!(relink(&mut db, &moved));
+        assert_eq!(db.tags_for(&moved).len(), 2);
+        assert!(!relink(&mut db, &moved));
+
+        // Renamed and then rewritten: the inode alone isn't enough.
+        let draft = dir.path().join("draft.md");
+        fs::write(&draft, "first").unwrap();
+        add(&mut db, &draft, "drafts");
+        let rewritten = dir.path().join("final.md");
+        fs::rename(&draft, &rewritten).unwrap();
+        fs::write(&rewritten, "something else entirely").unwrap();
+        assert!(!relink(&mut db, &rewritten));
+        db.remove(&draft, "drafts");
+
+        // Rewritten as a new file with the same contents: found by hash.
+        let copy = dir.path().join("notes-copy.md");
+        fs::copy(&notes, &copy).unwrap();
+        fs::remove_file(&notes).unwrap();
+        assert!(relink(&mut db, &copy));
+        assert_eq!(db.tags_for(&copy)[0].name, "reading");
+
+        assert!(db.rename_path(dir.path(), Path::new("/elsewhere")));
+        assert_eq!(db.tags_for(Path::new("/elsewhere/novel.epub")).len(), 2);
+
+        db.delete_tag("reading");
+        assert_eq!(db.count("reading"), 0);
+        assert_eq!(db.tags_for(Path::new("/elsewhere/notes-copy.md")), Vec::<&Tag>::new());
+
+        db.save(dir.path()).unwrap();
+        assert_eq!(TagDb::load(dir.path()), db);
+    }
+}
@@ -0,0 +1,221 @@
+use encoding_rs::Encoding;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+
+use crate::charset;
+
+/// Every `CHECKPOINT_STRIDE`th line start is remembered, so the index of a
+/// multi-GB log stays small and any line is at most one stride of scanning
+/// away.
+const CHECKPOINT_STRIDE: usize = 256;
+const READ_CHUNK: usize = 64 * 1024;
+/// Longer lines (minified files, binary junk) are cut for display.
+pub const MAX_LINE_BYTES: usize = 4096;
+
+#[derive(Default)]
+struct LineIndex {
+    checkpoints: Vec<u64>,
+    /// Number of `\n` seen so far.
+    newlines: usize,
+    /// Where the line after the last `\n` starts.
+    last_line_start: u64,
+    len: u64,
+}
+
+impl LineIndex {
+    fn line_count(&self) -> usize {
+        self.newlines + usize::from(self.len > self.last_line_start)
+    }
+
+    fn scan(&mut self, file: &mut File, newline: &[u8]) -> io::Result<()> {
+        if self.checkpoints.is_empty() {
+            self.checkpoints.push(0);
+        }
+        let unit = newline.len();
+        let mut buf = vec![0; READ_CHUNK];
+        loop {
+            file.seek(SeekFrom::Start(self.len))?;
+            // A code unit cut in half by a short read is picked up next pass.
+            let read = file.read(&mut buf)?;
+            let read = read - read % unit;
+            if read == 0 {
+                return Ok(());
+            }
+            for (ix, _) in buf[..read]
+                .chunks_exact(unit)
+                .enumerate()
+                .filter(|(_, chunk)| *chunk == newline)
+            {
+                self.newlines += 1;
+                self.last_line_start = self.len + ((ix + 1) * unit) as u64;
+                if self.newlines.is_multiple_of(CHECKPOINT_STRIDE) {
+                    self.checkpoints.push(self.last_line_start);
+                }
+            }
+            self.len += read as u64;
+        }
+    }
+}
+
+/// A text file read in pages instead of all at once. Lines are located
+/// through a sparse index and read on demand, and the index can be extended
+/// as the file grows. Lines are split on the newline as `encoding` stores
+/// it, so UTF-16 works without decoding the whole file.
+pub struct TextFile {
+    file: Mutex<File>,
+    index: RwLock<LineIndex>,
+    encoding: &'static Encoding,
+    newline: &'static [u8],
+}
+
+impl TextFile {
+    pub fn open(path: &Path, encoding: &'static Encoding) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let newline = charset::newline(encoding);
+        let mut index = LineIndex::default();
+        index.scan(&mut file, newline)?;
+        Ok(Self {
+            file: Mutex::new(file),
+            index: RwLock::new(index),
+            encoding,
+            newline,
+        })
+    }
+
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.index.read().unwrap_or_else(|err| err.into_inner()).line_count()
+    }
+
+    pub fn len(&self) -> u64 {
+        self.index.read().unwrap_or_else(|err| err.into_inner()).len
+    }
+
+    /// Indexes whatever was appended since the last scan. A file that got
+    /// shorter was truncated or rotated, so it is indexed from scratch.
+    /// Returns whether anything changed.
+    pub fn refresh(&self) -> io::Result<bool> {
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+        let disk_len = file.metadata()?.len();
+        let mut index = self.index.write().unwrap_or_else(|err| err.into_inner());
+        if disk_len == index.len {
+            return Ok(false);
+        }
+        if disk_len < index.len {
+            *index = LineIndex::default();
+        }
+        index.scan(&mut file, self.newline)?;
+        Ok(true)
+    }
+
+    /// Reads the lines in `range`, without their line endings and decoded
+    /// lossily.
+    pub fn lines(&self, range: Range<usize>) -> io::Result<Vec<String>> {
+        let (mut offset, mut skip, end) = {
+            let index = self.index.read().unwrap_or_else(|err| err.into_inner());
+            let start = range.start.min(index.line_count());
+            let checkpoint = start / CHECKPOINT_STRIDE;
+            let Some(offset) = index.checkpoints.get(checkpoint).copied() else {
+                return Ok(Vec::new());
+            };
+            (offset, start - checkpoint * CHECKPOINT_STRIDE, index.len)
+        };
+        let mut wanted = range.len();
+
+        let unit = self.newline.len();
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+        let mut lines = Vec::with_capacity(wanted);
+        let mut line = Vec::new();
+        let mut buf = vec![0; READ_CHUNK];
+        while wanted > 0 && offset < end {
+            file.seek(SeekFrom::Start(offset))?;
+            let read = file.read(&mut buf[..READ_CHUNK.min((end - offset) as usize)])?;
+            let read = read - read % unit;
+            if read == 0 {
+                break;
+            }
+            offset += read as u64;
+            for chunk in buf[..read].chunks_exact(unit) {
+                if chunk != self.newline {
+                    if skip == 0 && line.len() < MAX_LINE_BYTES {
+                        line.extend_from_slice(chunk);
+                    }
+                    continue;
+                }
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                lines.push(self.decode_line(&line));
+                line.clear();
+                wanted -= 1;
+                if wanted == 0 {
+                    break;
+                }
+            }
+        }
+        // The last line has no trailing newline.
+        if wanted > 0 && skip == 0 && offset >= end && !line.is_empty() {
+            lines.push(self.decode_line(&line));
+        }
+        Ok(lines)
+    }
+
+    fn decode_line(&self, line: &[u8]) -> String {
+        let (text, _) = self.encoding.decode_without_bom_handling(line);
+        let text = text.strip_prefix('\u{FEFF}').unwrap_or(&text);
+        text.strip_suffix('\r').unwrap_or(text).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    #[test]
+    fn reads_lines_across_checkpoints_and_follows_growth() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let content = (0..1000).map(|ix| format!("line {ix}\r\n")).collect::<String>();
+        std::fs::write(&path, content).unwrap();
+
+        let file = TextFile::open(&path, encoding_rs::UTF_8).unwrap();
+        assert_eq!(file.line_count(), 1000);
+        assert_eq!(file.lines(255..258).unwrap(), ["line 255", "line 256", "line 257"]);
+        assert_eq!(file.lines(998..1005).unwrap(), ["line 998", "line 999"]);
+        assert!(!file.refresh().unwrap());
+
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(log, "tail without newline").unwrap();
+        assert!(file.refresh().unwrap());
+        assert_eq!(file.line_count(), 1001);
+        assert_eq!(file.lines(1000..1001).unwrap(), ["tail without newline"]);
+
+        std::fs::write(&path, "rotated\n").unwrap();
+        assert!(file.refresh().unwrap());
+        assert_eq!(file.lines(0..10).unwrap(), ["rotated"]);
+    }
+
+    #[test]
+    fn splits_utf16_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        let bytes = "\u{FEFF}图书馆\r\n第二行\n"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect::<Vec<_>>();
+        std::fs::write(&path, bytes).unwrap();
+
+        let file = TextFile::open(&path, encoding_rs::UTF_16LE).unwrap();
+        assert_eq!(file.line_count(), 2);
+        assert_eq!(file.lines(0..2).unwrap(), ["图书馆", "第二行"]);
+    }
+}
@@ -0,0 +1,305 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::file_ops::{self, Progress};
+use crate::storage::{self, BaseDir};
+
+const INFO_EXT: &str = ".trashinfo";
+
+/// The home trash described by the freedesktop.org Trash specification:
+/// trashed entries live in `files/`, each with a `.trashinfo` file in `info/`
+/// recording where it came from and when it was deleted.
+#[derive(Clone, Debug)]
+pub struct Trash {
+    root: PathBuf,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrashEntry {
+    pub name: String,
+    pub original_path: PathBuf,
+    pub deletion_date: String,
+    root: PathBuf,
+}
+
+impl Trash {
+    pub fn home() -> io::Result<Self> {
+        let data_home = storage::base_home(BaseDir::Data)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No home directory"))?;
+        Ok(Self::new(&data_home))
+    }
+
+    pub fn new(data_home: &Path) -> Self {
+        Self {
+            root: data_home.join("Trash"),
+        }
+    }
+
+    /// Claims a name in the trash for `path` by writing its info file. The
+    /// spec requires the info file to be created first, atomically, so two
+    /// processes never trash different files under the same name.
+    pub fn reserve(&self, path: &Path) -> io::Result<TrashEntry> {
+        fs::create_dir_all(self.root.join("files"))?;
+        fs::create_dir_all(self.root.join("info"))?;
+        let original_path = std::path::absolute(path)?;
+        let file_name = original_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Cannot trash this path"))?;
+        let deletion_date = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+
+        for n in 1.. {
+            let name = if n == 1 {
+                file_name.clone()
+            } else {
+                format!("{file_name}.{n}")
+            };
+            let entry = TrashEntry {
+                name,
+                original_path: original_path.clone(),
+                deletion_date: deletion_date.clone(),
+                root: self.root.clone(),
+            };
+            if entry.files_path().symlink_metadata().is_ok() {
+                continue;
+            }
+            match entry.write_info() {
+                Ok(()) => return Ok(entry),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(io::Error::other("No free name in the trash"))
+    }
+
+    /// The entries, newest first, and why each info file that couldn't be
+    /// read was left out.
+    pub fn list(&self) -> io::Result<(Vec<TrashEntry>, Vec<String>)> {
+        let read_dir = match fs::read_dir(self.root.join("info")) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((Vec::new(), Vec::new())),
+            Err(err) => return Err(err),
+        };
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+        for entry in read_dir {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    errors.push(err.to_string());
+                    continue;
+                }
+            };
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(name) = file_name.strip_suffix(INFO_EXT) else {
+                continue;
+            };
+            match fs::read_to_string(entry.path()) {
+                Ok(info) => match TrashEntry::parse(name, &info, &self.root) {
+                    Some(entry) => entries.push(entry),
+                    None => errors.push(format!("{file_name}: not a valid trash info file")),
+                },
+                Err(err) => errors.push(format!("{file_name}: {err}")),
+            }
+        }
+        entries.sort_by(|a, b| b.deletion_date.cmp(&a.deletion_date));
+        Ok((entries, errors))
+    }
+
+    /// Purges every entry that could be listed.
+    pub fn empty(&self) -> io::Result<()> {
+        for entry in self.list()?.0 {
+            entry.purge()?;
+        }
+        Ok(())
+    }
+}
+
+impl TrashEntry {
+    pub fn files_path(&self) -> PathBuf {
+        self.root.join("files").join(&self.name)
+    }
+
+    fn info_path(&self) -> PathBuf {
+        self.root.join("info").join(format!("{}{INFO_EXT}", self.name))
+    }
+
+    fn write_info(&self) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.info_path())?;
+        write!(
+            file,
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(&self.original_path),
+            self.deletion_date
+        )
+    }
+
+    fn parse(name: &str, info: &str, root: &Path) -> Option<Self> {
+        let mut lines = info.lines().map(str::trim);
+        if lines.next()? != "[Trash Info]" {
+            return None;
+        }
+        let mut original_path = None;
+        let mut deletion_date = String::new();
+        for line in lines {
+            if let Some(path) = line.strip_prefix("Path=") {
+                original_path = Some(decode_path(path));
+            } else if let Some(date) = line.strip_prefix("DeletionDate=") {
+                deletion_date = date.to_string();
+            }
+        }
+        Some(Self {
+            name: name.to_string(),
+            original_path: original_path?,
+            deletion_date,
+            root: root.to_path_buf(),
+        })
+    }
+
+    /// Moves `path` into the trash under this entry's name.
+    pub fn put(&self, path: &Path, progress: &Progress) -> io::Result<()> {
+        // Redoing a delete after it was undone needs the info file back.
+        if self.info_path().symlink_metadata().is_err() {
+            self.write_info()?;
+        }
+        if let Err(err) = file_ops::move_path(path, &self.files_path(), progress) {
+            fs::remove_file(self.info_path())?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    pub fn restore(&self, progress: &Progress) -> io::Result<()> {
+        if let Some(parent) = self.original_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        file_ops::move_path(&self.files_path(), &self.original_path, progress)?;
+        fs::remove_file(self.info_path())
+    }
+
+    pub fn purge(&self) -> io::Result<()> {
+        match file_ops::remove_path(&self.files_path()) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        fs::remove_file(self.info_path())
+    }
+}
+
+/// Percent-encodes the raw bytes of `path`, so names that aren't UTF-8 come
+/// back unchanged.
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path_bytes(path).iter() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+fn decode_path(encoded: &str) -> PathBuf {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut ix = 0;
+    while ix < bytes.len() {
+        let hex = bytes
+            .get(ix + 1..ix + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[ix], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                ix += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                ix += 1;
+            }
+        }
+    }
+    path_from_bytes(decoded)
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.as_os_str().as_bytes().into()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    path.to_string_lossy().into_owned().into_bytes().into()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+
+    std::ffi::OsString::from_vec(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    String::from_utf8_lossy(&bytes).into_owned().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_encoding_round_trips() {
+        let path = Path::new("/home/me/图书馆/a file%.txt");
+        let encoded = encode_path(path);
+        assert!(!encoded.contains(' '));
+        assert_eq!(decode_path(&encoded), path);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let latin1 = Path::new(std::ffi::OsStr::from_bytes(b"/home/me/caf\xe9.txt"));
+            assert_eq!(encode_path(latin1), "/home/me/caf%E9.txt");
+            assert_eq!(decode_path(&encode_path(latin1)), latin1);
+        }
+    }
+
+    #[test]
+    fn trash_list_and_restore() {
+        let data_home = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let trash = Trash::new(data_home.path());
+        let file = work.path().join("notes.txt");
+        fs::write(&file, "keep me").unwrap();
+
+        let entry = trash.reserve(&file).unwrap();
+        entry.put(&file, &Progress::default()).unwrap();
+        assert!(!file.exists());
+        let info = fs::read_to_string(data_home.path().join("Trash/info/notes.txt.trashinfo")).unwrap();
+        assert!(info.starts_with("[Trash Info]\nPath="));
+
+        fs::write(&file, "second").unwrap();
+        let second = trash.reserve(&file).unwrap();
+        assert_eq!(second.name, "notes.txt.2");
+        second.put(&file, &Progress::default()).unwrap();
+
+        fs::write(data_home.path().join("Trash/info/broken.trashinfo"), "[Trash Info]\n").unwrap();
+        let (entries, errors) = trash.list().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(errors, ["broken.trashinfo: not a valid trash info file"]);
+        assert!(entries.iter().all(|entry| entry.original_path == file));
+
+        entry.restore(&Progress::default()).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "keep me");
+        second.purge().unwrap();
+        assert!(trash.list().unwrap().0.is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+use similar::{ChangeTag, TextDiff};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// autocorrect works on the whole text at once, so bigger files are left
+/// alone.
+pub const MAX_FILE_LEN: u64 = 2 * 1024 * 1024;
+/// Unchanged lines kept around each change in a diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// A spacing or punctuation problem reported by autocorrect.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    /// 1-based, as autocorrect reports it.
+    pub line: usize,
+    pub col: usize,
+    pub old: String,
+    pub new: String,
+}
+
+/// A file autocorrect would change, kept with the text it was checked
+/// against so it can be reviewed before anything is written.
+pub struct FileChange {
+    pub path: PathBuf,
+    pub original: String,
+    pub fixed: String,
+    pub issues: usize,
+}
+
+#[derive(Default)]
+pub struct BatchReport {
+    pub checked: usize,
+    pub issues: usize,
+    pub changes: Vec<FileChange>,
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffLine {
+    Context(String),
+    Removed(String),
+    Added(String),
+    /// A run of unchanged lines left out of the preview.
+    Skipped(usize),
+}
+
+/// Lints `text` with the rules autocorrect picks for `file_name`'s type.
+/// The issues come in line order.
+pub fn lint(text: &str, file_name: &str) -> Result<Vec<Issue>, String> {
+    let result = autocorrect::lint_for(text, file_name);
+    if !result.error.is_empty() {
+        return Err(result.error);
+    }
+    let mut issues = result
+        .lines
+        .into_iter()
+        .map(|line| Issue {
+            line: line.line,
+            col: line.col,
+            old: line.old,
+            new: line.new,
+        })
+        .collect::<Vec<_>>();
+    issues.sort_by_key(|issue| (issue.line, issue.col));
+    Ok(issues)
+}
+
+pub fn fix_all(text: &str, file_name: &str) -> Result<String, String> {
+    let result = autocorrect::format_for(text, file_name);
+    if !result.error.is_empty() {
+        return Err(result.error);
+    }
+    Ok(result.out)
+}
+
+/// Applies a single correction. The reported text is looked up on its line
+/// rather than trusted by column, and `None` means it is no longer there.
+pub fn fix_one(text: &str, issue: &Issue) -> Option<String> {
+    if issue.old.is_empty() {
+        return None;
+    }
+    let line_start = match issue.line {
+        0 | 1 => 0,
+        line => text.match_indices('\n').nth(line - 2)?.0 + 1,
+    };
+    let line_end = text[line_start..].find('\n').map_or(text.len(), |ix| line_start + ix);
+    let start = line_start + text[line_start..line_end].find(&issue.old)?;
+    let mut fixed = String::with_capacity(text.len() + issue.new.len());
+    fixed.push_str(&text[..start]);
+    fixed.push_str(&issue.new);
+    fixed.push_str(&text[start + issue.old.len()..]);
+    Some(fixed)
+}
+
+/// Writes `fixed` over `path`, unless the file no longer holds the
+/// `original` text the fix was made from.
+pub fn write_fixed(path: &Path, original: &str, fixed: &str) -> Result<(), String> {
+    let current = fs::read(path).map_err(|err| format!("Failed to read file: {err}"))?;
+    if current != original.as_bytes() {
+        return Err("The file changed on disk, check again before fixing".to_string());
+    }
+    fs::write(path, fixed).map_err(|err| format!("Failed to save: {err}"))
+}
+
+/// Runs autocorrect over every file it supports under `dir`, except those
+/// `skip` rejects. Nothing is written; the changes come back for review.
+/// Symlinks are not followed, so a link loop can't trap the walk.
+pub fn check_dir(dir: &Path, skip: &dyn Fn(&Path) -> bool, cancelled: &AtomicBool) -> BatchReport {
+    let mut report = BatchReport::default();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                report.errors.push((dir, err.to_string()));
+                continue;
+            }
+        };
+        let mut files = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if skip(&path) {
+                continue;
+            }
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => dirs.push(path),
+                Ok(metadata) if metadata.is_file() && metadata.len() <= MAX_FILE_LEN && is_supported(&path) => {
+                    files.push(path)
+                }
+                _ => {}
+            }
+        }
+        for path in files {
+            if cancelled.load(Ordering::Relaxed) {
+                return report;
+            }
+            let text = match fs::read(&path).map(String::from_utf8) {
+                Ok(Ok(text)) => text,
+                // Not UTF-8, so nothing autocorrect can work on.
+                Ok(Err(_)) => continue,
+                Err(err) => {
+                    report.errors.push((path, err.to_string()));
+                    continue;
+                }
+            };
+            report.checked += 1;
+            let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+            let result = lint(&text, &file_name).and_then(|issues| match issues.len() {
+                0 => Ok(None),
+                count => fix_all(&text, &file_name).map(|fixed| Some((count, fixed))),
+            });
+            match result {
+                Ok(Some((issues, fixed))) => {
+                    report.issues += issues;
+                    if fixed != text {
+                        report.changes.push(FileChange {
+                            path,
+                            original: text,
+                            fixed,
+                            issues,
+                        });
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => report.errors.push((path, err)),
+            }
+        }
+    }
+    report.changes.sort_by(|a, b| a.path.cmp(&b.path));
+    report
+}
+
+fn is_supported(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        autocorrect::is_support_type(&autocorrect::get_file_extension(&name.to_string_lossy()))
+    })
+}
+
+/// A line diff of `old` and `new` with long unchanged stretches folded.
+/// Lines come without their endings, and the final newline doesn't add an
+/// empty line.
+pub fn diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let lines = TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| {
+            let line = change.value().trim_end_matches(['\r', '\n']).to_string();
+            match change.tag() {
+                ChangeTag::Delete => DiffLine::Removed(line),
+                ChangeTag::Insert => DiffLine::Added(line),
+                ChangeTag::Equal => DiffLine::Context(line),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut folded = Vec::new();
+    let mut ix = 0;
+    while ix < lines.len() {
+        if !matches!(lines[ix], DiffLine::Context(_)) {
+            folded.push(lines[ix].clone());
+            ix += 1;
+            continue;
+        }
+        let end = lines[ix..]
+            .iter()
+            .position(|line| !matches!(line, DiffLine::Context(_)))
+            .map_or(lines.len(), |len| ix + len);
+        let before = if ix == 0 { 0 } else { DIFF_CONTEXT };
+        let after = if end == lines.len() { 0 } else { DIFF_CONTEXT };
+        if end - ix > before + after {
+            folded.extend_from_slice(&lines[ix..ix + before]);
+            folded.push(DiffLine::Skipped(end - ix - before - after));
+            folded.extend_from_slice(&lines[end - after..end]);
+        } else {
+            folded.extend_from_slice(&lines[ix..end]);
+        }
+        ix = end;
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixes_one_issue_and_folds_the_diff() {
+        let text = (1..=10).map(|ix| format!("line {ix}\n")).collect::<String>() + "使用Rust编写\n";
+        let issue = Issue {
+            line: 11,
+            col: 1,
+            old: "使用Rust编写".into(),
+            new: "使用 Rust 编写".into(),
+        };
+        let fixed = fix_one(&text, &issue).unwrap();
+        assert!(fixed.ends_with("line 10\n使用 Rust 编写\n"));
+        assert_eq!(fix_one(&fixed, &issue), None);
+
+        let diff = diff(&text, &fixed);
+        assert_eq!(diff[0], DiffLine::Skipped(7));
+        assert_eq!(diff[1], DiffLine::Context("line 8".into()));
+        assert_eq!(
+            diff[4..],
+            [DiffLine::Removed("使用Rust编写".into()), DiffLine::Added("使用 Rust 编写".into())]
+        );
+        assert_eq!(
+            super::diff("a\r\nb\r\n", "a\r\nc\r\n"),
+            [
+                DiffLine::Context("a".into()),
+                DiffLine::Removed("b".into()),
+                DiffLine::Added("c".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_a_directory_for_review() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("docs")).unwrap();
+        std::fs::create_dir(dir.path().join("vendor")).unwrap();
+        std::fs::write(dir.path().join("docs/guide.md"), "使用Rust编写\n").unwrap();
+        std::fs::write(dir.path().join("docs/clean.md"), "使用 Rust 编写\n").unwrap();
+        std::fs::write(dir.path().join("vendor/lib.md"), "使用Rust编写\n").unwrap();
+
+        let vendor = dir.path().join("vendor");
+        let report = check_dir(dir.path(), &|path| path.starts_with(&vendor), &AtomicBool::new(false));
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.changes.len(), 1);
+        let change = &report.changes[0];
+        assert_eq!(change.path, dir.path().join("docs/guide.md"));
+        assert_eq!(change.fixed, "使用 Rust 编写\n");
+
+        std::fs::write(&change.path, "edited elsewhere\n").unwrap();
+        assert!(write_fixed(&change.path, &change.original, &change.fixed).is_err());
+        std::fs::write(&change.path, &change.original).unwrap();
+        write_fixed(&change.path, &change.original, &change.fixed).unwrap();
+        assert_eq!(std::fs::read_to_string(&change.path).unwrap(), change.fixed);
+    }
+}
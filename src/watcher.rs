@@ -0,0 +1,165 @@
+use futures::channel::mpsc::{self, UnboundedReceiver};
+use futures::{FutureExt, Stream, StreamExt};
+use gpui::BackgroundExecutor;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FsChange {
+    Added(PathBuf),
+    Removed(PathBuf),
+    Renamed(PathBuf, PathBuf),
+}
+
+/// Watches `root` without descending into it; folders are added with
+/// [`watch_dir`] once the tree has read them, so opening a huge directory
+/// doesn't register thousands of watches up front. The watcher stops when the
+/// returned `RecommendedWatcher` is dropped.
+pub fn watch(root: &Path) -> notify::Result<(RecommendedWatcher, UnboundedReceiver<Vec<FsChange>>)> {
+    let (mut watcher, rx) = channel_watcher(false)?;
+    watch_dir(&mut watcher, root)?;
+    Ok((watcher, rx))
+}
+
+/// Watches everything under `root` at once. Files whose contents changed are
+/// reported as [`FsChange::Added`] as well, for views that care about sizes
+/// and dates rather than just names.
+pub fn watch_tree(root: &Path) -> notify::Result<(RecommendedWatcher, UnboundedReceiver<Vec<FsChange>>)> {
+    let (mut watcher, rx) = channel_watcher(true)?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    Ok((watcher, rx))
+}
+
+fn channel_watcher(contents: bool) -> notify::Result<(RecommendedWatcher, UnboundedReceiver<Vec<FsChange>>)> {
+    let (tx, rx) = mpsc::unbounded();
+    let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        match event {
+            Ok(event) => {
+                let changes = changes_for(event, contents);
+                if !changes.is_empty()
+                    && let Err(err) = tx.unbounded_send(changes)
+                {
+                    eprintln!("File watcher channel closed: {err}");
+                }
+            }
+            Err(err) => eprintln!("File watcher error: {err}"),
+        }
+    })?;
+    Ok((watcher, rx))
+}
+
+pub fn watch_dir(watcher: &mut RecommendedWatcher, dir: &Path) -> notify::Result<()> {
+    watcher.watch(dir, RecursiveMode::NonRecursive)
+}
+
+fn changes_for(event: Event, contents: bool) -> Vec<FsChange> {
+    let mut paths = event
+        .paths
+        .into_iter()
+        .filter(|path| !path.components().any(|c| c.as_os_str() == ".git"));
+    match event.kind {
+        EventKind::Create(_) => paths.map(FsChange::Added).collect(),
+        EventKind::Remove(_) => paths.map(FsChange::Removed).collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => match (paths.next(), paths.next()) {
+            (Some(from), Some(to)) => vec![FsChange::Renamed(from, to)],
+            (Some(path), None) => vec![FsChange::Added(path)],
+            _ => Vec::new(),
+        },
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => paths.map(FsChange::Removed).collect(),
+        EventKind::Modify(ModifyKind::Name(_)) => paths.map(FsChange::Added).collect(),
+        EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any) if contents => paths.map(FsChange::Added).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Waits for the next burst of changes and returns it coalesced, or `None`
+/// once the watcher is gone. Build scripts touch many files at once, so the
+/// burst has to go quiet first; that way it is handled once per burst.
+pub async fn next_burst(
+    changes: &mut (impl Stream<Item = Vec<FsChange>> + Unpin),
+    executor: &BackgroundExecutor,
+) -> Option<Vec<FsChange>> {
+    let mut batch = changes.next().await?;
+    loop {
+        executor.timer(DEBOUNCE).await;
+        let mut drained = false;
+        while let Some(Some(more)) = changes.next().now_or_never() {
+            batch.extend(more);
+            drained = true;
+        }
+        if !drained {
+            return Some(coalesce(batch));
+        }
+    }
+}
+
+/// Collapses a debounced burst into the changes that still hold on disk: each
+/// path is reported once, as it stands after the burst, however many events
+/// it received.
+pub fn coalesce(changes: Vec<FsChange>) -> Vec<FsChange> {
+    let exists = |path: &Path| path.symlink_metadata().is_ok();
+    let mut seen = HashSet::new();
+    let mut result = Vec::new();
+    for change in changes {
+        let paths = match change {
+            FsChange::Renamed(from, to) if !exists(&from) && exists(&to) => {
+                if !seen.contains(&from) && !seen.contains(&to) {
+                    seen.insert(from.clone());
+                    seen.insert(to.clone());
+                    result.push(FsChange::Renamed(from, to));
+                }
+                continue;
+            }
+            FsChange::Renamed(from, to) => vec![from, to],
+            FsChange::Added(path) | FsChange::Removed(path) => vec![path],
+        };
+        for path in paths {
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            if exists(&path) {
+                result.push(FsChange::Added(path));
+            } else {
+                result.push(FsChange::Removed(path));
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn coalesce_reports_final_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = dir.path().join("kept.txt");
+        let gone = dir.path().join("gone.txt");
+        let renamed = dir.path().join("renamed.txt");
+        fs::write(&kept, "").unwrap();
+        fs::write(&renamed, "").unwrap();
+
+        let changes = coalesce(vec![
+            FsChange::Added(gone.clone()),
+            FsChange::Added(kept.clone()),
+            FsChange::Removed(gone.clone()),
+            FsChange::Renamed(dir.path().join("old.txt"), renamed.clone()),
+            FsChange::Added(kept.clone()),
+        ]);
+        assert_eq!(
+            changes,
+            vec![
+                FsChange::Removed(gone),
+                FsChange::Added(kept),
+                FsChange::Renamed(dir.path().join("old.txt"), renamed),
+            ]
+        );
+    }
+}
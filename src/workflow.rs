@@ -0,0 +1,212 @@
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+
+/// A command as written in the config, e.g.
+/// `{"name": "PDF", "command": "pandoc {file} -o {stem}.pdf"}`.
+///
+/// `{file}`, `{name}`, `{stem}`, `{ext}` and `{dir}` stand for the selected
+/// file, its name with and without the extension, the extension, and the
+/// folder it is in (or the selected folder itself).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CommandDef {
+    pub name: String,
+    /// Run by the shell, so pipes and `&&` work.
+    pub command: String,
+    /// Where to run it; `{dir}` if left out.
+    #[serde(default)]
+    pub cwd: Option<String>,
+}
+
+/// One line the command wrote.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub text: String,
+    pub stderr: bool,
+}
+
+/// `template` with the placeholders filled in for `target`, each value
+/// passed through `escape`.
+pub fn expand(template: &str, target: &Path, escape: impl Fn(&str) -> String) -> String {
+    let dir = if target.is_dir() { Some(target) } else { target.parent() };
+    let part = |part: Option<&std::ffi::OsStr>| part.map(|part| part.to_string_lossy().to_string()).unwrap_or_default();
+    let values = [
+        ("{file}", target.to_string_lossy().to_string()),
+        ("{name}", part(target.file_name())),
+        ("{stem}", part(target.file_stem())),
+        ("{ext}", part(target.extension())),
+        ("{dir}", dir.map(|dir| dir.to_string_lossy().to_string()).unwrap_or_default()),
+    ];
+    let mut expanded = String::new();
+    let mut rest = template;
+    'outer: while !rest.is_empty() {
+        for (key, value) in &values {
+            if let Some(after) = rest.strip_prefix(key) {
+                expanded.push_str(&escape(value));
+                rest = after;
+                continue 'outer;
+            }
+        }
+        let mut chars = rest.chars();
+        expanded.extend(chars.next());
+        rest = chars.as_str();
+    }
+    expanded
+}
+
+/// Starts `def` on `target`. Lines from stdout and stderr arrive on the
+/// receiver, which closes once the command has closed both.
+pub fn spawn(def: &CommandDef, target: &Path) -> io::Result<(Child, UnboundedReceiver<Line>)> {
+    let command = expand(&def.command, target, quote);
+    let cwd = PathBuf::from(expand(def.cwd.as_deref().unwrap_or("{dir}"), target, str::to_string));
+    let mut child = shell(&command)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let (tx, rx) = mpsc::unbounded();
+    if let Some(stdout) = child.stdout.take() {
+        forward(stdout, false, tx.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward(stderr, true, tx);
+    }
+    Ok((child, rx))
+}
+
+/// Sends each line of `output` until it closes. Bytes that aren't UTF-8 are
+/// replaced rather than ending the read, which would leave the command
+/// blocked on a full pipe.
+fn forward(output: impl Read + Send + 'static, stderr: bool, tx: UnboundedSender<Line>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(output);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) => break,
+                Ok(_) => {
+                    let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+                    let line = line.strip_suffix(b"\r").unwrap_or(line);
+                    let text = String::from_utf8_lossy(line).into_owned();
+                    if tx.unbounded_send(Line { text, stderr }).is_err() {
+                        break;
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+    });
+}
+
+/// Stops the command along with everything it started, which could
+/// otherwise keep running and hold its output open.
+#[cfg(unix)]
+pub fn kill(child: &mut Child) -> io::Result<()> {
+    let group = libc::pid_t::try_from(child.id()).map_err(io::Error::other)?;
+    // SAFETY: kill has no memory effects; the group is the one `shell` made
+    // for this child, and the child hasn't been reaped yet.
+    if unsafe { libc::kill(-group, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+pub fn kill(child: &mut Child) -> io::Result<()> {
+    child.kill()
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    use std::os::unix::process::CommandExt;
+
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command).process_group(0);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(unix)]
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(not(unix))]
+fn quote(value: &str) -> String {
+    format!("\"{value}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use std::fs;
+
+    #[cfg(unix)]
+    #[test]
+    fn expands_and_runs_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("it's.md");
+        fs::write(&file, "# Title").unwrap();
+
+        let expanded = expand("pandoc {file} -o {stem}.pdf {unknown}", &file, quote);
+        assert_eq!(
+            expanded,
+            format!("pandoc '{}/it'\\''s.md' -o 'it'\\''s'.pdf {{unknown}}", dir.path().display())
+        );
+        assert_eq!(expand("{dir}", dir.path(), str::to_string), dir.path().to_string_lossy());
+        assert_eq!(expand("{name} {ext}", &file, str::to_string), "it's.md md");
+
+        let def = CommandDef {
+            name: "Show".to_string(),
+            command: "cat {name}; echo oops >&2; exit 3".to_string(),
+            cwd: None,
+        };
+        let (mut child, output) = spawn(&def, &file).unwrap();
+        let mut lines = futures::executor::block_on(output.collect::<Vec<_>>());
+        lines.sort_by_key(|line| line.stderr);
+        assert_eq!(
+            lines,
+            [
+                Line { text: "# Title".to_string(), stderr: false },
+                Line { text: "oops".to_string(), stderr: true },
+            ]
+        );
+        assert_eq!(child.wait().unwrap().code(), Some(3));
+
+        let def = CommandDef {
+            name: "Latin-1".to_string(),
+            command: "printf 'caf\\351\\nnext\\n'".to_string(),
+            cwd: None,
+        };
+        let (mut child, output) = spawn(&def, &file).unwrap();
+        let lines = futures::executor::block_on(output.map(|line| line.text).collect::<Vec<_>>());
+        assert_eq!(lines, ["caf\u{fffd}", "next"]);
+        assert_eq!(child.wait().unwrap().code(), Some(0));
+
+        // The sleep the shell started holds the output open until the whole
+        // group is gone.
+        let def = CommandDef {
+            name: "Slow".to_string(),
+            command: "sleep 30 && echo done".to_string(),
+            cwd: None,
+        };
+        let (mut child, output) = spawn(&def, &file).unwrap();
+        kill(&mut child).unwrap();
+        assert!(futures::executor::block_on(output.collect::<Vec<_>>()).is_empty());
+        assert!(child.wait().unwrap().code().is_none());
+    }
+}